@@ -0,0 +1,421 @@
+use crate::document::PdfDocument;
+use crate::page::Page;
+use crate::primitives::{Dictionary, Name, PdfStream, Primitives, Ref};
+use crate::serializer::format_real;
+use crate::date::PdfDate;
+use crate::utils::encode_text;
+use crate::error::Error;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnnotationType {
+    Link,
+    Text,
+    Highlight,
+    Underline,
+    StrikeOut,
+    Squiggly,
+    Square,
+    Circle,
+    Ink,
+    FreeText,
+    Stamp,
+    Popup,
+    Widget,
+    // Any other `/Subtype`, as written.
+    Other(String),
+}
+
+impl AnnotationType {
+    fn from_name(name: &[u8]) -> AnnotationType {
+        match name {
+            b"Link" => AnnotationType::Link,
+            b"Text" => AnnotationType::Text,
+            b"Highlight" => AnnotationType::Highlight,
+            b"Underline" => AnnotationType::Underline,
+            b"StrikeOut" => AnnotationType::StrikeOut,
+            b"Squiggly" => AnnotationType::Squiggly,
+            b"Square" => AnnotationType::Square,
+            b"Circle" => AnnotationType::Circle,
+            b"Ink" => AnnotationType::Ink,
+            b"FreeText" => AnnotationType::FreeText,
+            b"Stamp" => AnnotationType::Stamp,
+            b"Popup" => AnnotationType::Popup,
+            b"Widget" => AnnotationType::Widget,
+            other => AnnotationType::Other(String::from_utf8_lossy(other).into_owned()),
+        }
+    }
+}
+
+// The `/C` entry, its number of components picks the color space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnnotationColor {
+    Transparent,
+    Gray(f64),
+    Rgb([f64; 3]),
+    Cmyk([f64; 4]),
+}
+
+impl AnnotationColor {
+    fn from_components(c: &[f64]) -> Option<AnnotationColor> {
+        match *c {
+            [] => Some(AnnotationColor::Transparent),
+            [g] => Some(AnnotationColor::Gray(g)),
+            [r, g, b] => Some(AnnotationColor::Rgb([r, g, b])),
+            [c, m, y, k] => Some(AnnotationColor::Cmyk([c, m, y, k])),
+            _ => None,
+        }
+    }
+
+    fn components(&self) -> Vec<f64> {
+        match self {
+            AnnotationColor::Transparent => Vec::new(),
+            AnnotationColor::Gray(g) => vec![*g],
+            AnnotationColor::Rgb(rgb) => rgb.to_vec(),
+            AnnotationColor::Cmyk(cmyk) => cmyk.to_vec(),
+        }
+    }
+
+    // The fill color operator for content streams, `None` for transparent.
+    fn fill_operator(&self) -> Option<String> {
+        let operator = match self {
+            AnnotationColor::Transparent => return None,
+            AnnotationColor::Gray(_) => "g",
+            AnnotationColor::Rgb(_) => "rg",
+            AnnotationColor::Cmyk(_) => "k",
+        };
+        let components: Vec<String> = self.components().into_iter().map(format_real).collect();
+        Some(format!("{} {}", components.join(" "), operator))
+    }
+}
+
+// The appearance streams of the `/AP` entry, already picked by the
+// appearance state `/AS` where there are several.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Appearances {
+    pub normal: Option<PdfStream>,
+    pub rollover: Option<PdfStream>,
+    pub down: Option<PdfStream>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    // `None` for annotations stored directly in the `/Annots` array.
+    pub reference: Option<Ref>,
+    pub subtype: AnnotationType,
+    // Normalized so that the lower left corner comes first.
+    pub rect: [f64; 4],
+    pub contents: Option<String>,
+    // The `/T` entry, the author of markup annotations.
+    pub author: Option<String>,
+    pub color: Option<AnnotationColor>,
+    // One entry per quadrilateral of text markup annotations, as x1 y1 ...
+    // x4 y4 in the order of the file.
+    pub quad_points: Vec<[f64; 8]>,
+    pub flags: u32,
+    pub modified: Option<PdfDate>,
+    pub appearances: Appearances,
+    // The annotation dictionary as stored in the file.
+    pub dict: Dictionary,
+}
+
+// Settings for annotations created by `add_highlight` and `add_note`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnnotationOptions {
+    pub contents: Option<String>,
+    pub author: Option<String>,
+    pub color: [f64; 3],
+}
+
+impl Default for AnnotationOptions {
+    fn default() -> Self {
+        AnnotationOptions {
+            contents: None,
+            author: None,
+            // The yellow of a highlighter.
+            color: [1.0, 1.0, 0.0],
+        }
+    }
+}
+
+// Annotation flags from the `/F` entry.
+const FLAG_PRINT: i64 = 4;
+const FLAG_NO_ZOOM: i64 = 8;
+const FLAG_NO_ROTATE: i64 = 16;
+
+// Size of the generated note icon.
+const NOTE_SIZE: f64 = 20.0;
+
+impl Page {
+    pub fn annotations(&self, doc: &PdfDocument) -> Result<Vec<Annotation>, Error> {
+        let annots = match self.dict.get(&b"Annots"[..]) {
+            Some(annots) => doc.resolve(annots)?,
+            None => return Ok(Vec::new()),
+        };
+        let mut annotations = Vec::new();
+        for annot in annots.get_array().map_or(&[][..], |a| &a[..]) {
+            if let Primitives::Dict(dict) = doc.resolve(annot)? {
+                annotations.push(read_annotation(doc, annot.get_ref(), dict)?);
+            }
+        }
+        Ok(annotations)
+    }
+}
+
+fn read_annotation(doc: &PdfDocument, reference: Option<Ref>, dict: Dictionary) -> Result<Annotation, Error> {
+    let numbers = |obj: Primitives| -> Vec<f64> {
+        obj.get_array().map_or(Vec::new(), |a| a.iter().filter_map(|n| n.get_real()).collect())
+    };
+    let rect = match numbers(doc.get(&dict, "Rect")?)[..] {
+        [x1, y1, x2, y2] => [x1.min(x2), y1.min(y2), x1.max(x2), y1.max(y2)],
+        _ => [0.0; 4],
+    };
+    let quad_points = numbers(doc.get(&dict, "QuadPoints")?)
+        .chunks_exact(8)
+        .map(|q| [q[0], q[1], q[2], q[3], q[4], q[5], q[6], q[7]])
+        .collect();
+
+    let color = match doc.get(&dict, "C")? {
+        c @ Primitives::Array(_) => AnnotationColor::from_components(&numbers(c)),
+        _ => None,
+    };
+
+    let state = doc.get(&dict, "AS")?;
+    let ap = doc.get(&dict, "AP")?;
+    let appearance = |key: &str| -> Result<Option<PdfStream>, Error> {
+        let entry = match ap.get(key) {
+            Some(entry) => doc.resolve(entry)?,
+            None => return Ok(None),
+        };
+        let entry = match (&entry, state.get_name()) {
+            (Primitives::Dict(states), Some(state)) => match states.get(state) {
+                Some(stream) => doc.resolve(stream)?,
+                None => return Ok(None),
+            },
+            _ => entry,
+        };
+        Ok(entry.get_stream().cloned())
+    };
+
+    Ok(Annotation {
+        reference: reference,
+        subtype: AnnotationType::from_name(doc.get(&dict, "Subtype")?.get_name().unwrap_or(b"")),
+        rect: rect,
+        contents: doc.get(&dict, "Contents")?.get_text(),
+        author: doc.get(&dict, "T")?.get_text(),
+        color: color,
+        quad_points: quad_points,
+        flags: doc.get(&dict, "F")?.get_integer().unwrap_or(0) as u32,
+        modified: doc.get(&dict, "M")?.get_text().and_then(|m| PdfDate::parse(&m)),
+        appearances: Appearances {
+            normal: appearance("N")?,
+            rollover: appearance("R")?,
+            down: appearance("D")?,
+        },
+        dict: dict,
+    })
+}
+
+impl PdfDocument {
+    pub fn annotations(&self, page: usize) -> Result<Vec<Annotation>, Error> {
+        self.page(page)?.annotations(self)
+    }
+
+    // Adds a highlight over the given quadrilaterals (x1 y1 x2 y2 x3 y3 x4
+    // y4: upper left, upper right, lower left, lower right, like text
+    // markup in other writers), with an appearance stream that multiplies
+    // the color over the text below.
+    pub fn add_highlight(&mut self, page: usize, quad_points: &[[f64; 8]], options: &AnnotationOptions) -> Result<Ref, Error> {
+        if quad_points.is_empty() {
+            return Err(Error::InvalidArgument("a highlight needs at least one quadrilateral"));
+        }
+        let xs = quad_points.iter().flat_map(|q| q.iter().step_by(2));
+        let ys = quad_points.iter().flat_map(|q| q.iter().skip(1).step_by(2));
+        let rect = [
+            xs.clone().cloned().fold(f64::INFINITY, f64::min),
+            ys.clone().cloned().fold(f64::INFINITY, f64::min),
+            xs.cloned().fold(f64::NEG_INFINITY, f64::max),
+            ys.cloned().fold(f64::NEG_INFINITY, f64::max),
+        ];
+
+        let color = AnnotationColor::Rgb(options.color);
+        let mut content = format!("/GS0 gs\n{}\n", color.fill_operator().unwrap());
+        for q in quad_points {
+            let point = |i: usize| format!("{} {}", format_real(q[2 * i]), format_real(q[2 * i + 1]));
+            // Lower left, lower right, upper right, upper left.
+            content.push_str(&format!("{} m\n{} l\n{} l\n{} l\nf\n", point(2), point(3), point(1), point(0)));
+        }
+        let mut gs = Dictionary::new();
+        gs.insert(Name::from("Type"), Primitives::name(b"ExtGState".to_vec()));
+        gs.insert(Name::from("BM"), Primitives::name(b"Multiply".to_vec()));
+        let mut ext_g_state = Dictionary::new();
+        ext_g_state.insert(Name::from("GS0"), Primitives::Dict(gs));
+        let mut resources = Dictionary::new();
+        resources.insert(Name::from("ExtGState"), Primitives::Dict(ext_g_state));
+
+        let mut dict = markup_dict("Highlight", rect, &color, options);
+        dict.insert(Name::from("F"), Primitives::Int(FLAG_PRINT));
+        let quads = quad_points.iter().flat_map(|q| q.iter().map(|&n| Primitives::Real(n))).collect();
+        dict.insert(Name::from("QuadPoints"), Primitives::Array(quads));
+        self.add_annotation(page, dict, rect, resources, content)
+    }
+
+    // Adds a sticky note with its upper left corner at (`x`, `y`).
+    pub fn add_note(&mut self, page: usize, x: f64, y: f64, options: &AnnotationOptions) -> Result<Ref, Error> {
+        let rect = [x, y - NOTE_SIZE, x + NOTE_SIZE, y];
+        let color = AnnotationColor::Rgb(options.color);
+        // A filled sheet with a folded corner and three lines of text.
+        let (x0, y0, x1, y1) = (rect[0] + 0.5, rect[1] + 0.5, rect[2] - 0.5, rect[3] - 0.5);
+        let fold = 5.0;
+        let mut content = format!("{}\n0 G\n1 w\n", color.fill_operator().unwrap());
+        content.push_str(&format!(
+            "{} {} m\n{} {} l\n{} {} l\n{} {} l\n{} {} l\nh\nB\n",
+            format_real(x0), format_real(y0),
+            format_real(x1), format_real(y0),
+            format_real(x1), format_real(y1 - fold),
+            format_real(x1 - fold), format_real(y1),
+            format_real(x0), format_real(y1),
+        ));
+        content.push_str(&format!(
+            "{} {} m\n{} {} l\n{} {} l\nS\n",
+            format_real(x1 - fold), format_real(y1),
+            format_real(x1 - fold), format_real(y1 - fold),
+            format_real(x1), format_real(y1 - fold),
+        ));
+        for i in 1..=3 {
+            let line_y = y1 - fold - 3.5 * i as f64;
+            content.push_str(&format!(
+                "{} {} m\n{} {} l\nS\n",
+                format_real(x0 + 3.0), format_real(line_y),
+                format_real(x1 - 3.0), format_real(line_y),
+            ));
+        }
+
+        let mut dict = markup_dict("Text", rect, &color, options);
+        // Notes keep their size and orientation when the page is zoomed or
+        // rotated.
+        dict.insert(Name::from("F"), Primitives::Int(FLAG_PRINT | FLAG_NO_ZOOM | FLAG_NO_ROTATE));
+        dict.insert(Name::from("Name"), Primitives::name(b"Note".to_vec()));
+        dict.insert(Name::from("Open"), Primitives::Bool(false));
+        self.add_annotation(page, dict, rect, Dictionary::new(), content)
+    }
+
+    // Stores the annotation with a normal appearance drawn by `content` in
+    // page space, and appends it to the page's `/Annots`.
+    fn add_annotation(&mut self, page: usize, mut dict: Dictionary, rect: [f64; 4], resources: Dictionary, content: String) -> Result<Ref, Error> {
+        let page = self.page(page)?;
+        let mut form = Dictionary::new();
+        form.insert(Name::from("Type"), Primitives::name(b"XObject".to_vec()));
+        form.insert(Name::from("Subtype"), Primitives::name(b"Form".to_vec()));
+        form.insert(Name::from("BBox"), Primitives::Array(rect.iter().map(|&n| Primitives::Real(n)).collect()));
+        form.insert(Name::from("Resources"), Primitives::Dict(resources));
+        let appearance = self.add_object(Primitives::Stream(PdfStream::new(form, content.into_bytes())));
+        let mut ap = Dictionary::new();
+        ap.insert(Name::from("N"), appearance.into());
+        dict.insert(Name::from("AP"), Primitives::Dict(ap));
+        dict.insert(Name::from("P"), page.reference.into());
+        let annot = self.add_object(Primitives::Dict(dict));
+
+        let mut page_dict = page.dict;
+        match page_dict.get(&b"Annots"[..]).cloned() {
+            // A shared array is updated in place.
+            Some(Primitives::Ref(num, gen)) => {
+                let r = Ref::new(num, gen);
+                let mut annots = self.fetch(r)?.get_array().cloned().unwrap_or_default();
+                annots.push(annot.into());
+                self.replace_object(r, Primitives::Array(annots))?;
+            }
+            annots => {
+                let mut annots = annots.and_then(|a| a.get_array().cloned()).unwrap_or_default();
+                annots.push(annot.into());
+                page_dict.insert(Name::from("Annots"), Primitives::Array(annots));
+                self.replace_object(page.reference, Primitives::Dict(page_dict))?;
+            }
+        }
+        Ok(annot)
+    }
+}
+
+// Entries shared by the created markup annotations.
+fn markup_dict(subtype: &str, rect: [f64; 4], color: &AnnotationColor, options: &AnnotationOptions) -> Dictionary {
+    let mut dict = Dictionary::new();
+    dict.insert(Name::from("Type"), Primitives::name(b"Annot".to_vec()));
+    dict.insert(Name::from("Subtype"), Primitives::name(subtype.as_bytes().to_vec()));
+    dict.insert(Name::from("Rect"), Primitives::Array(rect.iter().map(|&n| Primitives::Real(n)).collect()));
+    dict.insert(Name::from("C"), Primitives::Array(color.components().into_iter().map(Primitives::Real).collect()));
+    let now = Primitives::Str(PdfDate::now().to_pdf_string().into_bytes());
+    dict.insert(Name::from("M"), now.clone());
+    dict.insert(Name::from("CreationDate"), now);
+    if let Some(contents) = &options.contents {
+        dict.insert(Name::from("Contents"), Primitives::Str(encode_text(contents)));
+    }
+    if let Some(author) = &options.author {
+        dict.insert(Name::from("T"), Primitives::Str(encode_text(author)));
+    }
+    dict
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::PdfDocument;
+    use crate::writer::SaveOptions;
+    use super::{AnnotationColor, AnnotationOptions, AnnotationType};
+
+    #[test]
+    fn read_annotations() {
+        let doc = PdfDocument::open("tests/examples/PDF_sample.pdf").unwrap();
+        let annotations = doc.annotations(0).unwrap();
+        assert_eq!(annotations.len(), 2);
+        assert_eq!(annotations[0].subtype, AnnotationType::Link);
+        assert_eq!(annotations[0].rect, [220.67, 493.27, 374.63, 523.97]);
+        assert_eq!(annotations[0].flags, 4);
+        assert!(annotations[0].reference.is_some());
+        assert!(annotations[0].appearances.normal.is_none());
+        assert!(doc.annotations(0).unwrap().iter().all(|a| a.color.is_none()));
+    }
+
+    #[test]
+    fn create_highlight_and_note() {
+        let mut doc = PdfDocument::open("tests/examples/sample.pdf").unwrap();
+        let options = AnnotationOptions {
+            contents: Some("Important".to_string()),
+            author: Some("Reviewer".to_string()),
+            ..AnnotationOptions::default()
+        };
+        let quads = [[100.0, 720.0, 300.0, 720.0, 100.0, 700.0, 300.0, 700.0]];
+        doc.add_highlight(1, &quads, &options).unwrap();
+        let note = AnnotationOptions {
+            color: [0.0, 0.5, 1.0],
+            ..AnnotationOptions::default()
+        };
+        doc.add_note(1, 50.0, 750.0, &note).unwrap();
+        assert!(doc.add_highlight(1, &[], &options).is_err());
+
+        let mut data = Vec::new();
+        doc.write_to(&mut data, &SaveOptions::default()).unwrap();
+        let saved = PdfDocument::load_data(data).unwrap();
+        assert!(saved.annotations(0).unwrap().is_empty());
+        let annotations = saved.annotations(1).unwrap();
+        assert_eq!(annotations.len(), 2);
+
+        let highlight = &annotations[0];
+        assert_eq!(highlight.subtype, AnnotationType::Highlight);
+        assert_eq!(highlight.rect, [100.0, 700.0, 300.0, 720.0]);
+        assert_eq!(highlight.quad_points, quads);
+        assert_eq!(highlight.contents.as_deref(), Some("Important"));
+        assert_eq!(highlight.author.as_deref(), Some("Reviewer"));
+        assert_eq!(highlight.color, Some(AnnotationColor::Rgb([1.0, 1.0, 0.0])));
+        assert!(highlight.modified.is_some());
+        let appearance = highlight.appearances.normal.as_ref().unwrap().decode().unwrap();
+        assert_eq!(
+            String::from_utf8(appearance).unwrap(),
+            "/GS0 gs\n1 1 0 rg\n100 700 m\n300 700 l\n300 720 l\n100 720 l\nf\n"
+        );
+
+        let note = &annotations[1];
+        assert_eq!(note.subtype, AnnotationType::Text);
+        assert_eq!(note.rect, [50.0, 730.0, 70.0, 750.0]);
+        assert_eq!(note.flags, 28);
+        assert_eq!(note.color, Some(AnnotationColor::Rgb([0.0, 0.5, 1.0])));
+        assert!(note.appearances.normal.is_some());
+    }
+}
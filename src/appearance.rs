@@ -0,0 +1,271 @@
+use crate::document::PdfDocument;
+use crate::primitives::{Dictionary, Name, Primitives};
+use crate::lexer::Lexer;
+use crate::stream::Stream;
+use crate::serializer::{format_real, write_literal_string, write_name};
+use crate::error::Error;
+
+// Space between the widget border and the text.
+const PADDING: f64 = 2.0;
+// Height of a line of text relative to the font size.
+const LINE_FACTOR: f64 = 1.35;
+// Depth of the baseline below the middle of a line, relative to the font
+// size.
+const BASELINE_SHIFT: f64 = 0.25;
+// Auto sized multiline text and list boxes use this size at most.
+const MAX_AUTO_SIZE: f64 = 12.0;
+// Width of glyphs the font doesn't give a width for, in text space units.
+const DEFAULT_WIDTH: f64 = 0.5;
+// Background of the selected options of list boxes.
+const SELECTION_COLOR: &str = "0.6 0.756863 0.854902 rg";
+
+// The default appearance string `/DA` of a variable text field.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct DefaultAppearance {
+    // Key of the font in the `/DR` font resources.
+    pub font: Vec<u8>,
+    // Zero means the size is chosen to fit the widget.
+    pub size: f64,
+    // The color operator with its operands, e.g. "0 0 1 rg".
+    pub color: String,
+}
+
+impl DefaultAppearance {
+    // Takes the last `Tf` and color operator of the string, like readers
+    // do. Missing parts default to auto sized Helvetica in black.
+    pub fn parse(da: &str) -> DefaultAppearance {
+        let mut appearance = DefaultAppearance {
+            font: b"Helv".to_vec(),
+            size: 0.0,
+            color: String::from("0 g"),
+        };
+        let data = da.as_bytes().to_vec();
+        let mut lexer = Lexer::new(Stream::from_vec(&data));
+        let mut operands = Vec::new();
+        loop {
+            let obj = match lexer.get_obj() {
+                Ok(Primitives::EOF) | Err(_) => break,
+                Ok(obj) => obj,
+            };
+            let op = match obj {
+                Primitives::Cmd(op) => op,
+                operand => {
+                    operands.push(operand);
+                    continue;
+                }
+            };
+            match (&op[..], &operands[..]) {
+                (b"Tf", [.., Primitives::Name(font), size]) => {
+                    appearance.font = font.0.clone();
+                    appearance.size = size.get_real().unwrap_or(0.0).max(0.0);
+                }
+                (b"g", [.., _]) | (b"rg", [.., _, _, _]) | (b"k", [.., _, _, _, _]) => {
+                    let count = match &op[..] {
+                        b"g" => 1,
+                        b"rg" => 3,
+                        _ => 4,
+                    };
+                    let components: Vec<String> = operands[operands.len() - count..]
+                        .iter()
+                        .map(|c| format_real(c.get_real().unwrap_or(0.0)))
+                        .collect();
+                    appearance.color = format!("{} {}", components.join(" "), String::from_utf8_lossy(&op));
+                }
+                _ => (),
+            }
+            operands.clear();
+        }
+        appearance
+    }
+}
+
+// Glyph widths of a simple font, used to fit and align text. Fonts without
+// `/Widths`, like the standard 14 fonts, get an average width.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct FontMetrics {
+    first_char: i64,
+    // At font size 1, i.e. `/Widths` divided by 1000.
+    widths: Vec<f64>,
+    missing_width: f64,
+}
+
+impl FontMetrics {
+    pub fn new(doc: &PdfDocument, font: &Dictionary) -> Result<FontMetrics, Error> {
+        let widths = doc.get(font, "Widths")?;
+        let widths = widths.get_array().map_or(Vec::new(), |w| w.iter().map(|w| w.get_real().unwrap_or(0.0) / 1000.0).collect());
+        let missing_width = match doc.get(font, "FontDescriptor")? {
+            Primitives::Dict(descriptor) => doc.get(&descriptor, "MissingWidth")?.get_real().map(|w| w / 1000.0),
+            _ => None,
+        };
+        Ok(FontMetrics {
+            first_char: doc.get(font, "FirstChar")?.get_integer().unwrap_or(0),
+            widths: widths,
+            missing_width: missing_width.filter(|&w| w > 0.0).unwrap_or(DEFAULT_WIDTH),
+        })
+    }
+
+    // Width of `text` at font size 1.
+    pub fn width(&self, text: &[u8]) -> f64 {
+        text.iter()
+            .map(|&c| {
+                let index = c as i64 - self.first_char;
+                match self.widths.get(index.max(0) as usize) {
+                    Some(&w) if index >= 0 && w > 0.0 => w,
+                    _ => self.missing_width,
+                }
+            })
+            .sum()
+    }
+}
+
+impl Default for FontMetrics {
+    fn default() -> FontMetrics {
+        FontMetrics {
+            first_char: 0,
+            widths: Vec::new(),
+            missing_width: DEFAULT_WIDTH,
+        }
+    }
+}
+
+// Appearance fonts are simple fonts with a single byte encoding, so text
+// is encoded as Latin-1 and other characters become '?'.
+pub(crate) fn encode(text: &str) -> Vec<u8> {
+    text.chars().map(|c| if (c as u32) < 0x100 { c as u8 } else { b'?' }).collect()
+}
+
+// Content of a text field or combo box appearance of `width` by `height`.
+// Multiline text is wrapped at spaces, `quadding` is the `/Q` alignment.
+pub(crate) fn text_content(da: &DefaultAppearance, metrics: &FontMetrics, width: f64, height: f64, text: &str, quadding: i64, multiline: bool) -> Vec<u8> {
+    let text = encode(text);
+    let inner_width = (width - 2.0 * PADDING).max(0.0);
+    let inner_height = (height - 2.0 * PADDING).max(0.0);
+    let size = if da.size > 0.0 {
+        da.size
+    } else if multiline {
+        MAX_AUTO_SIZE.min(inner_height / LINE_FACTOR)
+    } else {
+        let text_width = metrics.width(&text);
+        let fit_height = inner_height / LINE_FACTOR;
+        if text_width > 0.0 { fit_height.min(inner_width / text_width) } else { fit_height }
+    };
+
+    let lines = if multiline {
+        wrap(&text, metrics, inner_width / size)
+    } else {
+        vec![text.iter().map(|&c| if c == b'\n' || c == b'\r' { b' ' } else { c }).collect()]
+    };
+    let line_height = size * LINE_FACTOR;
+    let mut placed = Vec::new();
+    for (i, line) in lines.into_iter().enumerate() {
+        let line_width = metrics.width(&line) * size;
+        let x = match quadding {
+            1 => (width - line_width) / 2.0,
+            2 => width - PADDING - line_width,
+            _ => PADDING,
+        };
+        // Single lines are centered vertically, multiple lines start at the
+        // top.
+        let middle = if multiline { height - PADDING - line_height * (i as f64 + 0.5) } else { height / 2.0 };
+        placed.push((x, middle - size * BASELINE_SHIFT, line));
+    }
+    field_content(da, width, height, size, "", &placed)
+}
+
+// Content of a list box appearance, all options from the top with the
+// selected ones highlighted.
+pub(crate) fn list_content(da: &DefaultAppearance, width: f64, height: f64, options: &[String], selected: &[usize]) -> Vec<u8> {
+    let size = if da.size > 0.0 { da.size } else { MAX_AUTO_SIZE.min((height - 2.0 * PADDING).max(0.0) / LINE_FACTOR) };
+    let line_height = size * LINE_FACTOR;
+    let mut highlights = String::new();
+    let mut placed = Vec::new();
+    for (i, option) in options.iter().enumerate() {
+        let top = height - PADDING - line_height * i as f64;
+        if selected.contains(&i) {
+            highlights.push_str(&format!(
+                "{}\n1 {} {} {} re f\n",
+                SELECTION_COLOR, format_real(top - line_height), format_real(width - 2.0), format_real(line_height),
+            ));
+        }
+        placed.push((PADDING, top - line_height / 2.0 - size * BASELINE_SHIFT, encode(option)));
+    }
+    field_content(da, width, height, size, &highlights, &placed)
+}
+
+// Wraps the text in the marked content readers replace when they
+// regenerate the appearance, clipped to the widget.
+fn field_content(da: &DefaultAppearance, width: f64, height: f64, size: f64, background: &str, lines: &[(f64, f64, Vec<u8>)]) -> Vec<u8> {
+    let mut content = format!(
+        "/Tx BMC\nq\n1 1 {} {} re W n\n{}BT\n",
+        format_real(width - 2.0), format_real(height - 2.0), background,
+    ).into_bytes();
+    write_name(&mut content, &Name(da.font.clone())).unwrap();
+    content.extend(format!(" {} Tf\n{}\n", format_real(size), da.color).into_bytes());
+    for (x, y, line) in lines {
+        content.extend(format!("1 0 0 1 {} {} Tm\n", format_real(*x), format_real(*y)).into_bytes());
+        write_literal_string(&mut content, line).unwrap();
+        content.extend_from_slice(b" Tj\n");
+    }
+    content.extend_from_slice(b"ET\nQ\nEMC\n");
+    content
+}
+
+// Breaks paragraphs into lines no wider than `max_width` at font size 1.
+// Words longer than a line get a line of their own.
+fn wrap(text: &[u8], metrics: &FontMetrics, max_width: f64) -> Vec<Vec<u8>> {
+    let mut lines = Vec::new();
+    let text: Vec<u8> = text.iter().filter(|&&c| c != b'\r').cloned().collect();
+    for paragraph in text.split(|&c| c == b'\n') {
+        let mut line: Vec<u8> = Vec::new();
+        for word in paragraph.split(|&c| c == b' ') {
+            let mut candidate = line.clone();
+            if !candidate.is_empty() {
+                candidate.push(b' ');
+            }
+            candidate.extend_from_slice(word);
+            if line.is_empty() || metrics.width(&candidate) <= max_width {
+                line = candidate;
+            } else {
+                lines.push(std::mem::replace(&mut line, word.to_vec()));
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{text_content, wrap, DefaultAppearance, FontMetrics};
+
+    #[test]
+    fn parse_default_appearance() {
+        let da = DefaultAppearance::parse("/Helv 0 Tf 0 g");
+        assert_eq!(da, DefaultAppearance { font: b"Helv".to_vec(), size: 0.0, color: "0 g".to_string() });
+        let da = DefaultAppearance::parse("0 0 1 rg /F1 10.5 Tf");
+        assert_eq!(da.font, b"F1");
+        assert_eq!(da.size, 10.5);
+        assert_eq!(da.color, "0 0 1 rg");
+        assert_eq!(DefaultAppearance::parse("").font, b"Helv");
+    }
+
+    #[test]
+    fn layout_text() {
+        let metrics = FontMetrics::default();
+        assert_eq!(metrics.width(b"abcd"), 2.0);
+        let lines = wrap(b"one two three\nfour", &metrics, 3.0);
+        assert_eq!(lines, [b"one".to_vec(), b"two".to_vec(), b"three".to_vec(), b"four".to_vec()]);
+
+        // Auto size fits the height: (20 - 4) / 1.35.
+        let da = DefaultAppearance::parse("/Helv 0 Tf 0 g");
+        let content = String::from_utf8(text_content(&da, &metrics, 200.0, 20.0, "Hi (there)", 0, false)).unwrap();
+        assert!(content.starts_with("/Tx BMC\nq\n1 1 198 18 re W n\nBT\n/Helv 11.851852 Tf\n0 g\n"));
+        assert!(content.contains("1 0 0 1 2 7.037037 Tm\n(Hi \\(there\\)) Tj\n"));
+        assert!(content.ends_with("ET\nQ\nEMC\n"));
+
+        // Right aligned with a fixed size.
+        let da = DefaultAppearance::parse("/Helv 10 Tf 0 g");
+        let content = String::from_utf8(text_content(&da, &metrics, 100.0, 20.0, "abcd", 2, false)).unwrap();
+        assert!(content.contains("1 0 0 1 78 7.5 Tm\n"));
+    }
+}
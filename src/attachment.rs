@@ -0,0 +1,294 @@
+use crate::document::PdfDocument;
+use crate::primitives::{Dictionary, Name, PdfStream, Primitives, Ref};
+use crate::annotation::AnnotationType;
+use crate::date::PdfDate;
+use crate::filters::flate_encode;
+use crate::utils::encode_text;
+use crate::error::Error;
+
+// A file embedded in the document, either in the `/EmbeddedFiles` name tree
+// or in a file attachment annotation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attachment {
+    // The key in `/EmbeddedFiles`, `None` for annotations.
+    pub key: Option<String>,
+    // The page of the annotation, `None` for `/EmbeddedFiles`.
+    pub page: Option<usize>,
+    pub file_name: String,
+    pub description: Option<String>,
+    // The MIME type from the stream's `/Subtype`, e.g. "text/xml".
+    pub mime_type: Option<String>,
+    // The size and MD5 checksum of the decoded contents as recorded in the
+    // file, which aren't necessarily right.
+    pub size: Option<usize>,
+    pub checksum: Option<Vec<u8>>,
+    pub creation_date: Option<PdfDate>,
+    pub modification_date: Option<PdfDate>,
+    // How the file relates to the document, e.g. "Alternative" or "Data".
+    pub relationship: Option<String>,
+    stream: PdfStream,
+}
+
+impl Attachment {
+    // The decoded contents of the file.
+    pub fn data(&self) -> Result<Vec<u8>, Error> {
+        self.stream.decode()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AttachmentOptions {
+    pub description: Option<String>,
+    pub mime_type: Option<String>,
+    // `/AFRelationship`. When set, the file is also listed in the catalog's
+    // `/AF` associated files, as PDF/A-3 invoices like ZUGFeRD and
+    // Factur-X require.
+    pub relationship: Option<String>,
+    // Defaults to now.
+    pub modification_date: Option<PdfDate>,
+}
+
+impl PdfDocument {
+    // The document's embedded files followed by those of file attachment
+    // annotations in page order. Entries without an embedded file stream
+    // are left out.
+    pub fn attachments(&self) -> Result<Vec<Attachment>, Error> {
+        let mut attachments = Vec::new();
+        if let Some(tree) = self.name_tree("EmbeddedFiles")? {
+            for entry in tree.iter() {
+                let (key, spec) = entry?;
+                if let Some(mut attachment) = self.attachment(&self.resolve(&spec)?)? {
+                    attachment.key = Some(Primitives::Str(key).get_text().unwrap_or_default());
+                    attachments.push(attachment);
+                }
+            }
+        }
+        for page in 0..self.page_count()? {
+            for annotation in self.annotations(page)? {
+                if annotation.subtype != AnnotationType::Other("FileAttachment".to_string()) {
+                    continue;
+                }
+                if let Some(mut attachment) = self.attachment(&self.get(&annotation.dict, "FS")?)? {
+                    attachment.page = Some(page);
+                    if attachment.description.is_none() {
+                        attachment.description = annotation.contents;
+                    }
+                    attachments.push(attachment);
+                }
+            }
+        }
+        Ok(attachments)
+    }
+
+    fn attachment(&self, spec: &Primitives) -> Result<Option<Attachment>, Error> {
+        let spec = match spec {
+            Primitives::Dict(spec) => spec,
+            _ => return Ok(None),
+        };
+        let ef = match self.get(spec, "EF")? {
+            Primitives::Dict(ef) => ef,
+            _ => return Ok(None),
+        };
+        let stream = match (self.get(&ef, "UF")?, self.get(&ef, "F")?) {
+            (Primitives::Stream(stream), _) | (_, Primitives::Stream(stream)) => stream,
+            _ => return Ok(None),
+        };
+        let params = match self.get(&stream.dict, "Params")? {
+            Primitives::Dict(params) => params,
+            _ => Dictionary::new(),
+        };
+        let date = |key: &str| -> Result<Option<PdfDate>, Error> {
+            Ok(self.get(&params, key)?.get_text().and_then(|d| PdfDate::parse(&d)))
+        };
+        let name = |dict: &Dictionary, key: &str| -> Result<Option<String>, Error> {
+            Ok(self.get(dict, key)?.get_name().map(|n| String::from_utf8_lossy(n).into_owned()))
+        };
+        Ok(Some(Attachment {
+            key: None,
+            page: None,
+            file_name: self.file_name(&Primitives::Dict(spec.clone()))?.unwrap_or_default(),
+            description: self.get(spec, "Desc")?.get_text(),
+            mime_type: name(&stream.dict, "Subtype")?,
+            size: self.get(&params, "Size")?.get_integer().filter(|&s| s >= 0).map(|s| s as usize),
+            checksum: self.get(&params, "CheckSum")?.get_string_bytes(),
+            creation_date: date("CreationDate")?,
+            modification_date: date("ModDate")?,
+            relationship: name(spec, "AFRelationship")?,
+            stream: stream,
+        }))
+    }
+
+    // Embeds `data` as `file_name` in the `/EmbeddedFiles` name tree,
+    // replacing a file embedded under the same name. Returns the file
+    // specification.
+    pub fn attach_file(&mut self, file_name: &str, data: &[u8], options: &AttachmentOptions) -> Result<Ref, Error> {
+        if file_name.is_empty() {
+            return Err(Error::InvalidArgument("attachment file name is empty"));
+        }
+        let date = Primitives::Str(options.modification_date.unwrap_or_else(PdfDate::now).to_pdf_string().into_bytes());
+        let mut params = Dictionary::new();
+        params.insert(Name::from("Size"), Primitives::Int(data.len() as _));
+        params.insert(Name::from("CheckSum"), Primitives::Str(md5::compute(data).0.to_vec()));
+        params.insert(Name::from("CreationDate"), date.clone());
+        params.insert(Name::from("ModDate"), date);
+        let mut dict = Dictionary::new();
+        dict.insert(Name::from("Type"), Primitives::name(b"EmbeddedFile".to_vec()));
+        if let Some(mime_type) = &options.mime_type {
+            dict.insert(Name::from("Subtype"), Primitives::name(mime_type.as_bytes().to_vec()));
+        }
+        dict.insert(Name::from("Params"), Primitives::Dict(params));
+        dict.insert(Name::from("Filter"), Primitives::name(b"FlateDecode".to_vec()));
+        let stream = self.add_object(Primitives::Stream(PdfStream::new(dict, flate_encode(data))));
+
+        let mut ef = Dictionary::new();
+        ef.insert(Name::from("F"), stream.into());
+        ef.insert(Name::from("UF"), stream.into());
+        let mut spec = Dictionary::new();
+        spec.insert(Name::from("Type"), Primitives::name(b"Filespec".to_vec()));
+        spec.insert(Name::from("F"), Primitives::Str(encode_text(file_name)));
+        spec.insert(Name::from("UF"), Primitives::Str(encode_text(file_name)));
+        spec.insert(Name::from("EF"), Primitives::Dict(ef));
+        if let Some(description) = &options.description {
+            spec.insert(Name::from("Desc"), Primitives::Str(encode_text(description)));
+        }
+        if let Some(relationship) = &options.relationship {
+            spec.insert(Name::from("AFRelationship"), Primitives::name(relationship.as_bytes().to_vec()));
+        }
+        let spec = self.add_object(Primitives::Dict(spec));
+
+        // The tree is rewritten as a single sorted leaf.
+        let key = encode_text(file_name);
+        let mut entries = Vec::new();
+        let mut replaced = None;
+        if let Some(tree) = self.name_tree("EmbeddedFiles")? {
+            for entry in tree.iter() {
+                let entry = entry?;
+                if entry.0 != key {
+                    entries.push(entry);
+                } else {
+                    replaced = Some(entry.1);
+                }
+            }
+        }
+        entries.push((key, spec.into()));
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut tree = Dictionary::new();
+        let names = entries.into_iter().flat_map(|(key, value)| vec![Primitives::Str(key), value]).collect();
+        tree.insert(Name::from("Names"), Primitives::Array(names));
+        let tree = self.add_object(Primitives::Dict(tree));
+
+        let catalog_ref = self.catalog_ref()?;
+        let mut catalog = self.catalog()?;
+        match catalog.get(&b"Names"[..]).cloned() {
+            Some(Primitives::Ref(num, gen)) => {
+                let r = Ref::new(num, gen);
+                let mut names = self.fetch(r)?.get_dict().cloned().unwrap_or_default();
+                names.insert(Name::from("EmbeddedFiles"), tree.into());
+                self.replace_object(r, Primitives::Dict(names))?;
+            }
+            names => {
+                let mut names = names.and_then(|n| n.get_dict().cloned()).unwrap_or_default();
+                names.insert(Name::from("EmbeddedFiles"), tree.into());
+                catalog.insert(Name::from("Names"), Primitives::Dict(names));
+            }
+        }
+        if options.relationship.is_some() || replaced.is_some() {
+            // The replaced file's specification goes away with it.
+            let mut af = self.get(&catalog, "AF")?.get_array().cloned().unwrap_or_default();
+            af.retain(|f| Some(f) != replaced.as_ref());
+            if options.relationship.is_some() {
+                af.push(spec.into());
+            }
+            if af.is_empty() {
+                catalog.remove(&b"AF"[..]);
+            } else {
+                catalog.insert(Name::from("AF"), Primitives::Array(af));
+            }
+        }
+        self.replace_object(catalog_ref, Primitives::Dict(catalog))?;
+        Ok(spec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::PdfDocument;
+    use crate::primitives::{Dictionary, Name, PdfStream, Primitives};
+    use crate::date::PdfDate;
+    use crate::writer::SaveOptions;
+    use super::AttachmentOptions;
+
+    #[test]
+    fn attach_and_extract_files() {
+        let mut doc = PdfDocument::open("tests/examples/sample.pdf").unwrap();
+        assert!(doc.attachments().unwrap().is_empty());
+        let date = PdfDate::new(2024, 3, 1, 12, 0, 0, Some(60));
+        let invoice = AttachmentOptions {
+            description: Some("Invoice data".to_string()),
+            mime_type: Some("text/xml".to_string()),
+            relationship: Some("Alternative".to_string()),
+            modification_date: Some(date),
+        };
+        doc.attach_file("zugferd-invoice.xml", b"<draft/>", &invoice).unwrap();
+        doc.attach_file("zugferd-invoice.xml", b"<invoice/>", &invoice).unwrap();
+        doc.attach_file("notes.txt", b"old", &AttachmentOptions::default()).unwrap();
+        doc.attach_file("notes.txt", b"hello", &AttachmentOptions::default()).unwrap();
+        assert!(doc.attach_file("", b"", &AttachmentOptions::default()).is_err());
+
+        let mut data = Vec::new();
+        doc.write_to(&mut data, &SaveOptions::default()).unwrap();
+        let doc = PdfDocument::load_data(data).unwrap();
+        let attachments = doc.attachments().unwrap();
+        let names: Vec<&str> = attachments.iter().map(|a| a.file_name.as_str()).collect();
+        assert_eq!(names, ["notes.txt", "zugferd-invoice.xml"]);
+
+        let notes = &attachments[0];
+        assert_eq!(notes.key.as_deref(), Some("notes.txt"));
+        assert_eq!(notes.data().unwrap(), b"hello");
+        assert_eq!(notes.size, Some(5));
+        assert_eq!(notes.relationship, None);
+
+        let invoice = &attachments[1];
+        assert_eq!(invoice.description.as_deref(), Some("Invoice data"));
+        assert_eq!(invoice.mime_type.as_deref(), Some("text/xml"));
+        assert_eq!(invoice.checksum.as_deref(), Some(&md5::compute(b"<invoice/>").0[..]));
+        assert_eq!(invoice.modification_date, Some(date));
+        assert_eq!(invoice.relationship.as_deref(), Some("Alternative"));
+        assert_eq!(invoice.data().unwrap(), b"<invoice/>");
+        let af = doc.get(&doc.catalog().unwrap(), "AF").unwrap();
+        assert_eq!(af.get_array().map(|a| a.len()), Some(1));
+    }
+
+    #[test]
+    fn file_attachment_annotations() {
+        let mut doc = PdfDocument::open("tests/examples/sample.pdf").unwrap();
+        let mut stream = Dictionary::new();
+        stream.insert(Name::from("Type"), Primitives::name(b"EmbeddedFile".to_vec()));
+        let stream = doc.add_object(Primitives::Stream(PdfStream::new(stream, b"a,b\n1,2\n".to_vec())));
+        let mut ef = Dictionary::new();
+        ef.insert(Name::from("F"), stream.into());
+        let mut spec = Dictionary::new();
+        spec.insert(Name::from("F"), Primitives::Str(b"table.csv".to_vec()));
+        spec.insert(Name::from("EF"), Primitives::Dict(ef));
+        let mut annot = Dictionary::new();
+        annot.insert(Name::from("Type"), Primitives::name(b"Annot".to_vec()));
+        annot.insert(Name::from("Subtype"), Primitives::name(b"FileAttachment".to_vec()));
+        annot.insert(Name::from("Rect"), Primitives::Array(vec![Primitives::Int(0), Primitives::Int(0), Primitives::Int(10), Primitives::Int(10)]));
+        annot.insert(Name::from("Contents"), Primitives::Str(b"Raw data".to_vec()));
+        annot.insert(Name::from("FS"), Primitives::Dict(spec));
+        let annot = doc.add_object(Primitives::Dict(annot));
+        let page = doc.page(1).unwrap();
+        let mut dict = page.dict;
+        dict.insert(Name::from("Annots"), Primitives::Array(vec![annot.into()]));
+        doc.replace_object(page.reference, Primitives::Dict(dict)).unwrap();
+
+        let attachments = doc.attachments().unwrap();
+        assert_eq!(attachments.len(), 1);
+        assert_eq!(attachments[0].page, Some(1));
+        assert_eq!(attachments[0].key, None);
+        assert_eq!(attachments[0].file_name, "table.csv");
+        assert_eq!(attachments[0].description.as_deref(), Some("Raw data"));
+        assert_eq!(attachments[0].size, None);
+        assert_eq!(attachments[0].data().unwrap(), b"a,b\n1,2\n");
+    }
+}
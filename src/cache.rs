@@ -0,0 +1,156 @@
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::Arc;
+
+pub const PAGE_SIZE: u64 = 4096;
+pub const DEFAULT_CAPACITY: usize = 64;
+
+// A small least-recently-used cache of fixed-size pages read from a source.
+// The lexer asks for one byte at a time, so without it every character costs
+// a `read` and a `seek` on the underlying file.
+//
+// Pages are reference counted so cloning a `Stream` (which the lexer and the
+// xref parser do a lot) doesn't copy any data.
+#[derive(Clone, Debug)]
+pub struct BlockCache {
+    // Ordered from least to most recently used.
+    pages: Vec<(u64, Arc<[u8]>)>,
+    capacity: usize,
+}
+
+impl BlockCache {
+    pub fn new(capacity: usize) -> Self {
+        BlockCache {
+            pages: Vec::new(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+        if self.pages.len() > self.capacity {
+            let excess = self.pages.len() - self.capacity;
+            self.pages.drain(..excess);
+        }
+    }
+
+    // Returns the page with the given index, reading it from `source` on a
+    // miss. The last page of a source may be shorter than `PAGE_SIZE`.
+    pub fn page<T: Read+Seek>(&mut self, source: &mut T, index: u64) -> io::Result<&[u8]> {
+        if let Some(found) = self.pages.iter().rposition(|(i, _)| *i == index) {
+            let last = self.pages.len() - 1;
+            if found != last {
+                let page = self.pages.remove(found);
+                self.pages.push(page);
+            }
+        } else {
+            let data = read_page(source, index)?;
+            if self.pages.len() >= self.capacity {
+                self.pages.remove(0);
+            }
+            self.pages.push((index, data));
+        }
+        Ok(&self.pages[self.pages.len() - 1].1)
+    }
+
+    // Copies `buf.len()` bytes starting at `pos` into `buf`, stopping early at
+    // the end of the source. Returns the number of bytes copied.
+    pub fn read_at<T: Read+Seek>(&mut self, source: &mut T, pos: u64, buf: &mut [u8]) -> io::Result<usize> {
+        let mut copied = 0;
+        while copied < buf.len() {
+            let at = pos + copied as u64;
+            let page = self.page(source, at / PAGE_SIZE)?;
+            let offset = (at % PAGE_SIZE) as usize;
+            if offset >= page.len() {
+                break;
+            }
+            let n = (page.len() - offset).min(buf.len() - copied);
+            buf[copied..copied + n].copy_from_slice(&page[offset..offset + n]);
+            copied += n;
+        }
+        Ok(copied)
+    }
+}
+
+impl Default for BlockCache {
+    fn default() -> Self {
+        BlockCache::new(DEFAULT_CAPACITY)
+    }
+}
+
+fn read_page<T: Read+Seek>(source: &mut T, index: u64) -> io::Result<Arc<[u8]>> {
+    source.seek(SeekFrom::Start(index * PAGE_SIZE))?;
+    let mut data = Vec::with_capacity(PAGE_SIZE as usize);
+    source.take(PAGE_SIZE).read_to_end(&mut data)?;
+    Ok(data.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Cursor, Read, Seek, SeekFrom};
+
+    use super::{BlockCache, PAGE_SIZE};
+
+    // Counts how often the cache goes to the underlying source.
+    struct CountingReader {
+        inner: Cursor<Vec<u8>>,
+        reads: usize,
+    }
+
+    impl Read for CountingReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.reads += 1;
+            self.inner.read(buf)
+        }
+    }
+
+    impl Seek for CountingReader {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    #[test]
+    fn reads_across_pages() -> io::Result<()> {
+        let data: Vec<u8> = (0..3 * PAGE_SIZE + 10).map(|i| i as u8).collect();
+        let mut source = Cursor::new(data.clone());
+        let mut cache = BlockCache::new(2);
+
+        let mut buf = vec![0_u8; 20];
+        let pos = PAGE_SIZE - 10;
+        assert_eq!(cache.read_at(&mut source, pos, &mut buf)?, 20);
+        assert_eq!(&buf[..], &data[pos as usize..pos as usize + 20]);
+
+        // Short read at the end of the source.
+        let pos = 3 * PAGE_SIZE + 5;
+        assert_eq!(cache.read_at(&mut source, pos, &mut buf)?, 5);
+        assert_eq!(&buf[..5], &data[pos as usize..]);
+        Ok(())
+    }
+
+    #[test]
+    fn evicts_least_recently_used() -> io::Result<()> {
+        let mut source = CountingReader {
+            inner: Cursor::new(vec![7_u8; 4 * PAGE_SIZE as usize]),
+            reads: 0,
+        };
+        let mut cache = BlockCache::new(2);
+
+        cache.page(&mut source, 0)?;
+        cache.page(&mut source, 1)?;
+        let reads = source.reads;
+
+        // Both pages are cached; touching page 0 makes page 1 the eviction candidate.
+        cache.page(&mut source, 0)?;
+        cache.page(&mut source, 1)?;
+        cache.page(&mut source, 0)?;
+        assert_eq!(source.reads, reads);
+
+        cache.page(&mut source, 2)?;
+        let reads = source.reads;
+        cache.page(&mut source, 0)?;
+        assert_eq!(source.reads, reads);
+        cache.page(&mut source, 1)?;
+        assert!(source.reads > reads);
+        Ok(())
+    }
+}
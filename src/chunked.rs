@@ -0,0 +1,324 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::{Arc, Mutex};
+
+use crate::linearization::Linearization;
+use crate::stream::Stream;
+
+pub const DEFAULT_CHUNK_SIZE: u64 = 65536;
+
+// Fetches byte ranges of a remote document, e.g. with HTTP range requests.
+// `fetch` returns the bytes in `[begin, end)`.
+pub trait RangeFetcher: Send + Sync {
+    fn length(&self) -> io::Result<u64>;
+    fn fetch(&self, begin: u64, end: u64) -> io::Result<Vec<u8>>;
+}
+
+// A `Read + Seek` source that loads a document in fixed-size chunks on demand.
+// Clones share the loaded chunks but keep their own read position, so a
+// `Stream` and all its sub-streams can be built on top of it.
+#[derive(Clone)]
+pub struct ChunkedSource {
+    shared: Arc<Mutex<Chunks>>,
+    fetcher: Arc<dyn RangeFetcher>,
+    chunk_size: u64,
+    length: u64,
+    pos: u64,
+}
+
+struct Chunks {
+    loaded: BTreeMap<u64, Arc<[u8]>>,
+    requests: usize,
+}
+
+impl ChunkedSource {
+    pub fn new(fetcher: Arc<dyn RangeFetcher>) -> io::Result<Self> {
+        ChunkedSource::with_chunk_size(fetcher, DEFAULT_CHUNK_SIZE)
+    }
+
+    // Creates the source and loads the first chunk. When the document is
+    // linearized, the whole first page section and the hint stream are
+    // requested right away so the first page can be shown without further
+    // round trips.
+    pub fn with_chunk_size(fetcher: Arc<dyn RangeFetcher>, chunk_size: u64) -> io::Result<Self> {
+        let length = fetcher.length()?;
+        let source = ChunkedSource {
+            shared: Arc::new(Mutex::new(Chunks {
+                loaded: BTreeMap::new(),
+                requests: 0,
+            })),
+            fetcher: fetcher,
+            chunk_size: chunk_size.max(1),
+            length: length,
+            pos: 0,
+        };
+
+        source.prefetch(0, source.chunk_size.min(length))?;
+        if let Some(linearization) = source.linearization() {
+            source.prefetch(0, linearization.end_first)?;
+            let hints = linearization.hints_offset;
+            source.prefetch(hints, hints + linearization.hints_length)?;
+        }
+        Ok(source)
+    }
+
+    pub fn len(&self) -> u64 {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    pub fn chunk_size(&self) -> u64 {
+        self.chunk_size
+    }
+
+    // Number of fetcher calls made so far.
+    pub fn requests(&self) -> usize {
+        self.shared.lock().unwrap().requests
+    }
+
+    pub fn loaded_bytes(&self) -> u64 {
+        self.shared.lock().unwrap().loaded.values().map(|chunk| chunk.len() as u64).sum()
+    }
+
+    pub fn is_loaded(&self, begin: u64, end: u64) -> bool {
+        let end = end.min(self.length);
+        if begin >= end {
+            return true;
+        }
+        let chunks = self.shared.lock().unwrap();
+        (begin / self.chunk_size..=(end - 1) / self.chunk_size).all(|i| chunks.loaded.contains_key(&i))
+    }
+
+    // Makes sure `[begin, end)` is available locally. Consecutive missing
+    // chunks are requested together.
+    pub fn prefetch(&self, begin: u64, end: u64) -> io::Result<()> {
+        let end = end.min(self.length);
+        if begin >= end {
+            return Ok(());
+        }
+        let first = begin / self.chunk_size;
+        let last = (end - 1) / self.chunk_size;
+
+        // The lock isn't held while fetching, so other readers aren't kept
+        // waiting on the round trip. Chunks another reader loads meanwhile
+        // may be fetched twice, the first copy is kept.
+        let runs = {
+            let chunks = self.shared.lock().unwrap();
+            let mut runs = Vec::new();
+            let mut i = first;
+            while i <= last {
+                if chunks.loaded.contains_key(&i) {
+                    i += 1;
+                    continue;
+                }
+                let run_start = i;
+                while i <= last && !chunks.loaded.contains_key(&i) {
+                    i += 1;
+                }
+                runs.push((run_start, i));
+            }
+            runs
+        };
+
+        for (run_start, run_end) in runs {
+            let range_begin = run_start * self.chunk_size;
+            let range_end = (run_end * self.chunk_size).min(self.length);
+            let data = self.fetcher.fetch(range_begin, range_end)?;
+            let mut chunks = self.shared.lock().unwrap();
+            chunks.requests += 1;
+            if (data.len() as u64) < range_end - range_begin {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "range request returned too few bytes"));
+            }
+
+            for (n, chunk) in data.chunks(self.chunk_size as usize).enumerate() {
+                chunks.loaded.entry(run_start + n as u64).or_insert_with(|| chunk.into());
+            }
+        }
+        Ok(())
+    }
+
+    // Only the first chunk is looked at, a linearization dictionary that
+    // doesn't fit in it goes unnoticed.
+    fn linearization(&self) -> Option<Linearization> {
+        let head = self.shared.lock().unwrap().loaded.get(&0)?.clone();
+        Linearization::create_with_length(Stream::from_bytes(head), self.length).ok().flatten()
+    }
+}
+
+impl Read for ChunkedSource {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.length || buf.is_empty() {
+            return Ok(0);
+        }
+        let index = self.pos / self.chunk_size;
+        let offset = (self.pos % self.chunk_size) as usize;
+        self.prefetch(self.pos, (self.pos + buf.len() as u64).min((index + 1) * self.chunk_size))?;
+
+        let chunk = self.shared.lock().unwrap().loaded[&index].clone();
+        let n = (chunk.len() - offset).min(buf.len());
+        buf[..n].copy_from_slice(&chunk[offset..offset + n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for ChunkedSource {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(n) => n as i64,
+            SeekFrom::End(n) => self.length as i64 + n,
+            SeekFrom::Current(n) => self.pos as i64 + n,
+        };
+        if pos < 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "seek before the start of the source"));
+        }
+        self.pos = pos as u64;
+        Ok(self.pos)
+    }
+}
+
+impl Debug for ChunkedSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChunkedSource")
+            .field("length", &self.length)
+            .field("chunk_size", &self.chunk_size)
+            .field("pos", &self.pos)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::{self, BufRead, BufReader, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::Arc;
+    use std::thread;
+
+    use crate::stream::Stream;
+    use super::{ChunkedSource, RangeFetcher};
+
+    // A minimal HTTP/1.1 server standing in for object storage. It answers
+    // `HEAD` with the content length and `GET` with `Range: bytes=a-b`.
+    fn serve(data: Vec<u8>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            for conn in listener.incoming() {
+                let mut conn = match conn {
+                    Ok(conn) => conn,
+                    Err(_) => return,
+                };
+                let mut reader = BufReader::new(conn.try_clone().unwrap());
+                let mut request = String::new();
+                reader.read_line(&mut request).unwrap();
+                let mut range = None;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some(value) = line.strip_prefix("Range: bytes=") {
+                        let (begin, end) = value.trim().split_once('-').unwrap();
+                        range = Some((begin.parse::<usize>().unwrap(), end.parse::<usize>().unwrap()));
+                    }
+                }
+                if request.starts_with("HEAD") {
+                    write!(conn, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", data.len()).unwrap();
+                } else {
+                    let (begin, end) = range.unwrap();
+                    let body = &data[begin..=end];
+                    write!(conn, "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\n\r\n", body.len()).unwrap();
+                    conn.write_all(body).unwrap();
+                }
+            }
+        });
+        addr
+    }
+
+    struct HttpFetcher {
+        addr: String,
+    }
+
+    impl HttpFetcher {
+        fn request(&self, head: &str) -> io::Result<(u64, Vec<u8>)> {
+            let mut conn = TcpStream::connect(&self.addr)?;
+            conn.write_all(head.as_bytes())?;
+            let mut reader = BufReader::new(conn);
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line)?;
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some(value) = line.strip_prefix("Content-Length: ") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+            let mut body = Vec::new();
+            if head.starts_with("GET") {
+                reader.take(length).read_to_end(&mut body)?;
+            }
+            Ok((length, body))
+        }
+    }
+
+    impl RangeFetcher for HttpFetcher {
+        fn length(&self) -> io::Result<u64> {
+            Ok(self.request("HEAD /doc.pdf HTTP/1.1\r\n\r\n")?.0)
+        }
+
+        fn fetch(&self, begin: u64, end: u64) -> io::Result<Vec<u8>> {
+            let head = format!("GET /doc.pdf HTTP/1.1\r\nRange: bytes={}-{}\r\n\r\n", begin, end - 1);
+            Ok(self.request(&head)?.1)
+        }
+    }
+
+    #[test]
+    fn loads_chunks_on_demand() -> io::Result<()> {
+        let data = fs::read("tests/examples/PDF_sample.pdf")?;
+        let fetcher = HttpFetcher { addr: serve(data.clone()) };
+        let source = ChunkedSource::with_chunk_size(Arc::new(fetcher), 1024)?;
+        assert_eq!(source.requests(), 1);
+        assert_eq!(source.loaded_bytes(), 1024);
+
+        let mut stream = Stream::new(source.clone())?;
+        stream.set_pos(30000);
+        assert_eq!(stream.get_bytes(10).unwrap(), &data[30000..30010]);
+        assert!(source.is_loaded(30000, 30010));
+        assert!(!source.is_loaded(50000, 50010));
+        assert!(source.loaded_bytes() < data.len() as u64);
+
+        let mut all = Vec::new();
+        let mut reader = source.clone();
+        reader.read_to_end(&mut all)?;
+        assert_eq!(all, data);
+        Ok(())
+    }
+
+    #[test]
+    fn prefetches_first_page_of_linearized_files() -> io::Result<()> {
+        // The first 8355 bytes are the original linearized file (/L 8355,
+        // /E 3386, /H [458 161]); an incremental update follows.
+        let data = fs::read("tests/examples/140514041111253731pdf1.pdf")?;
+        let linearized = data[..8355].to_vec();
+
+        let source = ChunkedSource::with_chunk_size(Arc::new(HttpFetcher { addr: serve(linearized) }), 512)?;
+        assert!(source.is_loaded(0, 3386));
+        assert!(!source.is_loaded(4096, 4608));
+        // The first chunk, then the rest of the first page in one request.
+        assert_eq!(source.requests(), 2);
+
+        // After the update /L no longer matches, so the hints are ignored.
+        let source = ChunkedSource::with_chunk_size(Arc::new(HttpFetcher { addr: serve(data) }), 512)?;
+        assert!(!source.is_loaded(0, 3386));
+        assert_eq!(source.requests(), 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,418 @@
+use crate::document::PdfDocument;
+use crate::primitives::{Dictionary, Primitives};
+use crate::function::Function;
+use crate::error::Error;
+
+// Color spaces nested deeper than this, through bases and alternates, are
+// rejected.
+const MAX_DEPTH: usize = 8;
+
+const D65: [f64; 3] = [0.9505, 1.0, 1.089];
+
+// Bradford chromatic adaptation matrix and its inverse.
+const BRADFORD: [f64; 9] = [0.8951, 0.2664, -0.1614, -0.7502, 1.7135, 0.0367, 0.0389, -0.0685, 1.0296];
+const BRADFORD_INVERSE: [f64; 9] = [0.9869929, -0.1470543, 0.1599627, 0.4323053, 0.5183603, 0.0492912, -0.0085287, 0.0400428, 0.9684867];
+
+// From D65 relative XYZ to linear sRGB.
+const XYZ_TO_SRGB: [f64; 9] = [3.2404542, -1.5371385, -0.4985314, -0.969266, 1.8760108, 0.041556, 0.0556434, -0.2040259, 1.0572252];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColorSpace {
+    DeviceGray,
+    DeviceRgb,
+    DeviceCmyk,
+    CalGray {
+        white_point: [f64; 3],
+        gamma: f64,
+    },
+    CalRgb {
+        white_point: [f64; 3],
+        gamma: [f64; 3],
+        // Column-major like `/Matrix`: the XYZ of A, B and C in turn.
+        matrix: [f64; 9],
+    },
+    Lab {
+        white_point: [f64; 3],
+        // Ranges of a* and b*: amin, amax, bmin, bmax.
+        range: [f64; 4],
+    },
+    // ICC profiles aren't interpreted, colors go through the alternate.
+    IccBased {
+        components: usize,
+        alternate: Box<ColorSpace>,
+    },
+    Indexed {
+        base: Box<ColorSpace>,
+        hival: usize,
+        // `hival + 1` colors of the base space, one byte per component.
+        lookup: Vec<u8>,
+    },
+    Separation {
+        name: String,
+        alternate: Box<ColorSpace>,
+        // `None` when the transform can't be parsed.
+        tint_transform: Option<Function>,
+    },
+    DeviceN {
+        names: Vec<String>,
+        alternate: Box<ColorSpace>,
+        tint_transform: Option<Function>,
+    },
+    // Colors are patterns, with the underlying space of uncolored ones.
+    Pattern(Option<Box<ColorSpace>>),
+}
+
+impl ColorSpace {
+    // Parses a color space name or array. Names other than the device
+    // spaces are keys in the resources' `/ColorSpace`.
+    pub fn parse(doc: &PdfDocument, obj: &Primitives, resources: &Dictionary) -> Result<ColorSpace, Error> {
+        ColorSpace::parse_nested(doc, obj, resources, 0)
+    }
+
+    fn parse_nested(doc: &PdfDocument, obj: &Primitives, resources: &Dictionary, depth: usize) -> Result<ColorSpace, Error> {
+        if depth > MAX_DEPTH {
+            return Err(Error::UnsupportedColorSpace(String::from("color spaces nested too deeply")));
+        }
+        let nested = |obj: &Primitives| ColorSpace::parse_nested(doc, obj, resources, depth + 1).map(Box::new);
+        let obj = doc.resolve(obj)?;
+        let (family, params) = match &obj {
+            Primitives::Name(name) => (name.0.clone(), &[][..]),
+            Primitives::Array(array) => match array.first().and_then(|f| f.get_name()) {
+                Some(family) => (family.to_vec(), &array[1..]),
+                None => return Err(Error::UnsupportedColorSpace(String::from("array without a family name"))),
+            },
+            _ => return Err(Error::UnsupportedColorSpace(format!("{:?}", obj))),
+        };
+        let param = |index: usize| -> Result<Primitives, Error> {
+            match params.get(index) {
+                Some(param) => doc.resolve(param),
+                None => Ok(Primitives::Null),
+            }
+        };
+        let dict = param(0)?.get_dict().cloned().unwrap_or_default();
+        let numbers = |key: &str, default: &[f64]| -> Result<Vec<f64>, Error> {
+            let nums: Vec<f64> = doc.get(&dict, key)?.get_array().map_or(Vec::new(), |a| a.iter().filter_map(|n| n.get_real()).collect());
+            Ok(if nums.len() == default.len() { nums } else { default.to_vec() })
+        };
+        let white_point = || -> Result<[f64; 3], Error> {
+            let white = numbers("WhitePoint", &D65)?;
+            Ok([white[0], white[1], white[2]])
+        };
+
+        Ok(match &family[..] {
+            b"DeviceGray" | b"G" => ColorSpace::DeviceGray,
+            b"DeviceRGB" | b"RGB" => ColorSpace::DeviceRgb,
+            // CalCMYK was never specified, readers treat it as DeviceCMYK.
+            b"DeviceCMYK" | b"CMYK" | b"CalCMYK" => ColorSpace::DeviceCmyk,
+            b"CalGray" => ColorSpace::CalGray {
+                white_point: white_point()?,
+                gamma: numbers("Gamma", &[1.0])?[0],
+            },
+            b"CalRGB" => {
+                let gamma = numbers("Gamma", &[1.0; 3])?;
+                let matrix = numbers("Matrix", &[1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0])?;
+                let mut m = [0.0; 9];
+                m.copy_from_slice(&matrix);
+                ColorSpace::CalRgb {
+                    white_point: white_point()?,
+                    gamma: [gamma[0], gamma[1], gamma[2]],
+                    matrix: m,
+                }
+            }
+            b"Lab" => {
+                let range = numbers("Range", &[-100.0, 100.0, -100.0, 100.0])?;
+                ColorSpace::Lab {
+                    white_point: white_point()?,
+                    range: [range[0], range[1], range[2], range[3]],
+                }
+            }
+            b"ICCBased" => {
+                let profile = match param(0)? {
+                    Primitives::Stream(profile) => profile.dict,
+                    _ => return Err(Error::UnsupportedColorSpace(String::from("ICCBased without a profile stream"))),
+                };
+                let components = doc.get(&profile, "N")?.get_integer().unwrap_or(0);
+                let alternate = match profile.get(&b"Alternate"[..]) {
+                    Some(alternate) => nested(alternate)?,
+                    None => Box::new(match components {
+                        1 => ColorSpace::DeviceGray,
+                        3 => ColorSpace::DeviceRgb,
+                        4 => ColorSpace::DeviceCmyk,
+                        _ => return Err(Error::UnsupportedColorSpace(String::from("ICCBased with an invalid /N"))),
+                    }),
+                };
+                ColorSpace::IccBased {
+                    components: alternate.components(),
+                    alternate: alternate,
+                }
+            }
+            b"Indexed" | b"I" => {
+                let base = nested(params.first().unwrap_or(&Primitives::Null))?;
+                let hival = param(1)?.get_integer().unwrap_or(0).clamp(0, 255) as usize;
+                let mut lookup = match param(2)? {
+                    Primitives::Stream(stream) => stream.decode()?,
+                    other => other.get_string_bytes().unwrap_or_default(),
+                };
+                // Short tables are padded with zeros.
+                lookup.resize((hival + 1) * base.components(), 0);
+                ColorSpace::Indexed {
+                    base: base,
+                    hival: hival,
+                    lookup: lookup,
+                }
+            }
+            b"Separation" => ColorSpace::Separation {
+                name: param(0)?.get_name().map(|n| String::from_utf8_lossy(n).into_owned()).unwrap_or_default(),
+                alternate: nested(params.get(1).unwrap_or(&Primitives::Null))?,
+                tint_transform: Function::parse(doc, params.get(2).unwrap_or(&Primitives::Null)).ok(),
+            },
+            b"DeviceN" => ColorSpace::DeviceN {
+                names: param(0)?.get_array().map_or(Vec::new(), |n| {
+                    n.iter().filter_map(|n| n.get_name()).map(|n| String::from_utf8_lossy(n).into_owned()).collect()
+                }),
+                alternate: nested(params.get(1).unwrap_or(&Primitives::Null))?,
+                tint_transform: Function::parse(doc, params.get(2).unwrap_or(&Primitives::Null)).ok(),
+            },
+            b"Pattern" => ColorSpace::Pattern(match params.first() {
+                Some(base) => Some(nested(base)?),
+                None => None,
+            }),
+            _ if params.is_empty() => match doc.get(resources, "ColorSpace")?.get(&String::from_utf8_lossy(&family)) {
+                Some(named) => ColorSpace::parse_nested(doc, named, resources, depth + 1)?,
+                None => return Err(Error::UnsupportedColorSpace(String::from_utf8_lossy(&family).into_owned())),
+            },
+            _ => return Err(Error::UnsupportedColorSpace(String::from_utf8_lossy(&family).into_owned())),
+        })
+    }
+
+    // The number of components of a color.
+    pub fn components(&self) -> usize {
+        match self {
+            ColorSpace::DeviceGray | ColorSpace::CalGray { .. } | ColorSpace::Indexed { .. } | ColorSpace::Separation { .. } => 1,
+            ColorSpace::DeviceRgb | ColorSpace::CalRgb { .. } | ColorSpace::Lab { .. } => 3,
+            ColorSpace::DeviceCmyk => 4,
+            ColorSpace::IccBased { components, .. } => *components,
+            ColorSpace::DeviceN { names, .. } => names.len().max(1),
+            ColorSpace::Pattern(_) => 1,
+        }
+    }
+
+    // Whether colors are shades of gray, so images can be stored with a
+    // single channel.
+    pub fn is_gray(&self) -> bool {
+        match self {
+            ColorSpace::DeviceGray | ColorSpace::CalGray { .. } => true,
+            ColorSpace::IccBased { alternate, .. } => alternate.is_gray(),
+            _ => false,
+        }
+    }
+
+    // The `/Decode` array images with `bits` per component default to.
+    pub fn default_decode(&self, bits: u8) -> Vec<f64> {
+        match self {
+            ColorSpace::Indexed { .. } => vec![0.0, ((1u32 << bits) - 1) as f64],
+            ColorSpace::Lab { range, .. } => vec![0.0, 100.0, range[0], range[1], range[2], range[3]],
+            _ => [0.0, 1.0].repeat(self.components()),
+        }
+    }
+
+    // Converts a color to sRGB, each component in 0 to 1. Missing
+    // components read as zero.
+    pub fn to_rgb(&self, color: &[f64]) -> [f64; 3] {
+        let c = |i: usize| color.get(i).cloned().unwrap_or(0.0).clamp(0.0, 1.0);
+        match self {
+            ColorSpace::DeviceGray => [c(0); 3],
+            ColorSpace::DeviceRgb => [c(0), c(1), c(2)],
+            ColorSpace::DeviceCmyk => {
+                let k = 1.0 - c(3);
+                [(1.0 - c(0)) * k, (1.0 - c(1)) * k, (1.0 - c(2)) * k]
+            }
+            ColorSpace::CalGray { white_point, gamma } => {
+                let y = c(0).powf(*gamma);
+                xyz_to_rgb(&[white_point[0] * y, white_point[1] * y, white_point[2] * y], white_point)
+            }
+            ColorSpace::CalRgb { white_point, gamma, matrix } => {
+                let abc = [c(0).powf(gamma[0]), c(1).powf(gamma[1]), c(2).powf(gamma[2])];
+                let xyz = [
+                    matrix[0] * abc[0] + matrix[3] * abc[1] + matrix[6] * abc[2],
+                    matrix[1] * abc[0] + matrix[4] * abc[1] + matrix[7] * abc[2],
+                    matrix[2] * abc[0] + matrix[5] * abc[1] + matrix[8] * abc[2],
+                ];
+                xyz_to_rgb(&xyz, white_point)
+            }
+            ColorSpace::Lab { white_point, range } => {
+                let l = color.first().cloned().unwrap_or(0.0).clamp(0.0, 100.0);
+                let a = color.get(1).cloned().unwrap_or(0.0).clamp(range[0], range[1]);
+                let b = color.get(2).cloned().unwrap_or(0.0).clamp(range[2], range[3]);
+                let g = |x: f64| if x >= 6.0 / 29.0 { x * x * x } else { 108.0 / 841.0 * (x - 4.0 / 29.0) };
+                let m = (l + 16.0) / 116.0;
+                let xyz = [
+                    white_point[0] * g(m + a / 500.0),
+                    white_point[1] * g(m),
+                    white_point[2] * g(m - b / 200.0),
+                ];
+                xyz_to_rgb(&xyz, white_point)
+            }
+            ColorSpace::IccBased { alternate, .. } => alternate.to_rgb(color),
+            ColorSpace::Indexed { base, hival, lookup } => {
+                let index = color.first().cloned().unwrap_or(0.0).round().clamp(0.0, *hival as f64) as usize;
+                let n = base.components();
+                let entry: Vec<f64> = lookup[index * n..(index + 1) * n].iter().map(|&b| b as f64 / 255.0).collect();
+                // Lab lookup tables are stored scaled to its decode ranges.
+                match &**base {
+                    ColorSpace::Lab { .. } => {
+                        let decode = base.default_decode(8);
+                        let lab: Vec<f64> = entry.iter().enumerate().map(|(i, v)| decode[2 * i] + v * (decode[2 * i + 1] - decode[2 * i])).collect();
+                        base.to_rgb(&lab)
+                    }
+                    _ => base.to_rgb(&entry),
+                }
+            }
+            // Tints whose transform fails read as zeros in the alternate
+            // space. Without a usable transform, the darkest tint is shown
+            // as gray.
+            ColorSpace::Separation { alternate, tint_transform, .. } | ColorSpace::DeviceN { alternate, tint_transform, .. } => {
+                let tints: Vec<f64> = (0..self.components()).map(c).collect();
+                match tint_transform {
+                    Some(tint_transform) => alternate.to_rgb(&tint_transform.evaluate(&tints).unwrap_or_default()),
+                    None => [1.0 - tints.iter().cloned().fold(0.0, f64::max); 3],
+                }
+            }
+            ColorSpace::Pattern(_) => [0.0; 3],
+        }
+    }
+
+    // Converts image samples of `bits` bits, `components()` per pixel, to 8
+    // bit sRGB. `decode` maps samples to color values like `/Decode`.
+    pub fn image_to_rgb(&self, samples: &[u16], bits: u8, decode: Option<&[f64]>) -> Vec<u8> {
+        let n = self.components();
+        let default_decode = self.default_decode(bits);
+        let decode = match decode {
+            Some(decode) if decode.len() >= 2 * n => decode,
+            _ => &default_decode[..],
+        };
+        let max = ((1u32 << bits) - 1) as f64;
+        let to_byte = |rgb: [f64; 3]| rgb.iter().map(|v| (v * 255.0).round() as u8).collect::<Vec<u8>>();
+
+        let mut pixels = Vec::with_capacity(samples.len() / n * 3);
+        if let ColorSpace::Indexed { hival, .. } = self {
+            // Each index is converted once.
+            let palette: Vec<Vec<u8>> = (0..=*hival).map(|i| to_byte(self.to_rgb(&[i as f64]))).collect();
+            for &sample in samples {
+                let index = decode[0] + sample as f64 * (decode[1] - decode[0]) / max;
+                pixels.extend_from_slice(&palette[index.round().clamp(0.0, *hival as f64) as usize]);
+            }
+            return pixels;
+        }
+        let mut color = vec![0.0; n];
+        for pixel in samples.chunks_exact(n) {
+            for (i, &sample) in pixel.iter().enumerate() {
+                color[i] = decode[2 * i] + sample as f64 * (decode[2 * i + 1] - decode[2 * i]) / max;
+            }
+            pixels.extend(to_byte(self.to_rgb(&color)));
+        }
+        pixels
+    }
+}
+
+fn apply(m: &[f64; 9], v: &[f64; 3]) -> [f64; 3] {
+    [
+        m[0] * v[0] + m[1] * v[1] + m[2] * v[2],
+        m[3] * v[0] + m[4] * v[1] + m[5] * v[2],
+        m[6] * v[0] + m[7] * v[1] + m[8] * v[2],
+    ]
+}
+
+// Converts XYZ relative to `white_point` to sRGB, adapting the white point
+// to D65 with the Bradford transform.
+fn xyz_to_rgb(xyz: &[f64; 3], white_point: &[f64; 3]) -> [f64; 3] {
+    let cone = apply(&BRADFORD, xyz);
+    let source = apply(&BRADFORD, white_point);
+    let target = apply(&BRADFORD, &D65);
+    let adapted = [cone[0] * target[0] / source[0], cone[1] * target[1] / source[1], cone[2] * target[2] / source[2]];
+    let linear = apply(&XYZ_TO_SRGB, &apply(&BRADFORD_INVERSE, &adapted));
+    let encode = |v: f64| {
+        let v = v.clamp(0.0, 1.0);
+        if v <= 0.0031308 { 12.92 * v } else { 1.055 * v.powf(1.0 / 2.4) - 0.055 }
+    };
+    [encode(linear[0]), encode(linear[1]), encode(linear[2])]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::PdfDocument;
+    use crate::primitives::{Dictionary, Name, PdfStream, Primitives};
+    use super::ColorSpace;
+
+    fn n(name: &str) -> Primitives {
+        Primitives::name(name.as_bytes().to_vec())
+    }
+
+    fn assert_rgb(actual: [f64; 3], expected: [f64; 3]) {
+        for i in 0..3 {
+            assert!((actual[i] - expected[i]).abs() < 0.01, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn convert_colors() {
+        assert_eq!(ColorSpace::DeviceGray.to_rgb(&[0.25]), [0.25; 3]);
+        assert_eq!(ColorSpace::DeviceCmyk.to_rgb(&[1.0, 0.0, 0.0, 0.5]), [0.0, 0.5, 0.5]);
+        let lab = ColorSpace::Lab { white_point: [0.9505, 1.0, 1.089], range: [-100.0, 100.0, -100.0, 100.0] };
+        assert_rgb(lab.to_rgb(&[100.0, 0.0, 0.0]), [1.0, 1.0, 1.0]);
+        assert_rgb(lab.to_rgb(&[53.24, 80.09, 67.2]), [1.0, 0.0, 0.0]);
+        // A D50 white point is adapted to sRGB's white.
+        let gray = ColorSpace::CalGray { white_point: [0.9642, 1.0, 0.8249], gamma: 2.2 };
+        assert_rgb(gray.to_rgb(&[1.0]), [1.0, 1.0, 1.0]);
+        assert_rgb(gray.to_rgb(&[0.0]), [0.0, 0.0, 0.0]);
+
+        let indexed = ColorSpace::Indexed { base: Box::new(ColorSpace::DeviceRgb), hival: 1, lookup: vec![255, 0, 0, 0, 0, 255] };
+        assert_eq!(indexed.to_rgb(&[1.0]), [0.0, 0.0, 1.0]);
+        assert_eq!(indexed.image_to_rgb(&[0, 1, 7], 4, None), [255, 0, 0, 0, 0, 255, 0, 0, 255]);
+        // `/Decode` [1 0] inverts gray images.
+        assert_eq!(ColorSpace::DeviceGray.image_to_rgb(&[0, 3], 2, Some(&[1.0, 0.0])), [255, 255, 255, 0, 0, 0]);
+        assert_eq!(lab.default_decode(8), [0.0, 100.0, -100.0, 100.0, -100.0, 100.0]);
+    }
+
+    #[test]
+    fn parse_color_spaces() {
+        let mut doc = PdfDocument::new();
+        let mut profile = Dictionary::new();
+        profile.insert(Name::from("N"), Primitives::Int(4));
+        let profile = doc.add_object(Primitives::Stream(PdfStream::new(profile, Vec::new())));
+        let lookup = doc.add_object(Primitives::Stream(PdfStream::new(Dictionary::new(), vec![0, 255])));
+        let mut named = Dictionary::new();
+        named.insert(Name::from("CS0"), Primitives::Array(vec![n("ICCBased"), profile.into()]));
+        named.insert(Name::from("CS1"), Primitives::Array(vec![n("Indexed"), n("CS0"), Primitives::Int(1), Primitives::Str(b"\x00\x00\x00\x00\x00\x00\x00\xff".to_vec())]));
+        let mut resources = Dictionary::new();
+        resources.insert(Name::from("ColorSpace"), Primitives::Dict(named));
+
+        let icc = ColorSpace::parse(&doc, &n("CS0"), &resources).unwrap();
+        assert_eq!(icc, ColorSpace::IccBased { components: 4, alternate: Box::new(ColorSpace::DeviceCmyk) });
+        let indexed = ColorSpace::parse(&doc, &n("CS1"), &resources).unwrap();
+        assert_eq!(indexed.to_rgb(&[1.0]), [0.0; 3]);
+        let gray = Primitives::Array(vec![n("Indexed"), n("G"), Primitives::Int(1), lookup.into()]);
+        assert_eq!(ColorSpace::parse(&doc, &gray, &resources).unwrap().to_rgb(&[1.0]), [1.0; 3]);
+
+        let mut tint_transform = Dictionary::new();
+        tint_transform.insert(Name::from("FunctionType"), Primitives::Int(2));
+        tint_transform.insert(Name::from("Domain"), Primitives::Array(vec![Primitives::Int(0), Primitives::Int(1)]));
+        tint_transform.insert(Name::from("C0"), Primitives::Array(vec![Primitives::Int(0); 3]));
+        tint_transform.insert(Name::from("C1"), Primitives::Array(vec![Primitives::Int(1), Primitives::Int(0), Primitives::Int(1)]));
+        tint_transform.insert(Name::from("N"), Primitives::Int(1));
+        let separation = Primitives::Array(vec![n("Separation"), n("Spot"), n("DeviceRGB"), Primitives::Dict(tint_transform)]);
+        let separation = ColorSpace::parse(&doc, &separation, &resources).unwrap();
+        assert_eq!(separation.components(), 1);
+        assert!(matches!(&separation, ColorSpace::Separation { name, .. } if name == "Spot"));
+        assert_eq!(separation.to_rgb(&[0.5]), [0.5, 0.0, 0.5]);
+        assert_eq!(separation.image_to_rgb(&[0, 255], 8, Some(&[1.0, 0.0])), [255, 0, 255, 0, 0, 0]);
+        let missing = Primitives::Array(vec![n("Separation"), n("Spot"), n("DeviceCMYK"), Primitives::Null]);
+        let missing = ColorSpace::parse(&doc, &missing, &resources).unwrap();
+        assert!(matches!(&missing, ColorSpace::Separation { tint_transform: None, .. }));
+        assert_eq!(missing.to_rgb(&[0.25]), [0.75; 3]);
+        let pattern = ColorSpace::parse(&doc, &Primitives::Array(vec![n("Pattern"), n("DeviceRGB")]), &resources).unwrap();
+        assert_eq!(pattern, ColorSpace::Pattern(Some(Box::new(ColorSpace::DeviceRgb))));
+        assert!(ColorSpace::parse(&doc, &n("Missing"), &resources).is_err());
+        assert!(ColorSpace::parse(&doc, &Primitives::Int(1), &resources).is_err());
+    }
+}
@@ -0,0 +1,191 @@
+use std::io::Cursor;
+
+use crate::primitives::{Dictionary, Primitives};
+use crate::lexer::Lexer;
+use crate::stream::Stream;
+use crate::error::Error;
+
+// Nesting limit for arrays and dictionaries in operands.
+const MAX_DEPTH: usize = 64;
+
+// An operator with its operands. Inline images are returned as a single
+// "EI" operation whose operands are the image dictionary, keys as written,
+// and the image data as a string.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Operation {
+    pub operator: Vec<u8>,
+    pub operands: Vec<Primitives>,
+}
+
+// Splits a decoded content stream into operations. Unlike `Parser` it
+// reads inline image data, which can't be tokenized.
+pub(crate) struct ContentParser<'a> {
+    data: &'a Vec<u8>,
+    lexer: Lexer<Cursor<&'a Vec<u8>>>,
+}
+
+impl<'a> ContentParser<'a> {
+    pub fn new(data: &'a Vec<u8>) -> ContentParser<'a> {
+        ContentParser {
+            data: data,
+            lexer: Lexer::new(Stream::from_vec(data)),
+        }
+    }
+
+    pub fn next_operation(&mut self) -> Result<Option<Operation>, Error> {
+        let mut operands = Vec::new();
+        loop {
+            match self.lexer.get_obj()? {
+                Primitives::EOF => return Ok(None),
+                Primitives::Cmd(cmd) if cmd == b"BI" => return self.inline_image().map(Some),
+                Primitives::Cmd(cmd) if cmd == b"[" || cmd == b"<<" => operands.push(self.compound(&cmd, 0)?),
+                Primitives::Cmd(operator) => {
+                    return Ok(Some(Operation {
+                        operator: operator,
+                        operands: operands,
+                    }))
+                }
+                operand => operands.push(operand),
+            }
+        }
+    }
+
+    // Reads an array or dictionary whose opening token was `open`.
+    fn compound(&mut self, open: &[u8], depth: usize) -> Result<Primitives, Error> {
+        let close: &[u8] = if open == b"[" { b"]" } else { b">>" };
+        let mut items = Vec::new();
+        loop {
+            let item = match self.lexer.get_obj()? {
+                Primitives::EOF => break,
+                Primitives::Cmd(cmd) if cmd == close => break,
+                Primitives::Cmd(cmd) if (cmd == b"[" || cmd == b"<<") && depth < MAX_DEPTH => self.compound(&cmd, depth + 1)?,
+                item => item,
+            };
+            items.push(item);
+        }
+        if open == b"[" {
+            return Ok(Primitives::Array(items));
+        }
+        Ok(Primitives::Dict(pairs(items)))
+    }
+
+    // Reads the dictionary after `BI` up to `ID`, then the data up to `EI`.
+    fn inline_image(&mut self) -> Result<Operation, Error> {
+        let mut items = Vec::new();
+        loop {
+            match self.lexer.get_obj()? {
+                Primitives::EOF => return Err(Error::ParserError),
+                Primitives::Cmd(cmd) if cmd == b"ID" => break,
+                Primitives::Cmd(cmd) if cmd == b"[" || cmd == b"<<" => items.push(self.compound(&cmd, 0)?),
+                item => items.push(item),
+            }
+        }
+        let dict = pairs(items);
+
+        // The lexer has consumed the single whitespace after `ID`.
+        let start = (self.lexer.stream().pos() as usize).min(self.data.len());
+        let length = dict.get(&b"L"[..]).or_else(|| dict.get(&b"Length"[..])).and_then(|l| l.get_integer());
+        let (end, next) = match length {
+            Some(length) if length >= 0 && start + length as usize <= self.data.len() => {
+                let end = start + length as usize;
+                let ei = find_end(self.data, end).unwrap_or(end);
+                (end, ei + 2)
+            }
+            _ => {
+                let ei = find_end(self.data, start).unwrap_or(self.data.len());
+                // The whitespace in front of `EI` separates it from the data.
+                let end = if ei > start && is_whitespace(self.data[ei - 1]) { ei - 1 } else { ei };
+                (end, ei + 2)
+            }
+        };
+        self.lexer.set_pos(next.min(self.data.len()) as u64);
+        Ok(Operation {
+            operator: b"EI".to_vec(),
+            operands: vec![Primitives::Dict(dict), Primitives::Str(self.data[start..end].to_vec())],
+        })
+    }
+}
+
+fn pairs(items: Vec<Primitives>) -> Dictionary {
+    let mut dict = Dictionary::new();
+    let mut items = items.into_iter();
+    while let (Some(key), Some(value)) = (items.next(), items.next()) {
+        if let Primitives::Name(key) = key {
+            dict.insert(key, value);
+        }
+    }
+    dict
+}
+
+fn is_whitespace(ch: u8) -> bool {
+    matches!(ch, b' ' | b'\t' | b'\r' | b'\n' | b'\x0c' | b'\0')
+}
+
+// The position of the `EI` that ends inline image data at or after `from`:
+// preceded by whitespace and followed by whitespace or the end of the data.
+fn find_end(data: &[u8], from: usize) -> Option<usize> {
+    (from..data.len().saturating_sub(1)).find(|&i| {
+        &data[i..i + 2] == b"EI"
+            && (i == 0 || is_whitespace(data[i - 1]))
+            && data.get(i + 2).is_none_or(|&c| is_whitespace(c))
+    })
+}
+
+// The matrix that applies `m` and then `n`, as `m n` in PDF notation.
+pub(crate) fn multiply(m: &[f64; 6], n: &[f64; 6]) -> [f64; 6] {
+    [
+        m[0] * n[0] + m[1] * n[2],
+        m[0] * n[1] + m[1] * n[3],
+        m[2] * n[0] + m[3] * n[2],
+        m[2] * n[1] + m[3] * n[3],
+        m[4] * n[0] + m[5] * n[2] + n[4],
+        m[4] * n[1] + m[5] * n[3] + n[5],
+    ]
+}
+
+pub(crate) const IDENTITY: [f64; 6] = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+// A matrix from an array operand or `/Matrix` entry, if it has six
+// numbers.
+pub(crate) fn matrix(obj: &Primitives) -> Option<[f64; 6]> {
+    let nums: Vec<f64> = obj.get_array()?.iter().filter_map(|n| n.get_real()).collect();
+    match nums[..] {
+        [a, b, c, d, e, f] => Some([a, b, c, d, e, f]),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::primitives::{Name, Primitives};
+    use super::{multiply, ContentParser, Operation};
+
+    #[test]
+    fn parse_operations() {
+        let data = b"q [1 [2]] 0 d /P <</MCID 3>> BDC\nBI /W 2 /H 1 /F [/AHx] ID 0f0e EI\nQ BI /W 1 /L 2 ID E\nEI Q".to_vec();
+        let mut parser = ContentParser::new(&data);
+        let mut operations = Vec::new();
+        while let Some(op) = parser.next_operation().unwrap() {
+            operations.push(op);
+        }
+        let operators: Vec<&[u8]> = operations.iter().map(|o| &o.operator[..]).collect();
+        assert_eq!(operators, [&b"q"[..], b"d", b"BDC", b"EI", b"Q", b"EI", b"Q"]);
+        assert_eq!(operations[0], Operation { operator: b"q".to_vec(), operands: Vec::new() });
+        assert_eq!(operations[1].operands[0], Primitives::Array(vec![Primitives::Int(1), Primitives::Array(vec![Primitives::Int(2)])]));
+        assert_eq!(operations[2].operands[1].get("MCID"), Some(&Primitives::Int(3)));
+
+        let dict = operations[3].operands[0].get_dict().unwrap();
+        assert_eq!(dict.get(&b"F"[..]), Some(&Primitives::Array(vec![Primitives::Name(Name::from("AHx"))])));
+        assert_eq!(operations[3].operands[1], Primitives::Str(b"0f0e".to_vec()));
+        // With `/L` the data may contain anything.
+        assert_eq!(operations[5].operands[1], Primitives::Str(b"E\n".to_vec()));
+    }
+
+    #[test]
+    fn multiply_matrices() {
+        let scale = [2.0, 0.0, 0.0, 3.0, 0.0, 0.0];
+        let translate = [1.0, 0.0, 0.0, 1.0, 10.0, 20.0];
+        assert_eq!(multiply(&scale, &translate), [2.0, 0.0, 0.0, 3.0, 10.0, 20.0]);
+        assert_eq!(multiply(&translate, &scale), [2.0, 0.0, 0.0, 3.0, 20.0, 60.0]);
+    }
+}
@@ -0,0 +1,223 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// A point in time as written in PDF date strings (`D:YYYYMMDDHHmmSSOHH'mm'`)
+// and XMP dates (ISO 8601).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PdfDate {
+    pub year: i32,
+    // 1 to 12.
+    pub month: u8,
+    // 1 to 31.
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    // Offset from UTC in minutes, `None` when the string doesn't say.
+    pub utc_offset: Option<i32>,
+}
+
+impl PdfDate {
+    pub fn new(year: i32, month: u8, day: u8, hour: u8, minute: u8, second: u8, utc_offset: Option<i32>) -> PdfDate {
+        PdfDate {
+            year: year,
+            month: month,
+            day: day,
+            hour: hour,
+            minute: minute,
+            second: second,
+            utc_offset: utc_offset,
+        }
+    }
+
+    // The current time in UTC.
+    pub fn now() -> PdfDate {
+        let secs = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as i64);
+        PdfDate::from_timestamp(secs)
+    }
+
+    // The UTC date for seconds since 1970-01-01T00:00:00Z.
+    pub fn from_timestamp(secs: i64) -> PdfDate {
+        let (days, secs) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+        let (year, month, day) = civil_from_days(days);
+        PdfDate::new(year, month, day, (secs / 3600) as u8, (secs / 60 % 60) as u8, (secs % 60) as u8, Some(0))
+    }
+
+    // Seconds since 1970-01-01T00:00:00Z. Dates without an offset are taken
+    // as UTC.
+    pub fn timestamp(&self) -> i64 {
+        let days = days_from_civil(self.year, self.month, self.day);
+        days * 86400 + self.hour as i64 * 3600 + self.minute as i64 * 60 + self.second as i64
+            - self.utc_offset.unwrap_or(0) as i64 * 60
+    }
+
+    // Parses a PDF date string. Like viewers do, the "D:" prefix and every
+    // field after the year are optional and out of range fields get their
+    // default.
+    pub fn parse(text: &str) -> Option<PdfDate> {
+        let text = text.trim();
+        let text = text.strip_prefix("D:").unwrap_or(text).as_bytes();
+        let mut pos = 0;
+        let year = digits(text, &mut pos, 4)? as i32;
+        let mut field = |range: std::ops::RangeInclusive<u32>, default: u32| {
+            digits(text, &mut pos, 2).filter(|v| range.contains(v)).unwrap_or(default) as u8
+        };
+        let month = field(1..=12, 1);
+        let day = field(1..=31, 1);
+        let hour = field(0..=23, 0);
+        let minute = field(0..=59, 0);
+        let second = field(0..=59, 0);
+
+        let utc_offset = match text.get(pos) {
+            Some(b'Z') => Some(0),
+            Some(&sign) if sign == b'+' || sign == b'-' => {
+                pos += 1;
+                let offset_hour = digits(text, &mut pos, 2).filter(|h| *h <= 23).unwrap_or(0) as i32;
+                if text.get(pos) == Some(&b'\'') {
+                    pos += 1;
+                }
+                let offset_minute = digits(text, &mut pos, 2).filter(|m| *m <= 59).unwrap_or(0) as i32;
+                let offset = offset_hour * 60 + offset_minute;
+                Some(if sign == b'-' { -offset } else { offset })
+            }
+            _ => None,
+        };
+        Some(PdfDate::new(year, month, day, hour, minute, second, utc_offset))
+    }
+
+    pub fn to_pdf_string(&self) -> String {
+        let mut text = format!(
+            "D:{:04}{:02}{:02}{:02}{:02}{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        );
+        match self.utc_offset {
+            Some(0) => text.push('Z'),
+            Some(offset) => {
+                let sign = if offset < 0 { '-' } else { '+' };
+                text.push_str(&format!("{}{:02}'{:02}'", sign, offset.abs() / 60, offset.abs() % 60));
+            }
+            None => (),
+        }
+        text
+    }
+
+    // Parses an XMP (ISO 8601) date like "2011-10-11T16:09:56.000-07:00".
+    // Only the year is required.
+    pub fn parse_xmp(text: &str) -> Option<PdfDate> {
+        let text = text.trim();
+        let (date, time) = match text.find('T') {
+            Some(t) => (&text[..t], Some(&text[t + 1..])),
+            None => (text, None),
+        };
+        let mut parts = date.split('-');
+        let year = parts.next()?.parse().ok()?;
+        let month = parts.next().map_or(Some(1), |m| m.parse().ok().filter(|m| (1..=12).contains(m)))?;
+        let day = parts.next().map_or(Some(1), |d| d.parse().ok().filter(|d| (1..=31).contains(d)))?;
+        let time = match time {
+            Some(time) => time,
+            None => return Some(PdfDate::new(year, month, day, 0, 0, 0, None)),
+        };
+
+        let zone = time.find(['Z', '+', '-']).unwrap_or(time.len());
+        let mut fields = time[..zone].split(':');
+        let hour = fields.next()?.parse().ok().filter(|h| *h <= 23)?;
+        let minute = fields.next()?.parse().ok().filter(|m| *m <= 59)?;
+        // Fractions of a second are dropped.
+        let second = match fields.next() {
+            Some(s) => s.split('.').next()?.parse().ok().filter(|s| *s <= 59)?,
+            None => 0,
+        };
+        let zone = &time[zone..];
+        let utc_offset = match zone.chars().next() {
+            Some('Z') => Some(0),
+            Some(sign) => {
+                let mut fields = zone[1..].split(':');
+                let offset_hour: i32 = fields.next()?.parse().ok()?;
+                let offset_minute: i32 = fields.next().map_or(Some(0), |m| m.parse().ok())?;
+                let offset = offset_hour * 60 + offset_minute;
+                Some(if sign == '-' { -offset } else { offset })
+            }
+            None => None,
+        };
+        Some(PdfDate::new(year, month, day, hour, minute, second, utc_offset))
+    }
+
+    pub fn to_xmp_string(&self) -> String {
+        let mut text = format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        );
+        match self.utc_offset {
+            Some(0) => text.push('Z'),
+            Some(offset) => {
+                let sign = if offset < 0 { '-' } else { '+' };
+                text.push_str(&format!("{}{:02}:{:02}", sign, offset.abs() / 60, offset.abs() % 60));
+            }
+            None => (),
+        }
+        text
+    }
+}
+
+// Reads `count` digits at `pos`, which is only advanced on success.
+fn digits(text: &[u8], pos: &mut usize, count: usize) -> Option<u32> {
+    let field = text.get(*pos..*pos + count)?;
+    if !field.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    *pos += count;
+    std::str::from_utf8(field).ok()?.parse().ok()
+}
+
+// Days since 1970-01-01 in the proleptic Gregorian calendar, after Howard
+// Hinnant's `days_from_civil`.
+fn days_from_civil(year: i32, month: u8, day: u8) -> i64 {
+    let year = year as i64 - if month <= 2 { 1 } else { 0 };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+fn civil_from_days(days: i64) -> (i32, u8, u8) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u8;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year as i32, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PdfDate;
+
+    #[test]
+    fn parse_pdf_dates() {
+        let date = PdfDate::parse("D:20111011160956-07'00'").unwrap();
+        assert_eq!(date, PdfDate::new(2011, 10, 11, 16, 9, 56, Some(-420)));
+        assert_eq!(date.to_pdf_string(), "D:20111011160956-07'00'");
+        assert_eq!(date.to_xmp_string(), "2011-10-11T16:09:56-07:00");
+        assert_eq!(PdfDate::parse_xmp("2011-10-11T16:09:56.000-07:00"), Some(date));
+
+        assert_eq!(PdfDate::parse("D:1999"), Some(PdfDate::new(1999, 1, 1, 0, 0, 0, None)));
+        assert_eq!(PdfDate::parse("20200230235960Z"), Some(PdfDate::new(2020, 2, 30, 23, 59, 0, Some(0))));
+        assert_eq!(PdfDate::parse("D:20200101+0530"), Some(PdfDate::new(2020, 1, 1, 0, 0, 0, Some(330))));
+        assert_eq!(PdfDate::parse("yesterday"), None);
+        assert_eq!(PdfDate::parse_xmp("2012-08-09"), Some(PdfDate::new(2012, 8, 9, 0, 0, 0, None)));
+    }
+
+    #[test]
+    fn timestamps() {
+        let date = PdfDate::parse("D:20111011160956-07'00'").unwrap();
+        assert_eq!(date.timestamp(), 1318374596);
+        assert_eq!(PdfDate::from_timestamp(1318374596), PdfDate::new(2011, 10, 11, 23, 9, 56, Some(0)));
+        assert_eq!(PdfDate::from_timestamp(-1), PdfDate::new(1969, 12, 31, 23, 59, 59, Some(0)));
+        assert_eq!(PdfDate::from_timestamp(951782400).day, 29);
+    }
+}
@@ -0,0 +1,204 @@
+use crate::document::PdfDocument;
+use crate::primitives::{Primitives, Ref};
+use crate::names::named_dests;
+use crate::error::Error;
+
+// How the target page is displayed. `None` coordinates or zoom leave the
+// current value unchanged.
+#[derive(Debug, Clone, PartialEq)]
+pub enum View {
+    XYZ { left: Option<f64>, top: Option<f64>, zoom: Option<f64> },
+    Fit,
+    FitH { top: Option<f64> },
+    FitV { left: Option<f64> },
+    FitR { left: f64, bottom: f64, right: f64, top: f64 },
+    FitB,
+    FitBH { top: Option<f64> },
+    FitBV { left: Option<f64> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Destination {
+    pub page: usize,
+    pub view: View,
+}
+
+impl View {
+    pub(crate) fn parse(params: &[Primitives]) -> Option<View> {
+        let num = |i: usize| params.get(i).and_then(|n| n.get_real());
+        let view = match params.first()?.get_name()? {
+            b"XYZ" => View::XYZ { left: num(1), top: num(2), zoom: num(3).filter(|&z| z != 0.0) },
+            b"Fit" => View::Fit,
+            b"FitH" => View::FitH { top: num(1) },
+            b"FitV" => View::FitV { left: num(1) },
+            b"FitR" => View::FitR { left: num(1)?, bottom: num(2)?, right: num(3)?, top: num(4)? },
+            b"FitB" => View::FitB,
+            b"FitBH" => View::FitBH { top: num(1) },
+            b"FitBV" => View::FitBV { left: num(1) },
+            _ => return None,
+        };
+        Some(view)
+    }
+
+    // The view as the tail of an explicit destination array.
+    pub fn to_array(&self) -> Vec<Primitives> {
+        let num = |n: &Option<f64>| n.map_or(Primitives::Null, Primitives::Real);
+        let name = |n: &str| Primitives::name(n.as_bytes().to_vec());
+        match self {
+            View::XYZ { left, top, zoom } => vec![name("XYZ"), num(left), num(top), num(zoom)],
+            View::Fit => vec![name("Fit")],
+            View::FitH { top } => vec![name("FitH"), num(top)],
+            View::FitV { left } => vec![name("FitV"), num(left)],
+            View::FitR { left, bottom, right, top } => vec![
+                name("FitR"),
+                Primitives::Real(*left),
+                Primitives::Real(*bottom),
+                Primitives::Real(*right),
+                Primitives::Real(*top),
+            ],
+            View::FitB => vec![name("FitB")],
+            View::FitBH { top } => vec![name("FitBH"), num(top)],
+            View::FitBV { left } => vec![name("FitBV"), num(left)],
+        }
+    }
+}
+
+impl PdfDocument {
+    // Resolves an explicit destination array, a name or string naming a
+    // destination, or a dictionary with `/D`. Destinations to pages that
+    // aren't in this document give `None`.
+    pub fn resolve_destination(&self, dest: &Primitives) -> Result<Option<Destination>, Error> {
+        let mut dest = self.resolve(dest)?;
+        let name = match &dest {
+            Primitives::Name(name) => Some(name.0.clone()),
+            other => other.get_string_bytes(),
+        };
+        if let Some(name) = name {
+            dest = match self.lookup_dest(&name)? {
+                Some(dest) => dest,
+                None => return Ok(None),
+            };
+        }
+        if let Some(d) = dest.get("D") {
+            dest = self.resolve(d)?;
+        }
+        let array = match dest.get_array() {
+            Some(array) if !array.is_empty() => array,
+            _ => return Ok(None),
+        };
+        let page = match &array[0] {
+            Primitives::Ref(num, gen) => self.page_refs()?.iter().position(|&r| r == Ref::new(*num, *gen)),
+            // Some writers use page numbers, as in remote destinations.
+            Primitives::Int(index) => Some(*index as usize).filter(|&i| i < self.page_refs().map_or(0, |p| p.len())),
+            _ => None,
+        };
+        Ok(page.map(|page| Destination {
+            page: page,
+            // A missing or unknown view fits the page like viewers do.
+            view: View::parse(&array[1..]).unwrap_or(View::Fit),
+        }))
+    }
+
+    pub fn named_destination(&self, name: &[u8]) -> Result<Option<Destination>, Error> {
+        match self.lookup_dest(name)? {
+            Some(dest) => self.resolve_destination(&dest),
+            None => Ok(None),
+        }
+    }
+
+    // All named destinations that point to pages of this document.
+    pub fn named_destinations(&self) -> Result<Vec<(Vec<u8>, Destination)>, Error> {
+        let mut dests = Vec::new();
+        for (name, dest) in named_dests(self)? {
+            if let Some(dest) = self.resolve_destination(&dest)? {
+                dests.push((name, dest));
+            }
+        }
+        Ok(dests)
+    }
+
+    // The raw value of a named destination, from the `/Dests` name tree or
+    // the PDF 1.1 `/Dests` dictionary.
+    fn lookup_dest(&self, name: &[u8]) -> Result<Option<Primitives>, Error> {
+        if let Some(tree) = self.name_tree("Dests")? {
+            if let Some(dest) = tree.get(&name.to_vec())? {
+                return Ok(Some(self.resolve(&dest)?));
+            }
+        }
+        if let Primitives::Dict(dests) = self.get(&self.catalog()?, "Dests")? {
+            if let Some(dest) = dests.get(name) {
+                return Ok(Some(self.resolve(dest)?));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::PdfDocument;
+    use crate::primitives::{Dictionary, Name, Primitives};
+    use super::{Destination, View};
+
+    #[test]
+    fn resolve_named_destinations() {
+        let mut doc = PdfDocument::open("tests/examples/sample.pdf").unwrap();
+        let pages = doc.page_refs().unwrap();
+        let explicit = |i: usize, view: &View| {
+            let mut array = vec![pages[i].into()];
+            array.extend(view.to_array());
+            Primitives::Array(array)
+        };
+
+        let fit_h = View::FitH { top: Some(700.0) };
+        let xyz = View::XYZ { left: Some(10.0), top: None, zoom: Some(1.5) };
+        let mut with_d = Dictionary::new();
+        with_d.insert(Name::from("D"), explicit(1, &xyz));
+        let mut tree = Dictionary::new();
+        tree.insert(Name::from("Names"), Primitives::Array(vec![
+            Primitives::Str(b"intro".to_vec()),
+            explicit(0, &fit_h),
+            Primitives::Str(b"second".to_vec()),
+            Primitives::Dict(with_d),
+        ]));
+        let mut names = Dictionary::new();
+        names.insert(Name::from("Dests"), doc.add_object(Primitives::Dict(tree)).into());
+        let mut legacy = Dictionary::new();
+        legacy.insert(Name::from("old"), explicit(1, &View::Fit));
+
+        let catalog_ref = doc.catalog_ref().unwrap();
+        let mut catalog = doc.catalog().unwrap();
+        catalog.insert(Name::from("Names"), Primitives::Dict(names));
+        catalog.insert(Name::from("Dests"), Primitives::Dict(legacy));
+        doc.replace_object(catalog_ref, Primitives::Dict(catalog)).unwrap();
+
+        assert_eq!(doc.named_destination(b"intro").unwrap(), Some(Destination { page: 0, view: fit_h }));
+        assert_eq!(doc.named_destination(b"second").unwrap(), Some(Destination { page: 1, view: xyz }));
+        assert_eq!(doc.named_destination(b"old").unwrap(), Some(Destination { page: 1, view: View::Fit }));
+        assert_eq!(doc.named_destination(b"missing").unwrap(), None);
+        assert_eq!(doc.named_destinations().unwrap().len(), 3);
+
+        let by_name = Primitives::Str(b"second".to_vec());
+        assert_eq!(doc.resolve_destination(&by_name).unwrap().map(|d| d.page), Some(1));
+    }
+
+    #[test]
+    fn parse_views() {
+        let doc = PdfDocument::open("tests/examples/dummy.pdf").unwrap();
+        let page = doc.page_refs().unwrap()[0];
+        let dest = Primitives::Array(vec![
+            page.into(),
+            Primitives::name(b"FitR".to_vec()),
+            Primitives::Int(1),
+            Primitives::Int(2),
+            Primitives::Real(3.5),
+            Primitives::Int(4),
+        ]);
+        let view = View::FitR { left: 1.0, bottom: 2.0, right: 3.5, top: 4.0 };
+        assert_eq!(doc.resolve_destination(&dest).unwrap(), Some(Destination { page: 0, view: view }));
+        // XYZ with a zero zoom keeps the current zoom.
+        let dest = Primitives::Array(vec![page.into(), Primitives::name(b"XYZ".to_vec()), Primitives::Null, Primitives::Int(5), Primitives::Int(0)]);
+        let view = View::XYZ { left: None, top: Some(5.0), zoom: None };
+        assert_eq!(doc.resolve_destination(&dest).unwrap().unwrap().view, view);
+    }
+}
@@ -1 +1,234 @@
-pub struct PdfDocument;
+use std::sync::{Arc, RwLock};
+
+use crate::stream::Source;
+use crate::xref::XRef;
+use crate::linearization::Linearization;
+use crate::primitives::{Dictionary, Primitives, Ref};
+use crate::error::Error;
+
+// A loaded document. All read access goes through `&self` and the type is
+// `Send + Sync`, so pages can be processed from several threads at once:
+// every fetch reads through its own clone of the source and parsed objects
+// are shared through a locked cache.
+pub struct PdfDocument {
+    pub(crate) xref: XRef<Box<dyn Source>>,
+    pub(crate) linearization: Option<Linearization>,
+    pub(crate) version: Option<String>,
+    // Page references in document order, filled on first use.
+    pub(crate) page_refs: RwLock<Option<Arc<Vec<Ref>>>>,
+    // Unique among the documents loaded by this process, so state kept for
+    // a particular document can tell it apart from others.
+    pub(crate) id: u64,
+}
+
+impl PdfDocument {
+    // An empty document with a catalog and an empty page tree.
+    pub fn new() -> PdfDocument {
+        // The generated file is always valid.
+        PdfDocument::load_data(crate::writer::empty_file()).unwrap()
+    }
+
+    // The version from the file header, e.g. "1.7".
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    pub fn linearization(&self) -> Option<&Linearization> {
+        self.linearization.as_ref()
+    }
+
+    pub fn trailer(&self) -> &Dictionary {
+        // `XRef::parse` fails without a trailer.
+        self.xref.trailer().unwrap()
+    }
+
+    pub fn fetch(&self, r: Ref) -> Result<Primitives, Error> {
+        self.xref.fetch(r)
+    }
+
+    // Stores `obj` as a new indirect object. Numbers of deleted objects are
+    // reused with their next generation.
+    pub fn add_object(&mut self, obj: Primitives) -> Ref {
+        self.page_refs_changed();
+        self.xref.add(obj)
+    }
+
+    pub fn replace_object(&mut self, r: Ref, obj: Primitives) -> Result<(), Error> {
+        self.page_refs_changed();
+        self.xref.replace(r, obj)
+    }
+
+    // Removes the object, references to it resolve to null afterwards.
+    pub fn delete_object(&mut self, r: Ref) -> Result<(), Error> {
+        self.page_refs_changed();
+        self.xref.delete(r)
+    }
+
+    // Whether objects were added, replaced or deleted since loading.
+    pub fn is_modified(&self) -> bool {
+        self.xref.is_modified()
+    }
+
+    fn page_refs_changed(&mut self) {
+        *self.page_refs.get_mut().unwrap() = None;
+    }
+
+    // Follows `obj` if it is a reference, otherwise returns it as is.
+    pub fn resolve(&self, obj: &Primitives) -> Result<Primitives, Error> {
+        match obj {
+            Primitives::Ref(num, gen) => self.fetch(Ref::new(*num, *gen)),
+            _ => Ok(obj.clone()),
+        }
+    }
+
+    // Looks up `key` in `dict` and resolves the value. Missing keys give
+    // `Primitives::Null`.
+    pub fn get(&self, dict: &Dictionary, key: &str) -> Result<Primitives, Error> {
+        match dict.get(key.as_bytes()) {
+            Some(obj) => self.resolve(obj),
+            None => Ok(Primitives::Null),
+        }
+    }
+
+    pub fn catalog_ref(&self) -> Result<Ref, Error> {
+        self.trailer()
+            .get(&b"Root"[..])
+            .and_then(|root| root.get_ref())
+            .ok_or(Error::InvalidFile("trailer has no /Root"))
+    }
+
+    // The document catalog (`/Root` of the trailer).
+    pub fn catalog(&self) -> Result<Dictionary, Error> {
+        match self.fetch(self.catalog_ref()?)? {
+            Primitives::Dict(dict) => Ok(dict),
+            _ => Err(Error::InvalidFile("catalog is not a dictionary")),
+        }
+    }
+}
+
+impl Default for PdfDocument {
+    fn default() -> Self {
+        PdfDocument::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use rayon::prelude::*;
+
+    use crate::stream::Stream;
+    use crate::primitives::{Primitives, Ref};
+    use crate::error::Error;
+    use super::PdfDocument;
+
+    const EXAMPLES: [(&str, usize); 5] = [
+        ("tests/examples/dummy.pdf", 1),
+        ("tests/examples/sample.pdf", 2),
+        ("tests/examples/PDF_sample.pdf", 1),
+        ("tests/examples/140514041111253731pdf1.pdf", 1),
+        ("tests/examples/7a79c35f7ce0704dec63be82440c8182.pdf", 1),
+    ];
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn load_examples() {
+        assert_send_sync::<PdfDocument>();
+        for (path, pages) in EXAMPLES {
+            let doc = PdfDocument::open(path).unwrap();
+            assert_eq!(doc.page_count().unwrap(), pages, "{}", path);
+            assert!(doc.catalog().unwrap().contains_key(&b"Pages"[..]));
+        }
+    }
+
+    #[test]
+    fn load_linearized_file() {
+        let data = fs::read("tests/examples/140514041111253731pdf1.pdf").unwrap();
+        let doc = PdfDocument::load(Stream::from_bytes(&data[..8355])).unwrap();
+        assert_eq!(doc.linearization().map(|l| l.object_number_first), Some(28));
+        assert_eq!(doc.page(0).unwrap().reference, Ref::new(28, 0));
+    }
+
+    #[test]
+    fn read_pages_in_parallel() {
+        let docs: Vec<PdfDocument> = EXAMPLES.iter().map(|(path, _)| PdfDocument::open(path).unwrap()).collect();
+        let jobs: Vec<(&PdfDocument, usize)> = docs.iter()
+            .flat_map(|doc| (0..doc.page_count().unwrap()).map(move |i| (doc, i)))
+            .collect();
+
+        let parallel: Vec<Vec<u8>> = jobs.par_iter()
+            .map(|&(doc, i)| doc.page(i).unwrap().contents(doc).unwrap())
+            .collect();
+
+        let sequential: Vec<Vec<u8>> = EXAMPLES.iter()
+            .map(|(path, _)| PdfDocument::open(path).unwrap())
+            .flat_map(|doc| doc.pages().unwrap().iter().map(|p| p.contents(&doc).unwrap()).collect::<Vec<_>>())
+            .collect();
+        assert!(parallel.iter().all(|c| !c.is_empty()));
+        assert_eq!(parallel, sequential);
+    }
+
+    #[test]
+    fn add_replace_and_delete_objects() {
+        let mut doc = PdfDocument::open("tests/examples/sample.pdf").unwrap();
+        let size = doc.xref.size();
+        assert!(!doc.is_modified());
+
+        let a = doc.add_object(Primitives::Int(1));
+        assert_eq!(a, Ref::new(size, 0));
+        assert_eq!(doc.fetch(a).unwrap(), Primitives::Int(1));
+        doc.replace_object(a, Primitives::Int(2)).unwrap();
+        assert_eq!(doc.fetch(a).unwrap(), Primitives::Int(2));
+        assert!(doc.is_modified());
+
+        // Deleting bumps the generation; the number is reused with it.
+        doc.delete_object(a).unwrap();
+        assert_eq!(doc.fetch(a).unwrap(), Primitives::Null);
+        assert!(matches!(doc.replace_object(a, Primitives::Null), Err(Error::MissingObject(_))));
+        let b = doc.add_object(Primitives::Int(3));
+        assert_eq!(b, Ref::new(size, 1));
+        assert_eq!(doc.fetch(a).unwrap(), Primitives::Null);
+        assert_eq!(doc.fetch(b).unwrap(), Primitives::Int(3));
+
+        // Objects from the file can be replaced and deleted too.
+        let page = doc.page_refs().unwrap()[1];
+        doc.delete_object(page).unwrap();
+        assert_eq!(doc.fetch(page).unwrap(), Primitives::Null);
+        assert_eq!(doc.page_count().unwrap(), 1);
+        assert!(doc.delete_object(page).is_err());
+        assert!(doc.delete_object(Ref::new(0, 65535)).is_err());
+    }
+
+    #[test]
+    fn fetch_with_bad_references() {
+        let mut data = b"%PDF-1.5\n".to_vec();
+        let mut offsets = Vec::new();
+        for obj in [&b"1 0 obj\n<< /Type /Catalog /Pages 2 0 R >>\nendobj\n"[..], b"2 0 obj\n<< /Type /Pages /Kids [] /Count 0 >>\nendobj\n"] {
+            offsets.push(data.len() as u16);
+            data.extend_from_slice(obj);
+        }
+        let xref = data.len() as u16;
+        // Object 3 is stored in object stream 3, objects 4 and 5 in each
+        // other.
+        let mut entries = vec![0, 0, 0, 255];
+        for (kind, field) in [(1, offsets[0]), (1, offsets[1]), (2, 3), (2, 5), (2, 4), (1, xref)] {
+            entries.push(kind);
+            entries.extend_from_slice(&field.to_be_bytes());
+            entries.push(0);
+        }
+        data.extend_from_slice(format!("6 0 obj\n<< /Type /XRef /Size 7 /W [1 2 1] /Root 1 0 R /Length {} >>\nstream\n", entries.len()).as_bytes());
+        data.extend_from_slice(&entries);
+        data.extend_from_slice(format!("\nendstream\nendobj\nstartxref\n{}\n%%EOF\n", xref).as_bytes());
+
+        let doc = PdfDocument::load_data(data).unwrap();
+        assert_eq!(doc.page_count().unwrap(), 0);
+        // Another generation than the one in use is null, cached or not.
+        assert_eq!(doc.fetch(Ref::new(2, 1)).unwrap(), Primitives::Null);
+        assert!(doc.fetch(Ref::new(2, 0)).unwrap().get_dict().is_some());
+        assert_eq!(doc.fetch(Ref::new(2, 1)).unwrap(), Primitives::Null);
+        assert!(matches!(doc.fetch(Ref::new(3, 0)), Err(Error::InvalidFile(_))));
+        assert!(matches!(doc.fetch(Ref::new(4, 0)), Err(Error::InvalidFile(_))));
+    }
+}
@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::primitives::Ref;
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("This pdf is invalid: ${0}")]
@@ -10,4 +12,28 @@ pub enum Error {
 
     #[error("Object parsing error")]
     ParserError,
+
+    #[error("Unsupported stream filter: {0}")]
+    UnsupportedFilter(String),
+
+    #[error("Unsupported color space: {0}")]
+    UnsupportedColorSpace(String),
+
+    #[error("Invalid function: {0}")]
+    InvalidFunction(&'static str),
+
+    #[error("Stream data is corrupted: {0}")]
+    DecodeError(&'static str),
+
+    #[error("XML is malformed: {0}")]
+    InvalidXml(&'static str),
+
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(&'static str),
+
+    #[error("Object {0:?} does not exist")]
+    MissingObject(Ref),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
 }
@@ -0,0 +1,381 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::document::PdfDocument;
+use crate::primitives::{Dictionary, Name, Primitives, Ref};
+use crate::form::{check_value, FieldType, FieldValue, FormField, FIELD_NO_EXPORT};
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::stream::Stream;
+use crate::serializer::write_object;
+use crate::utils::encode_text;
+use crate::xml::{self, XmlNode};
+use crate::error::Error;
+
+const XFDF_NAMESPACE: &str = "http://ns.adobe.com/xfdf/";
+
+// Fields nested deeper than this are ignored, on top of the cycle check.
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldData {
+    // Fully qualified field name.
+    pub name: String,
+    pub value: FieldValue,
+}
+
+// Form field values as exchanged in FDF and XFDF files. Neither format
+// says what type a field is, values are matched to the fields when they
+// are imported.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FormData {
+    // The PDF file the data belongs to, `/F` in FDF and `<f href>` in XFDF.
+    pub file: Option<String>,
+    pub fields: Vec<FieldData>,
+}
+
+impl FormData {
+    // Reads the `/FDF` dictionary of the trailer's `/Root`. FDF files use
+    // PDF syntax but have no cross-reference table, so all objects are read
+    // in order.
+    pub fn parse_fdf(data: &[u8]) -> Result<FormData, Error> {
+        if !data.starts_with(b"%FDF-") {
+            return Err(Error::InvalidArgument("data is not an FDF file"));
+        }
+        let data = data.to_vec();
+        let mut parser = Parser::new(Lexer::new(Stream::from_vec(&data)), true);
+        let mut objects = HashMap::new();
+        let mut trailer = None;
+        let mut previous: [Primitives; 2] = [Primitives::Null, Primitives::Null];
+        loop {
+            let obj = parser.get_obj()?;
+            match &obj {
+                Primitives::EOF => break,
+                Primitives::Cmd(cmd) if cmd == b"obj" => {
+                    if let [Primitives::Int(num), Primitives::Int(gen)] = &previous {
+                        objects.insert(Ref::new(*num as u32, *gen as u32), parser.get_obj()?);
+                    }
+                }
+                Primitives::Cmd(cmd) if cmd == b"trailer" => trailer = Some(parser.get_obj()?),
+                _ => (),
+            }
+            previous[0] = std::mem::replace(&mut previous[1], obj);
+        }
+
+        let resolve = |obj: &Primitives| -> Primitives {
+            match obj.get_ref() {
+                Some(r) => objects.get(&r).cloned().unwrap_or(Primitives::Null),
+                None => obj.clone(),
+            }
+        };
+        let root = trailer.as_ref().and_then(|t| t.get("Root")).map(&resolve).unwrap_or(Primitives::Null);
+        let fdf = match root.get("FDF").map(&resolve) {
+            Some(Primitives::Dict(fdf)) => fdf,
+            _ => return Err(Error::InvalidArgument("FDF file has no /FDF dictionary")),
+        };
+        let file = match fdf.get(&b"F"[..]).map(&resolve) {
+            Some(Primitives::Dict(spec)) => spec.get(&b"UF"[..]).or_else(|| spec.get(&b"F"[..])).map(&resolve).and_then(|f| f.get_text()),
+            Some(file) => file.get_text(),
+            None => None,
+        };
+
+        let mut form = FormData {
+            file: file,
+            fields: Vec::new(),
+        };
+        let mut visited = HashSet::new();
+        let fields = fdf.get(&b"Fields"[..]).map(&resolve).unwrap_or(Primitives::Null);
+        let mut stack: Vec<(Primitives, String, usize)> = fields.get_array().map_or(Vec::new(), |f| {
+            f.iter().rev().map(|f| (f.clone(), String::new(), 0)).collect()
+        });
+        while let Some((node, parent, depth)) = stack.pop() {
+            if node.get_ref().is_some_and(|r| !visited.insert(r)) || depth > MAX_DEPTH {
+                continue;
+            }
+            let field = match resolve(&node) {
+                Primitives::Dict(field) => field,
+                _ => continue,
+            };
+            let name = match (field.get(&b"T"[..]).map(&resolve).and_then(|t| t.get_text()), parent.is_empty()) {
+                (Some(partial), true) => partial,
+                (Some(partial), false) => format!("{}.{}", parent, partial),
+                (None, _) => parent,
+            };
+            let value = match field.get(&b"V"[..]).map(&resolve) {
+                Some(Primitives::Name(state)) => Some(FieldValue::State(String::from_utf8_lossy(&state.0).into_owned())),
+                Some(Primitives::Array(values)) => Some(FieldValue::Selection(values.iter().filter_map(|v| resolve(v).get_text()).collect())),
+                Some(value) => value.get_text().map(FieldValue::Text),
+                None => None,
+            };
+            if let Some(value) = value {
+                form.fields.push(FieldData { name: name.clone(), value: value });
+            }
+            if let Some(Primitives::Array(kids)) = field.get(&b"Kids"[..]).map(&resolve) {
+                stack.extend(kids.iter().rev().map(|k| (k.clone(), name.clone(), depth + 1)));
+            }
+        }
+        Ok(form)
+    }
+
+    pub fn parse_xfdf(data: &[u8]) -> Result<FormData, Error> {
+        let root = xml::parse(data)?;
+        if root.name != "xfdf" {
+            return Err(Error::InvalidArgument("data is not an XFDF file"));
+        }
+        let mut form = FormData {
+            file: root.child("f").and_then(|f| f.attribute("href")).map(String::from),
+            fields: Vec::new(),
+        };
+        if let Some(fields) = root.child("fields") {
+            for field in fields.children_named("field") {
+                read_xfdf_field(field, "", 0, &mut form.fields);
+            }
+        }
+        Ok(form)
+    }
+
+    pub fn to_fdf(&self) -> Vec<u8> {
+        let mut fdf = Dictionary::new();
+        fdf.insert(Name::from("Fields"), Primitives::Array(field_tree(&self.fields).iter().map(fdf_field).collect()));
+        if let Some(file) = &self.file {
+            fdf.insert(Name::from("F"), Primitives::Str(encode_text(file)));
+        }
+        let mut catalog = Dictionary::new();
+        catalog.insert(Name::from("FDF"), Primitives::Dict(fdf));
+
+        let mut out = b"%FDF-1.2\n%\xe2\xe3\xcf\xd3\n1 0 obj\n".to_vec();
+        write_object(&mut out, &Primitives::Dict(catalog)).unwrap();
+        out.extend_from_slice(b"\nendobj\ntrailer\n<</Root 1 0 R>>\n%%EOF\n");
+        out
+    }
+
+    pub fn to_xfdf(&self) -> Vec<u8> {
+        let mut root = XmlNode::new("xfdf");
+        root.attributes.push(("xmlns".to_string(), XFDF_NAMESPACE.to_string()));
+        root.attributes.push(("xml:space".to_string(), "preserve".to_string()));
+        if let Some(file) = &self.file {
+            let mut f = XmlNode::new("f");
+            f.attributes.push(("href".to_string(), file.clone()));
+            root.children.push(f);
+        }
+        let mut fields = XmlNode::new("fields");
+        fields.children = field_tree(&self.fields).iter().map(xfdf_field).collect();
+        root.children.push(fields);
+
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        root.write(&mut out);
+        out.push('\n');
+        out.into_bytes()
+    }
+}
+
+fn read_xfdf_field(node: &XmlNode, parent: &str, depth: usize, fields: &mut Vec<FieldData>) {
+    if depth > MAX_DEPTH {
+        return;
+    }
+    let name = match node.attribute("name") {
+        Some(partial) if !parent.is_empty() => format!("{}.{}", parent, partial),
+        Some(partial) => partial.to_string(),
+        None => parent.to_string(),
+    };
+    let values: Vec<String> = node.children_named("value").map(|v| v.text.clone()).collect();
+    let value = match values.len() {
+        0 => None,
+        1 => values.into_iter().next().map(FieldValue::Text),
+        _ => Some(FieldValue::Selection(values)),
+    };
+    if let Some(value) = value {
+        fields.push(FieldData { name: name.clone(), value: value });
+    }
+    for kid in node.children_named("field") {
+        read_xfdf_field(kid, &name, depth + 1, fields);
+    }
+}
+
+// Fields grouped by the parts of their names, as both formats nest them.
+struct FieldNode<'a> {
+    partial: &'a str,
+    value: Option<&'a FieldValue>,
+    kids: Vec<FieldNode<'a>>,
+}
+
+fn field_tree(fields: &[FieldData]) -> Vec<FieldNode<'_>> {
+    let mut roots: Vec<FieldNode> = Vec::new();
+    for field in fields {
+        let mut level = &mut roots;
+        let parts: Vec<&str> = field.name.split('.').collect();
+        for (i, partial) in parts.iter().enumerate() {
+            let index = match level.iter().position(|n| n.partial == *partial) {
+                Some(index) => index,
+                None => {
+                    level.push(FieldNode { partial: partial, value: None, kids: Vec::new() });
+                    level.len() - 1
+                }
+            };
+            if i == parts.len() - 1 {
+                level[index].value = Some(&field.value);
+            }
+            level = &mut level[index].kids;
+        }
+    }
+    roots
+}
+
+fn fdf_field(node: &FieldNode) -> Primitives {
+    let mut dict = Dictionary::new();
+    dict.insert(Name::from("T"), Primitives::Str(encode_text(node.partial)));
+    match node.value {
+        Some(FieldValue::Text(text)) => {
+            dict.insert(Name::from("V"), Primitives::Str(encode_text(text)));
+        }
+        Some(FieldValue::State(state)) => {
+            dict.insert(Name::from("V"), Primitives::name(state.as_bytes().to_vec()));
+        }
+        Some(FieldValue::Selection(values)) => {
+            let values = values.iter().map(|v| Primitives::Str(encode_text(v))).collect();
+            dict.insert(Name::from("V"), Primitives::Array(values));
+        }
+        None => (),
+    }
+    if !node.kids.is_empty() {
+        dict.insert(Name::from("Kids"), Primitives::Array(node.kids.iter().map(fdf_field).collect()));
+    }
+    Primitives::Dict(dict)
+}
+
+fn xfdf_field(node: &FieldNode) -> XmlNode {
+    let mut field = XmlNode::new("field");
+    field.attributes.push(("name".to_string(), node.partial.to_string()));
+    let values = match node.value {
+        Some(FieldValue::Text(value)) | Some(FieldValue::State(value)) => vec![value.clone()],
+        Some(FieldValue::Selection(values)) => values.clone(),
+        None => Vec::new(),
+    };
+    for value in values {
+        let mut node = XmlNode::new("value");
+        node.text = value;
+        field.children.push(node);
+    }
+    field.children.extend(node.kids.iter().map(xfdf_field));
+    field
+}
+
+impl PdfDocument {
+    // The current values of the form's fields, leaving out fields marked
+    // as not to be exported, buttons and signatures.
+    pub fn form_data(&self) -> Result<FormData, Error> {
+        let mut data = FormData::default();
+        for field in self.form_fields()? {
+            if field.flags & FIELD_NO_EXPORT != 0 {
+                continue;
+            }
+            if let (FieldType::PushButton, _) | (FieldType::Signature, _) | (_, None) = (field.field_type, &field.value) {
+                continue;
+            }
+            data.fields.push(FieldData { name: field.name, value: field.value.unwrap() });
+        }
+        Ok(data)
+    }
+
+    // Sets the form's fields to the imported values and returns how many
+    // were set. Values for fields the form doesn't have are ignored. All
+    // values are checked first, so nothing is changed when one is rejected.
+    pub fn import_form_data(&mut self, data: &FormData) -> Result<usize, Error> {
+        let fields: HashMap<String, FormField> = self.form_fields()?.into_iter().map(|f| (f.name.clone(), f)).collect();
+        let mut values = Vec::new();
+        for field in &data.fields {
+            let form_field = match fields.get(&field.name) {
+                Some(form_field) => form_field,
+                None => continue,
+            };
+            // Values read from XFDF are all text, so they are converted to
+            // what the field expects.
+            let text = match &field.value {
+                FieldValue::Text(value) | FieldValue::State(value) => vec![value.clone()],
+                FieldValue::Selection(values) => values.clone(),
+            };
+            let value = match form_field.field_type {
+                FieldType::Checkbox | FieldType::Radio => FieldValue::State(text.into_iter().next().unwrap_or_else(|| "Off".to_string())),
+                FieldType::Choice => FieldValue::Selection(text),
+                _ => FieldValue::Text(text.join("\n")),
+            };
+            check_value(form_field, &value)?;
+            values.push((&field.name, value));
+        }
+
+        let count = values.len();
+        for (name, value) in values {
+            self.set_value(fields[name].clone(), value)?;
+        }
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::form::FieldValue;
+    use crate::form::tests::document_with_form;
+    use super::{FieldData, FormData};
+
+    #[test]
+    fn fdf_round_trip() {
+        let doc = document_with_form();
+        let mut data = doc.form_data().unwrap();
+        assert_eq!(data.fields, [
+            FieldData { name: "name".to_string(), value: FieldValue::Text("Alice".to_string()) },
+            FieldData { name: "address.street".to_string(), value: FieldValue::Text("Main St".to_string()) },
+            FieldData { name: "agree".to_string(), value: FieldValue::State("Yes".to_string()) },
+            FieldData { name: "color".to_string(), value: FieldValue::State("Blue".to_string()) },
+            FieldData { name: "country".to_string(), value: FieldValue::Selection(vec!["de".to_string()]) },
+        ]);
+        data.file = Some("form.pdf".to_string());
+        let fdf = data.to_fdf();
+        assert_eq!(FormData::parse_fdf(&fdf).unwrap(), data);
+
+        let fdf = b"%FDF-1.2\n1 0 obj\n<< /FDF << /Fields 2 0 R /F (form.pdf) >> >>\nendobj\n\
+            2 0 obj\n[ << /T (address) /Kids [ << /T (city) /V (Paris) >> ] >> << /T (agree) /V /Off >> << /T (unknown) /V (x) >> ]\nendobj\n\
+            trailer\n<< /Root 1 0 R >>\n%%EOF\n";
+        let data = FormData::parse_fdf(fdf).unwrap();
+        assert_eq!(data.file.as_deref(), Some("form.pdf"));
+        let mut doc = document_with_form();
+        assert_eq!(doc.import_form_data(&data).unwrap(), 2);
+        assert_eq!(doc.form_field("address.city").unwrap().unwrap().value, Some(FieldValue::Text("Paris".to_string())));
+        assert_eq!(doc.form_field("agree").unwrap().unwrap().value, Some(FieldValue::State("Off".to_string())));
+        assert!(FormData::parse_fdf(b"%PDF-1.4\n").is_err());
+    }
+
+    #[test]
+    fn xfdf_round_trip() {
+        let data = document_with_form().form_data().unwrap();
+        let xfdf = String::from_utf8(data.to_xfdf()).unwrap();
+        assert!(xfdf.contains(r#"<field name="address"><field name="street"><value>Main St</value></field></field>"#));
+        let parsed = FormData::parse_xfdf(xfdf.as_bytes()).unwrap();
+        assert_eq!(parsed.fields[1], data.fields[1]);
+        // States and single selections come back as text.
+        assert_eq!(parsed.fields[2].value, FieldValue::Text("Yes".to_string()));
+
+        let xfdf = br#"<?xml version="1.0" encoding="UTF-8"?>
+            <xfdf xmlns="http://ns.adobe.com/xfdf/" xml:space="preserve">
+              <fields>
+                <field name="color"><value>Red</value></field>
+                <field name="country"><value>France</value></field>
+                <field name="name"><value>Bob &amp; Co</value></field>
+              </fields>
+            </xfdf>"#;
+        let mut doc = document_with_form();
+        assert_eq!(doc.import_form_data(&FormData::parse_xfdf(xfdf).unwrap()).unwrap(), 3);
+        let fields = doc.form_fields().unwrap();
+        assert_eq!(fields[0].value, Some(FieldValue::Text("Bob & Co".to_string())));
+        assert_eq!(fields[4].value, Some(FieldValue::State("Red".to_string())));
+        assert_eq!(fields[5].value, Some(FieldValue::Selection(vec!["France".to_string()])));
+
+        // Nothing is imported when a value is rejected.
+        let xfdf = br#"<xfdf xmlns="http://ns.adobe.com/xfdf/">
+              <fields>
+                <field name="name"><value>Eve</value></field>
+                <field name="color"><value>Purple</value></field>
+              </fields>
+            </xfdf>"#;
+        assert!(doc.import_form_data(&FormData::parse_xfdf(xfdf).unwrap()).is_err());
+        assert_eq!(doc.form_field("name").unwrap().unwrap().value, Some(FieldValue::Text("Bob & Co".to_string())));
+    }
+}
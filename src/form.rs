@@ -0,0 +1,831 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::document::PdfDocument;
+use crate::primitives::{Dictionary, Name, PdfStream, Primitives, Ref};
+use crate::appearance::{list_content, text_content, DefaultAppearance, FontMetrics};
+use crate::serializer::format_real;
+use crate::utils::encode_text;
+use crate::error::Error;
+
+// Field attributes that terminal fields inherit from their ancestors.
+const INHERITABLE: [&str; 8] = ["FT", "Ff", "V", "DV", "DA", "Q", "Opt", "MaxLen"];
+
+// Fields nested deeper than this are ignored, on top of the cycle check.
+const MAX_DEPTH: usize = 64;
+
+// Annotation flags that keep a widget from being flattened.
+const FLAG_HIDDEN: i64 = 2;
+const FLAG_NO_VIEW: i64 = 32;
+
+// Field flags (`/Ff`), common ones first.
+pub const FIELD_READ_ONLY: u32 = 1;
+pub const FIELD_REQUIRED: u32 = 1 << 1;
+pub const FIELD_NO_EXPORT: u32 = 1 << 2;
+pub const FIELD_MULTILINE: u32 = 1 << 12;
+pub const FIELD_PASSWORD: u32 = 1 << 13;
+pub const FIELD_NO_TOGGLE_TO_OFF: u32 = 1 << 14;
+pub const FIELD_RADIO: u32 = 1 << 15;
+pub const FIELD_PUSH_BUTTON: u32 = 1 << 16;
+pub const FIELD_COMBO: u32 = 1 << 17;
+pub const FIELD_EDIT: u32 = 1 << 18;
+pub const FIELD_MULTI_SELECT: u32 = 1 << 21;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FieldType {
+    Text,
+    Checkbox,
+    Radio,
+    PushButton,
+    // A list box, or a combo box when `FIELD_COMBO` is set.
+    Choice,
+    Signature,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Text(String),
+    // The appearance state of a checkbox or radio group, e.g. "Yes" or
+    // "Off".
+    State(String),
+    // The selected options of a choice field, by export value.
+    Selection(Vec<String>),
+}
+
+// An entry of `/Opt`: choice fields list their options, checkboxes and
+// radio buttons may give export values for their widgets.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldOption {
+    pub export: String,
+    pub display: String,
+}
+
+// A widget annotation that shows a field on a page.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Widget {
+    // `None` for widgets stored directly in an array.
+    pub reference: Option<Ref>,
+    // `None` if the widget isn't on any page.
+    pub page: Option<usize>,
+    pub rect: [f64; 4],
+    // The appearance state that turns a checkbox or radio button on.
+    pub on_state: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormField {
+    // Fully qualified name, the partial names of the field and its
+    // ancestors joined by periods.
+    pub name: String,
+    pub field_type: FieldType,
+    pub value: Option<FieldValue>,
+    pub default_value: Option<FieldValue>,
+    pub options: Vec<FieldOption>,
+    // `/Ff`, see the `FIELD_*` constants.
+    pub flags: u32,
+    // The user facing name from `/TU`.
+    pub alternate_name: Option<String>,
+    pub max_len: Option<usize>,
+    // Text alignment `/Q`: 0 left, 1 centered, 2 right.
+    pub quadding: i64,
+    // The default appearance string `/DA`, from the field or the form.
+    pub default_appearance: Option<String>,
+    // The terminal field dictionary, which is its only widget when the two
+    // are merged. `None` for fields stored directly in an array.
+    pub reference: Option<Ref>,
+    pub widgets: Vec<Widget>,
+}
+
+impl FormField {
+    pub fn is_read_only(&self) -> bool {
+        self.flags & FIELD_READ_ONLY != 0
+    }
+
+    pub fn is_required(&self) -> bool {
+        self.flags & FIELD_REQUIRED != 0
+    }
+}
+
+impl PdfDocument {
+    // The terminal fields of the interactive form in `/Fields` order, empty
+    // if the document has no form.
+    pub fn form_fields(&self) -> Result<Vec<FormField>, Error> {
+        let form = match self.get(&self.catalog()?, "AcroForm")? {
+            Primitives::Dict(form) => form,
+            _ => return Ok(Vec::new()),
+        };
+        let mut inherited = Dictionary::new();
+        if let Some(da) = form.get(&b"DA"[..]) {
+            inherited.insert(Name::from("DA"), da.clone());
+        }
+        let mut reader = FieldReader {
+            doc: self,
+            pages: self.annotation_pages()?,
+            visited: HashSet::new(),
+            fields: Vec::new(),
+        };
+        for field in self.get(&form, "Fields")?.get_array().map_or(&[][..], |f| &f[..]) {
+            reader.read_field(field, "", &inherited, 0)?;
+        }
+        Ok(reader.fields)
+    }
+
+    pub fn form_field(&self, name: &str) -> Result<Option<FormField>, Error> {
+        Ok(self.form_fields()?.into_iter().find(|f| f.name == name))
+    }
+
+    // The page index of every annotation reference. Widgets often lack
+    // `/P`, so their pages come from the pages' `/Annots` instead.
+    fn annotation_pages(&self) -> Result<HashMap<Ref, usize>, Error> {
+        let mut pages = HashMap::new();
+        for page in self.pages()? {
+            if let Some(annots) = page.dict.get(&b"Annots"[..]) {
+                for annot in self.resolve(annots)?.get_array().map_or(&[][..], |a| &a[..]) {
+                    if let Some(r) = annot.get_ref() {
+                        pages.entry(r).or_insert(page.index);
+                    }
+                }
+            }
+        }
+        Ok(pages)
+    }
+}
+
+impl PdfDocument {
+    // Sets the value of a text, checkbox, radio or choice field and updates
+    // its widgets: checkboxes and radio buttons switch their appearance
+    // state, text and choice fields get new appearance streams drawn with
+    // `/DA` and the `/DR` font.
+    pub fn set_field_value(&mut self, name: &str, value: FieldValue) -> Result<(), Error> {
+        let field = self.form_field(name)?.ok_or(Error::InvalidArgument("form field does not exist"))?;
+        self.set_value(field, value)
+    }
+
+    // Sets the value of a field as read by `form_fields`, if `check_value`
+    // accepts it.
+    pub(crate) fn set_value(&mut self, field: FormField, value: FieldValue) -> Result<(), Error> {
+        check_value(&field, &value)?;
+        let reference = field.reference.ok_or(Error::InvalidArgument("form field is not an indirect object"))?;
+        match (field.field_type, &value) {
+            (FieldType::Text, FieldValue::Text(text)) => {
+                self.set_entry(reference, "V", Some(Primitives::Str(encode_text(text))))?;
+            }
+            (FieldType::Checkbox, FieldValue::State(state)) | (FieldType::Radio, FieldValue::State(state)) => {
+                self.set_entry(reference, "V", Some(Primitives::name(state.as_bytes().to_vec())))?;
+                for widget in &field.widgets {
+                    let on = widget.on_state.as_deref() == Some(state);
+                    if let Some(r) = widget.reference {
+                        let state = if on { state.as_str() } else { "Off" };
+                        self.set_entry(r, "AS", Some(Primitives::name(state.as_bytes().to_vec())))?;
+                    }
+                }
+            }
+            (FieldType::Choice, FieldValue::Selection(selected)) => {
+                let mut indices: Vec<usize> = selected.iter().filter_map(|v| field.options.iter().position(|o| &o.export == v)).collect();
+                indices.sort_unstable();
+                let mut values: Vec<Primitives> = selected.iter().map(|v| Primitives::Str(encode_text(v))).collect();
+                let v = match values.len() {
+                    0 => None,
+                    1 => values.pop(),
+                    _ => Some(Primitives::Array(values)),
+                };
+                self.set_entry(reference, "V", v)?;
+                // `/I` keeps the selection unambiguous when options share an
+                // export value.
+                let i = if field.flags & FIELD_MULTI_SELECT != 0 && !indices.is_empty() {
+                    Some(Primitives::Array(indices.into_iter().map(|i| Primitives::Int(i as _)).collect()))
+                } else {
+                    None
+                };
+                self.set_entry(reference, "I", i)?;
+            }
+            _ => return Err(Error::InvalidArgument("value does not match the field type")),
+        }
+
+        if let FieldType::Text | FieldType::Choice = field.field_type {
+            let field = FormField { value: Some(value), ..field };
+            for widget in &field.widgets {
+                if let Some(r) = widget.reference {
+                    self.update_appearance(&field, widget, r)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn update_appearance(&mut self, field: &FormField, widget: &Widget, reference: Ref) -> Result<(), Error> {
+        let da = DefaultAppearance::parse(field.default_appearance.as_deref().unwrap_or(""));
+        let fonts = match self.get(&self.catalog()?, "AcroForm")? {
+            Primitives::Dict(form) => match self.get(&form, "DR")?.get("Font") {
+                Some(fonts) => self.resolve(fonts)?.get_dict().cloned().unwrap_or_default(),
+                None => Dictionary::new(),
+            },
+            _ => Dictionary::new(),
+        };
+        // Readers fall back to Helvetica when the font isn't in `/DR`.
+        let font = fonts.get(&da.font[..]).cloned().unwrap_or_else(|| {
+            let mut font = Dictionary::new();
+            font.insert(Name::from("Type"), Primitives::name(b"Font".to_vec()));
+            font.insert(Name::from("Subtype"), Primitives::name(b"Type1".to_vec()));
+            font.insert(Name::from("BaseFont"), Primitives::name(b"Helvetica".to_vec()));
+            font.insert(Name::from("Encoding"), Primitives::name(b"WinAnsiEncoding".to_vec()));
+            Primitives::Dict(font)
+        });
+        let metrics = match self.resolve(&font)? {
+            Primitives::Dict(font) => FontMetrics::new(self, &font)?,
+            _ => FontMetrics::default(),
+        };
+
+        let width = widget.rect[2] - widget.rect[0];
+        let height = widget.rect[3] - widget.rect[1];
+        let selected = match &field.value {
+            Some(FieldValue::Selection(selected)) => &selected[..],
+            _ => &[],
+        };
+        let content = match &field.value {
+            Some(FieldValue::Text(text)) if field.flags & FIELD_PASSWORD != 0 => {
+                let hidden: String = text.chars().map(|_| '*').collect();
+                text_content(&da, &metrics, width, height, &hidden, field.quadding, false)
+            }
+            Some(FieldValue::Text(text)) => {
+                text_content(&da, &metrics, width, height, text, field.quadding, field.flags & FIELD_MULTILINE != 0)
+            }
+            _ if field.flags & FIELD_COMBO != 0 => {
+                // Combo boxes show the display text of the selected option.
+                let shown = selected.first().map(|value| match field.options.iter().find(|o| &o.export == value) {
+                    Some(option) => option.display.clone(),
+                    None => value.clone(),
+                });
+                text_content(&da, &metrics, width, height, &shown.unwrap_or_default(), field.quadding, false)
+            }
+            _ => {
+                let displays: Vec<String> = field.options.iter().map(|o| o.display.clone()).collect();
+                let indices: Vec<usize> = field.options.iter().enumerate().filter(|(_, o)| selected.contains(&o.export)).map(|(i, _)| i).collect();
+                list_content(&da, width, height, &displays, &indices)
+            }
+        };
+
+        let mut font_resources = Dictionary::new();
+        font_resources.insert(Name(da.font.clone()), font);
+        let mut resources = Dictionary::new();
+        resources.insert(Name::from("Font"), Primitives::Dict(font_resources));
+        let mut form = Dictionary::new();
+        form.insert(Name::from("Type"), Primitives::name(b"XObject".to_vec()));
+        form.insert(Name::from("Subtype"), Primitives::name(b"Form".to_vec()));
+        form.insert(Name::from("BBox"), Primitives::Array(vec![Primitives::Int(0), Primitives::Int(0), Primitives::Real(width), Primitives::Real(height)]));
+        form.insert(Name::from("Resources"), Primitives::Dict(resources));
+        let appearance = self.add_object(Primitives::Stream(PdfStream::new(form, content)));
+        // The rollover and down appearances would show the old value.
+        let mut ap = Dictionary::new();
+        ap.insert(Name::from("N"), appearance.into());
+        self.set_entry(reference, "AP", Some(Primitives::Dict(ap)))
+    }
+
+    // Draws the visible widgets into the page contents and removes the
+    // interactive form, so the values can no longer be changed.
+    pub fn flatten_form(&mut self) -> Result<(), Error> {
+        for page in self.pages()? {
+            let annots = match page.dict.get(&b"Annots"[..]) {
+                Some(annots) => self.resolve(annots)?.get_array().cloned().unwrap_or_default(),
+                None => continue,
+            };
+            let mut resources = page.resources(self)?;
+            let mut xobjects = match resources.get(&b"XObject"[..]) {
+                Some(xobjects) => self.resolve(xobjects)?.get_dict().cloned().unwrap_or_default(),
+                None => Dictionary::new(),
+            };
+            let mut kept = Vec::new();
+            let mut has_widgets = false;
+            let mut content = String::from("Q\n");
+            for annot in annots {
+                let dict = match self.resolve(&annot)? {
+                    Primitives::Dict(dict) => dict,
+                    _ => continue,
+                };
+                if self.get(&dict, "Subtype")?.get_name() != Some(b"Widget") {
+                    kept.push(annot);
+                    continue;
+                }
+                has_widgets = true;
+                if let Some((appearance, matrix)) = self.flattened_widget(&dict)? {
+                    let mut index = 0;
+                    while xobjects.contains_key(format!("Flat{}", index).as_bytes()) {
+                        index += 1;
+                    }
+                    let name = format!("Flat{}", index);
+                    xobjects.insert(Name::from(name.as_str()), appearance.into());
+                    let matrix: Vec<String> = matrix.iter().map(|&n| format_real(n)).collect();
+                    content.push_str(&format!("q\n{} cm\n/{} Do\nQ\n", matrix.join(" "), name));
+                }
+            }
+            if !has_widgets {
+                continue;
+            }
+
+            let mut page_dict = page.dict.clone();
+            if kept.is_empty() {
+                page_dict.remove(&b"Annots"[..]);
+            } else {
+                page_dict.insert(Name::from("Annots"), Primitives::Array(kept));
+            }
+            if content.len() > 2 {
+                // The existing content is wrapped in q/Q so whatever graphics
+                // state it leaves behind doesn't affect the widgets.
+                let mut contents = match page_dict.get(&b"Contents"[..]) {
+                    Some(Primitives::Ref(num, gen)) => match self.fetch(Ref::new(*num, *gen))? {
+                        Primitives::Array(parts) => parts,
+                        _ => vec![Primitives::Ref(*num, *gen)],
+                    },
+                    Some(Primitives::Array(parts)) => parts.clone(),
+                    _ => Vec::new(),
+                };
+                contents.insert(0, self.add_object(Primitives::Stream(PdfStream::new(Dictionary::new(), b"q\n".to_vec()))).into());
+                contents.push(self.add_object(Primitives::Stream(PdfStream::new(Dictionary::new(), content.into_bytes()))).into());
+                page_dict.insert(Name::from("Contents"), Primitives::Array(contents));
+                resources.insert(Name::from("XObject"), Primitives::Dict(xobjects));
+                page_dict.insert(Name::from("Resources"), Primitives::Dict(resources));
+            }
+            self.replace_object(page.reference, Primitives::Dict(page_dict))?;
+        }
+
+        let catalog_ref = self.catalog_ref()?;
+        let mut catalog = self.catalog()?;
+        if catalog.remove(&b"AcroForm"[..]).is_some() {
+            self.replace_object(catalog_ref, Primitives::Dict(catalog))?;
+        }
+        Ok(())
+    }
+
+    // The normal appearance of a visible widget and the matrix that maps
+    // it onto the widget's rectangle, see "Appearance Streams" in the spec.
+    fn flattened_widget(&mut self, dict: &Dictionary) -> Result<Option<(Ref, [f64; 6])>, Error> {
+        let flags = self.get(dict, "F")?.get_integer().unwrap_or(0);
+        if flags & (FLAG_HIDDEN | FLAG_NO_VIEW) != 0 {
+            return Ok(None);
+        }
+        let mut normal = match self.get(dict, "AP")?.get("N") {
+            Some(normal) => normal.clone(),
+            None => return Ok(None),
+        };
+        if let Primitives::Dict(states) = self.resolve(&normal)? {
+            let state = self.get(dict, "AS")?;
+            normal = match state.get_name().and_then(|s| states.get(s)) {
+                Some(appearance) => appearance.clone(),
+                None => return Ok(None),
+            };
+        }
+        let appearance = match normal {
+            Primitives::Ref(num, gen) => Ref::new(num, gen),
+            stream @ Primitives::Stream(_) => self.add_object(stream),
+            _ => return Ok(None),
+        };
+        let form = match self.fetch(appearance)? {
+            Primitives::Stream(stream) => stream.dict,
+            _ => return Ok(None),
+        };
+
+        let numbers = |obj: Primitives| -> Vec<f64> { obj.get_array().map_or(Vec::new(), |a| a.iter().filter_map(|n| n.get_real()).collect()) };
+        let (rect, bbox) = (numbers(self.get(dict, "Rect")?), numbers(self.get(&form, "BBox")?));
+        let matrix = match numbers(self.get(&form, "Matrix")?)[..] {
+            [a, b, c, d, e, f] => [a, b, c, d, e, f],
+            _ => [1.0, 0.0, 0.0, 1.0, 0.0, 0.0],
+        };
+        let (rect, bbox) = match (&rect[..], &bbox[..]) {
+            (&[x1, y1, x2, y2], &[bx1, by1, bx2, by2]) => ([x1.min(x2), y1.min(y2), x1.max(x2), y1.max(y2)], [bx1, by1, bx2, by2]),
+            _ => return Ok(None),
+        };
+        // The bounding box of the transformed BBox is scaled onto the rect.
+        let corners = [(bbox[0], bbox[1]), (bbox[2], bbox[1]), (bbox[0], bbox[3]), (bbox[2], bbox[3])];
+        let transformed: Vec<(f64, f64)> = corners
+            .iter()
+            .map(|&(x, y)| (matrix[0] * x + matrix[2] * y + matrix[4], matrix[1] * x + matrix[3] * y + matrix[5]))
+            .collect();
+        let min_x = transformed.iter().map(|p| p.0).fold(f64::INFINITY, f64::min);
+        let max_x = transformed.iter().map(|p| p.0).fold(f64::NEG_INFINITY, f64::max);
+        let min_y = transformed.iter().map(|p| p.1).fold(f64::INFINITY, f64::min);
+        let max_y = transformed.iter().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max);
+        if max_x - min_x <= 0.0 || max_y - min_y <= 0.0 {
+            return Ok(None);
+        }
+        let scale_x = (rect[2] - rect[0]) / (max_x - min_x);
+        let scale_y = (rect[3] - rect[1]) / (max_y - min_y);
+        Ok(Some((appearance, [scale_x, 0.0, 0.0, scale_y, rect[0] - min_x * scale_x, rect[1] - min_y * scale_y])))
+    }
+
+    // Sets or, with `None`, removes an entry of a dictionary object.
+    fn set_entry(&mut self, r: Ref, key: &str, value: Option<Primitives>) -> Result<(), Error> {
+        let mut dict = match self.fetch(r)? {
+            Primitives::Dict(dict) => dict,
+            _ => return Err(Error::InvalidArgument("object is not a dictionary")),
+        };
+        match value {
+            Some(value) => dict.insert(Name::from(key), value),
+            None => dict.remove(key.as_bytes()),
+        };
+        self.replace_object(r, Primitives::Dict(dict))
+    }
+}
+
+// Fails with `InvalidArgument` if `value` can't be set on `field`: it
+// doesn't match the field type, is longer than `/MaxLen`, isn't a state of
+// the widgets or isn't one of the options.
+pub(crate) fn check_value(field: &FormField, value: &FieldValue) -> Result<(), Error> {
+    if field.reference.is_none() {
+        return Err(Error::InvalidArgument("form field is not an indirect object"));
+    }
+    match (field.field_type, value) {
+        (FieldType::Text, FieldValue::Text(text)) => {
+            if field.max_len.is_some_and(|max| text.chars().count() > max) {
+                return Err(Error::InvalidArgument("value is longer than the field's /MaxLen"));
+            }
+        }
+        (FieldType::Checkbox, FieldValue::State(state)) | (FieldType::Radio, FieldValue::State(state)) => {
+            if state != "Off" && !field.widgets.iter().any(|w| w.on_state.as_deref() == Some(state)) {
+                return Err(Error::InvalidArgument("no widget of the field has this state"));
+            }
+        }
+        (FieldType::Choice, FieldValue::Selection(selected)) => {
+            if selected.len() > 1 && field.flags & FIELD_MULTI_SELECT == 0 {
+                return Err(Error::InvalidArgument("field does not allow selecting several options"));
+            }
+            // Editable combo boxes accept any text.
+            let editable = field.flags & FIELD_COMBO != 0 && field.flags & FIELD_EDIT != 0;
+            if !editable && !selected.iter().all(|v| field.options.iter().any(|o| &o.export == v)) {
+                return Err(Error::InvalidArgument("value is not one of the field's options"));
+            }
+        }
+        _ => return Err(Error::InvalidArgument("value does not match the field type")),
+    }
+    Ok(())
+}
+
+struct FieldReader<'a> {
+    doc: &'a PdfDocument,
+    pages: HashMap<Ref, usize>,
+    visited: HashSet<Ref>,
+    fields: Vec<FormField>,
+}
+
+impl<'a> FieldReader<'a> {
+    fn read_field(&mut self, node: &Primitives, parent_name: &str, inherited: &Dictionary, depth: usize) -> Result<(), Error> {
+        if let Some(r) = node.get_ref() {
+            if !self.visited.insert(r) {
+                eprintln!("read_field - form contains a cycle at {:?}.", r);
+                return Ok(());
+            }
+        }
+        if depth > MAX_DEPTH {
+            return Ok(());
+        }
+        let dict = match self.doc.resolve(node)? {
+            Primitives::Dict(dict) => dict,
+            _ => return Ok(()),
+        };
+        let name = match self.doc.get(&dict, "T")?.get_text() {
+            Some(partial) if !parent_name.is_empty() => format!("{}.{}", parent_name, partial),
+            Some(partial) => partial,
+            None => parent_name.to_string(),
+        };
+        let mut inherited = inherited.clone();
+        for key in INHERITABLE.iter() {
+            if let Some(value) = dict.get(key.as_bytes()) {
+                inherited.insert(Name::from(key), value.clone());
+            }
+        }
+
+        // Kids with a partial name are fields, the others are widgets of
+        // this field. Without kids the field is merged with its widget.
+        let kids = self.doc.get(&dict, "Kids")?.get_array().cloned().unwrap_or_default();
+        let mut widgets = Vec::new();
+        for kid in &kids {
+            let kid_dict = self.doc.resolve(kid)?;
+            if kid_dict.get("T").is_some() {
+                self.read_field(kid, &name, &inherited, depth + 1)?;
+            } else if let Primitives::Dict(kid_dict) = kid_dict {
+                widgets.push(self.widget(kid.get_ref(), &kid_dict)?);
+            }
+        }
+        if kids.is_empty() {
+            widgets.push(self.widget(node.get_ref(), &dict)?);
+        } else if widgets.is_empty() {
+            return Ok(());
+        }
+        let field = self.terminal_field(name, node.get_ref(), &dict, &inherited, widgets)?;
+        self.fields.push(field);
+        Ok(())
+    }
+
+    fn terminal_field(&self, name: String, reference: Option<Ref>, dict: &Dictionary, inherited: &Dictionary, widgets: Vec<Widget>) -> Result<FormField, Error> {
+        let doc = self.doc;
+        let flags = doc.get(inherited, "Ff")?.get_integer().unwrap_or(0) as u32;
+        let field_type = match doc.get(inherited, "FT")?.get_name() {
+            Some(b"Btn") if flags & FIELD_PUSH_BUTTON != 0 => FieldType::PushButton,
+            Some(b"Btn") if flags & FIELD_RADIO != 0 => FieldType::Radio,
+            Some(b"Btn") => FieldType::Checkbox,
+            Some(b"Ch") => FieldType::Choice,
+            Some(b"Sig") => FieldType::Signature,
+            // Text is the default for fields that don't say.
+            _ => FieldType::Text,
+        };
+
+        let mut options = Vec::new();
+        for option in doc.get(inherited, "Opt")?.get_array().map_or(&[][..], |o| &o[..]) {
+            let option = doc.resolve(option)?;
+            let (export, display) = match option.get_array().map(|o| &o[..]) {
+                Some([export, display]) => (doc.resolve(export)?.get_text(), doc.resolve(display)?.get_text()),
+                _ => (option.get_text(), option.get_text()),
+            };
+            if let (Some(export), Some(display)) = (export, display) {
+                options.push(FieldOption { export: export, display: display });
+            }
+        }
+
+        Ok(FormField {
+            name: name,
+            field_type: field_type,
+            value: self.value(field_type, &doc.get(inherited, "V")?)?,
+            default_value: self.value(field_type, &doc.get(inherited, "DV")?)?,
+            options: options,
+            flags: flags,
+            alternate_name: doc.get(dict, "TU")?.get_text(),
+            max_len: doc.get(inherited, "MaxLen")?.get_integer().filter(|&n| n >= 0).map(|n| n as usize),
+            quadding: doc.get(inherited, "Q")?.get_integer().unwrap_or(0),
+            default_appearance: doc.get(inherited, "DA")?.get_text(),
+            reference: reference,
+            widgets: widgets,
+        })
+    }
+
+    fn value(&self, field_type: FieldType, value: &Primitives) -> Result<Option<FieldValue>, Error> {
+        Ok(match (field_type, value) {
+            (_, Primitives::Null) | (FieldType::Signature, _) | (FieldType::PushButton, _) => None,
+            (FieldType::Checkbox, Primitives::Name(state)) | (FieldType::Radio, Primitives::Name(state)) => {
+                Some(FieldValue::State(String::from_utf8_lossy(&state.0).into_owned()))
+            }
+            (FieldType::Choice, Primitives::Array(selected)) => {
+                let mut values = Vec::new();
+                for v in selected {
+                    values.extend(self.doc.resolve(v)?.get_text());
+                }
+                Some(FieldValue::Selection(values))
+            }
+            (FieldType::Choice, v) => v.get_text().map(|v| FieldValue::Selection(vec![v])),
+            // Long text values may be stored in a stream.
+            (_, Primitives::Stream(stream)) => Some(FieldValue::Text(String::from_utf8_lossy(&stream.decode()?).into_owned())),
+            (_, v) => v.get_text().map(FieldValue::Text),
+        })
+    }
+
+    fn widget(&self, reference: Option<Ref>, dict: &Dictionary) -> Result<Widget, Error> {
+        let doc = self.doc;
+        let rect: Vec<f64> = doc.get(dict, "Rect")?.get_array().map_or(Vec::new(), |a| a.iter().filter_map(|n| n.get_real()).collect());
+        let rect = match rect[..] {
+            [x1, y1, x2, y2] => [x1.min(x2), y1.min(y2), x1.max(x2), y1.max(y2)],
+            _ => [0.0; 4],
+        };
+        let page = match reference.and_then(|r| self.pages.get(&r)) {
+            Some(&page) => Some(page),
+            None => match dict.get(&b"P"[..]).and_then(|p| p.get_ref()) {
+                Some(p) => doc.page_refs()?.iter().position(|&r| r == p),
+                None => None,
+            },
+        };
+        let normal = match doc.get(dict, "AP")?.get("N") {
+            Some(normal) => doc.resolve(normal)?,
+            None => Primitives::Null,
+        };
+        let on_state = normal
+            .get_dict()
+            .filter(|_| !matches!(normal, Primitives::Stream(_)))
+            .and_then(|states| states.keys().find(|k| &k.0[..] != b"Off"))
+            .map(|k| String::from_utf8_lossy(&k.0).into_owned());
+        Ok(Widget {
+            reference: reference,
+            page: page,
+            rect: rect,
+            on_state: on_state,
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::document::PdfDocument;
+    use crate::primitives::{Dictionary, Name, Primitives, Ref};
+    use crate::writer::SaveOptions;
+    use super::{FieldOption, FieldType, FieldValue, FormField, FIELD_COMBO, FIELD_RADIO};
+
+    fn dict(entries: Vec<(&str, Primitives)>) -> Dictionary {
+        entries.into_iter().map(|(k, v)| (Name::from(k), v)).collect()
+    }
+
+    fn s(text: &str) -> Primitives {
+        Primitives::Str(text.as_bytes().to_vec())
+    }
+
+    fn n(name: &str) -> Primitives {
+        Primitives::name(name.as_bytes().to_vec())
+    }
+
+    fn rect(x: i64, y: i64) -> Primitives {
+        Primitives::Array(vec![Primitives::Int(x), Primitives::Int(y), Primitives::Int(x + 100), Primitives::Int(y + 20)])
+    }
+
+    // Appearance dictionary with an "on" state `on` and "Off".
+    fn states(doc: &mut PdfDocument, on: &str) -> Primitives {
+        let stream = |doc: &mut PdfDocument| -> Primitives {
+            doc.add_object(Primitives::Stream(crate::primitives::PdfStream::new(Dictionary::new(), Vec::new()))).into()
+        };
+        let normal = dict(vec![(on, stream(doc)), ("Off", stream(doc))]);
+        Primitives::Dict(dict(vec![("N", Primitives::Dict(normal))]))
+    }
+
+    // A form with a text field merged with its widget, a text field
+    // hierarchy, a checkbox, a radio group, a combo box and a signature.
+    pub(crate) fn document_with_form() -> PdfDocument {
+        let mut doc = PdfDocument::open("tests/examples/sample.pdf").unwrap();
+        let pages = doc.page_refs().unwrap();
+        let mut annots: Vec<Vec<Primitives>> = vec![Vec::new(), Vec::new()];
+        let mut fields = Vec::new();
+        let mut widget = |doc: &mut PdfDocument, page: usize, entries: Vec<(&str, Primitives)>| -> Ref {
+            let mut entries = entries;
+            entries.push(("Type", n("Annot")));
+            entries.push(("Subtype", n("Widget")));
+            let r = doc.add_object(Primitives::Dict(dict(entries)));
+            annots[page].push(r.into());
+            r
+        };
+
+        let name = widget(&mut doc, 0, vec![("FT", n("Tx")), ("T", s("name")), ("V", s("Alice")), ("DV", s("")), ("MaxLen", Primitives::Int(40)), ("Rect", rect(50, 700))]);
+        fields.push(name.into());
+
+        let address = doc.add_object(Primitives::Null);
+        let street = widget(&mut doc, 0, vec![("T", s("street")), ("V", s("Main St")), ("Parent", address.into()), ("Rect", rect(50, 650))]);
+        let city = widget(&mut doc, 1, vec![("T", s("city")), ("Parent", address.into()), ("Rect", rect(50, 650)), ("Ff", Primitives::Int(1))]);
+        doc.replace_object(address, Primitives::Dict(dict(vec![
+            ("T", s("address")),
+            ("FT", n("Tx")),
+            ("DA", s("/Helv 10 Tf 0 g")),
+            ("MaxLen", Primitives::Int(20)),
+            ("Kids", Primitives::Array(vec![street.into(), city.into()])),
+        ]))).unwrap();
+        fields.push(address.into());
+
+        let ap = states(&mut doc, "Yes");
+        let agree = widget(&mut doc, 0, vec![("FT", n("Btn")), ("T", s("agree")), ("V", n("Yes")), ("AS", n("Yes")), ("AP", ap), ("Rect", rect(50, 600))]);
+        fields.push(agree.into());
+
+        let color = doc.add_object(Primitives::Null);
+        let ap = states(&mut doc, "Red");
+        let red = widget(&mut doc, 0, vec![("Parent", color.into()), ("AS", n("Off")), ("AP", ap), ("Rect", rect(50, 550))]);
+        let ap = states(&mut doc, "Blue");
+        let blue = widget(&mut doc, 1, vec![("Parent", color.into()), ("AS", n("Blue")), ("AP", ap), ("Rect", rect(50, 550)), ("P", pages[1].into())]);
+        doc.replace_object(color, Primitives::Dict(dict(vec![
+            ("T", s("color")),
+            ("FT", n("Btn")),
+            ("Ff", Primitives::Int(FIELD_RADIO as i64)),
+            ("V", n("Blue")),
+            ("Kids", Primitives::Array(vec![red.into(), blue.into()])),
+        ]))).unwrap();
+        fields.push(color.into());
+
+        let country = widget(&mut doc, 1, vec![
+            ("FT", n("Ch")),
+            ("T", s("country")),
+            ("Ff", Primitives::Int(FIELD_COMBO as i64)),
+            ("Opt", Primitives::Array(vec![Primitives::Array(vec![s("de"), s("Germany")]), s("France")])),
+            ("V", s("de")),
+            ("Rect", rect(50, 500)),
+        ]);
+        fields.push(country.into());
+
+        let sig = widget(&mut doc, 1, vec![("FT", n("Sig")), ("T", s("sig")), ("Rect", rect(50, 450))]);
+        fields.push(sig.into());
+
+        for (page, annots) in pages.iter().zip(annots) {
+            let mut page_dict = doc.fetch(*page).unwrap().get_dict().cloned().unwrap();
+            page_dict.insert(Name::from("Annots"), Primitives::Array(annots));
+            doc.replace_object(*page, Primitives::Dict(page_dict)).unwrap();
+        }
+        let mut resources = Dictionary::new();
+        let font = dict(vec![("Type", n("Font")), ("Subtype", n("Type1")), ("BaseFont", n("Helvetica"))]);
+        resources.insert(Name::from("Font"), Primitives::Dict(dict(vec![("Helv", Primitives::Dict(font))])));
+        let form = dict(vec![
+            ("Fields", Primitives::Array(fields)),
+            ("DA", s("/Helv 0 Tf 0 g")),
+            ("DR", Primitives::Dict(resources)),
+        ]);
+        let catalog_ref = doc.catalog_ref().unwrap();
+        let mut catalog = doc.catalog().unwrap();
+        catalog.insert(Name::from("AcroForm"), Primitives::Dict(form));
+        doc.replace_object(catalog_ref, Primitives::Dict(catalog)).unwrap();
+        doc
+    }
+
+    #[test]
+    fn read_form_fields() {
+        let doc = document_with_form();
+        let fields = doc.form_fields().unwrap();
+        let names: Vec<&str> = fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["name", "address.street", "address.city", "agree", "color", "country", "sig"]);
+
+        let name = &fields[0];
+        assert_eq!(name.field_type, FieldType::Text);
+        assert_eq!(name.value, Some(FieldValue::Text("Alice".to_string())));
+        assert_eq!(name.default_value, Some(FieldValue::Text(String::new())));
+        assert_eq!(name.max_len, Some(40));
+        assert_eq!(name.widgets.len(), 1);
+        assert_eq!(name.widgets[0].reference, name.reference);
+        assert_eq!(name.default_appearance.as_deref(), Some("/Helv 0 Tf 0 g"));
+
+        let city = &fields[2];
+        assert_eq!(city.field_type, FieldType::Text);
+        assert!(city.is_read_only());
+        assert_eq!(city.value, None);
+        assert_eq!(city.widgets[0].page, Some(1));
+        assert_eq!(city.default_appearance.as_deref(), Some("/Helv 10 Tf 0 g"));
+        assert_eq!(city.max_len, Some(20));
+
+        let agree = &fields[3];
+        assert_eq!(agree.field_type, FieldType::Checkbox);
+        assert_eq!(agree.value, Some(FieldValue::State("Yes".to_string())));
+        assert_eq!(agree.widgets[0].on_state.as_deref(), Some("Yes"));
+
+        let color = &fields[4];
+        assert_eq!(color.field_type, FieldType::Radio);
+        assert_eq!(color.value, Some(FieldValue::State("Blue".to_string())));
+        let states: Vec<(Option<usize>, Option<&str>)> = color.widgets.iter().map(|w| (w.page, w.on_state.as_deref())).collect();
+        assert_eq!(states, [(Some(0), Some("Red")), (Some(1), Some("Blue"))]);
+        assert_eq!(color.widgets[0].rect, [50.0, 550.0, 150.0, 570.0]);
+
+        let country = &fields[5];
+        assert_eq!(country.field_type, FieldType::Choice);
+        assert_eq!(country.value, Some(FieldValue::Selection(vec!["de".to_string()])));
+        assert_eq!(country.options, [
+            FieldOption { export: "de".to_string(), display: "Germany".to_string() },
+            FieldOption { export: "France".to_string(), display: "France".to_string() },
+        ]);
+
+        assert_eq!(fields[6].field_type, FieldType::Signature);
+        assert_eq!(doc.form_field("address.street").unwrap().unwrap().value, Some(FieldValue::Text("Main St".to_string())));
+        assert!(PdfDocument::open("tests/examples/dummy.pdf").unwrap().form_fields().unwrap().is_empty());
+    }
+
+    #[test]
+    fn fill_form_fields() {
+        let mut doc = document_with_form();
+        doc.set_field_value("name", FieldValue::Text("Bob".to_string())).unwrap();
+        doc.set_field_value("agree", FieldValue::State("Off".to_string())).unwrap();
+        doc.set_field_value("color", FieldValue::State("Red".to_string())).unwrap();
+        doc.set_field_value("country", FieldValue::Selection(vec!["France".to_string()])).unwrap();
+        assert!(doc.set_field_value("name", FieldValue::State("Yes".to_string())).is_err());
+        assert!(doc.set_field_value("color", FieldValue::State("Green".to_string())).is_err());
+        assert!(doc.set_field_value("country", FieldValue::Selection(vec!["Spain".to_string()])).is_err());
+        assert!(doc.set_field_value("sig", FieldValue::Text("x".to_string())).is_err());
+        assert!(doc.set_field_value("missing", FieldValue::Text("x".to_string())).is_err());
+
+        let mut data = Vec::new();
+        doc.write_to(&mut data, &SaveOptions::default()).unwrap();
+        let doc = PdfDocument::load_data(data).unwrap();
+        let fields = doc.form_fields().unwrap();
+        assert_eq!(fields[0].value, Some(FieldValue::Text("Bob".to_string())));
+        assert_eq!(fields[3].value, Some(FieldValue::State("Off".to_string())));
+        assert_eq!(fields[4].value, Some(FieldValue::State("Red".to_string())));
+        assert_eq!(fields[5].value, Some(FieldValue::Selection(vec!["France".to_string()])));
+
+        let appearance = |field: &FormField, widget: usize| -> (Option<String>, String) {
+            let dict = doc.fetch(field.widgets[widget].reference.unwrap()).unwrap().get_dict().cloned().unwrap();
+            let state = dict.get(&b"AS"[..]).and_then(|s| s.get_name()).map(|s| String::from_utf8_lossy(s).into_owned());
+            let normal = doc.get(&doc.get(&dict, "AP").unwrap().get_dict().cloned().unwrap(), "N").unwrap();
+            let content = normal.get_stream().map(|s| String::from_utf8(s.decode().unwrap()).unwrap()).unwrap_or_default();
+            (state, content)
+        };
+        let (_, name) = appearance(&fields[0], 0);
+        assert!(name.contains("/Helv 11.851852 Tf\n0 g\n"));
+        assert!(name.contains("(Bob) Tj"));
+        assert_eq!(appearance(&fields[3], 0).0.as_deref(), Some("Off"));
+        assert_eq!(appearance(&fields[4], 0).0.as_deref(), Some("Red"));
+        assert_eq!(appearance(&fields[4], 1).0.as_deref(), Some("Off"));
+        assert!(appearance(&fields[5], 0).1.contains("(France) Tj"));
+    }
+
+    #[test]
+    fn flatten_form() {
+        let mut doc = document_with_form();
+        doc.set_field_value("address.street", FieldValue::Text("Elm St".to_string())).unwrap();
+        doc.flatten_form().unwrap();
+
+        let mut data = Vec::new();
+        doc.write_to(&mut data, &SaveOptions::default()).unwrap();
+        let doc = PdfDocument::load_data(data).unwrap();
+        assert!(doc.form_fields().unwrap().is_empty());
+        assert!(doc.annotations(0).unwrap().is_empty());
+        let page = doc.page(0).unwrap();
+        let contents = String::from_utf8(page.contents(&doc).unwrap()).unwrap();
+        assert!(contents.starts_with("q\n"));
+        // The street widget at [50 650 150 670] has a 100 x 20 BBox, the
+        // checkbox and radio button appearances have none and are skipped.
+        assert!(contents.ends_with("Q\nq\n1 0 0 1 50 650 cm\n/Flat0 Do\nQ\n"));
+        let xobjects = page.resources(&doc).unwrap().get(&b"XObject"[..]).cloned().unwrap();
+        assert!(doc.resolve(&xobjects).unwrap().get("Flat0").is_some());
+    }
+}
@@ -0,0 +1,598 @@
+use std::io::Cursor;
+
+use crate::document::PdfDocument;
+use crate::primitives::Primitives;
+use crate::lexer::Lexer;
+use crate::stream::Stream;
+use crate::error::Error;
+
+// Stitched functions and calculator procedures nested deeper than this are
+// rejected.
+const MAX_DEPTH: usize = 16;
+
+// Sampled functions take at most this many inputs, each sample being
+// interpolated from `2^inputs` neighbours.
+const MAX_INPUTS: usize = 16;
+
+// The operand stack limit of calculator functions.
+const MAX_STACK: usize = 100;
+
+// Operators of calculator functions, apart from `if` and `ifelse`.
+const OPERATORS: [&str; 38] = [
+    "abs", "add", "atan", "ceiling", "cos", "cvi", "cvr", "div", "exp", "floor", "idiv", "ln", "log", "mod", "mul",
+    "neg", "round", "sin", "sqrt", "sub", "truncate", "and", "bitshift", "eq", "ge", "gt", "le", "lt", "ne", "not",
+    "or", "xor", "copy", "dup", "exch", "index", "pop", "roll",
+];
+
+// A PDF function, as used for tint transforms, shadings and transfer
+// functions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    // Pairs of minimum and maximum, one for each input.
+    pub domain: Vec<f64>,
+    // Pairs of minimum and maximum, one for each output. Exponential and
+    // stitching functions may leave their outputs unclipped.
+    pub range: Option<Vec<f64>>,
+    kind: Kind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Kind {
+    // Type 0, samples in the order of the stream, first input varying
+    // fastest.
+    Sampled {
+        size: Vec<usize>,
+        bits: u8,
+        encode: Vec<f64>,
+        decode: Vec<f64>,
+        samples: Vec<f64>,
+    },
+    // Type 2.
+    Exponential {
+        c0: Vec<f64>,
+        c1: Vec<f64>,
+        exponent: f64,
+    },
+    // Type 3.
+    Stitching {
+        functions: Vec<Function>,
+        bounds: Vec<f64>,
+        encode: Vec<f64>,
+    },
+    // Type 4.
+    Calculator(Vec<Token>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Value(Value),
+    Operator(Vec<u8>),
+    If(Vec<Token>),
+    IfElse(Vec<Token>, Vec<Token>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Value {
+    Number(f64),
+    Bool(bool),
+}
+
+impl Function {
+    pub fn parse(doc: &PdfDocument, obj: &Primitives) -> Result<Function, Error> {
+        Function::parse_nested(doc, obj, 0)
+    }
+
+    fn parse_nested(doc: &PdfDocument, obj: &Primitives, depth: usize) -> Result<Function, Error> {
+        if depth > MAX_DEPTH {
+            return Err(Error::InvalidFunction("functions nested too deeply"));
+        }
+        let obj = doc.resolve(obj)?;
+        let dict = obj.get_dict().ok_or(Error::InvalidFunction("not a dictionary or stream"))?;
+        let numbers = |key: &str| -> Result<Option<Vec<f64>>, Error> {
+            Ok(doc.get(dict, key)?.get_array().map(|a| a.iter().filter_map(|n| n.get_real()).collect()))
+        };
+        let pairs = |values: Option<Vec<f64>>| values.filter(|v| !v.is_empty() && v.len() % 2 == 0);
+        let data = || match &obj {
+            Primitives::Stream(stream) => stream.decode(),
+            _ => Err(Error::InvalidFunction("function data isn't a stream")),
+        };
+        let domain = pairs(numbers("Domain")?).ok_or(Error::InvalidFunction("missing /Domain"))?;
+        let range = pairs(numbers("Range")?);
+        let inputs = domain.len() / 2;
+
+        let kind = match doc.get(dict, "FunctionType")?.get_integer() {
+            Some(0) => {
+                let range = range.as_ref().ok_or(Error::InvalidFunction("sampled function without /Range"))?;
+                let size: Vec<usize> = numbers("Size")?.unwrap_or_default().iter().map(|&s| s.max(1.0) as usize).collect();
+                if size.len() != inputs || inputs > MAX_INPUTS {
+                    return Err(Error::InvalidFunction("/Size doesn't match /Domain"));
+                }
+                let bits = doc.get(dict, "BitsPerSample")?.get_integer().unwrap_or(0);
+                if ![1, 2, 4, 8, 12, 16, 24, 32].contains(&bits) {
+                    return Err(Error::InvalidFunction("invalid /BitsPerSample"));
+                }
+                let encode = numbers("Encode")?
+                    .filter(|e| e.len() == 2 * inputs)
+                    .unwrap_or_else(|| size.iter().flat_map(|&s| vec![0.0, (s - 1) as f64]).collect());
+                let decode = numbers("Decode")?.filter(|d| d.len() == range.len()).unwrap_or_else(|| range.clone());
+                // Sample indexes are computed without overflow checks.
+                let count = size
+                    .iter()
+                    .try_fold(range.len() / 2, |count, &s| count.checked_mul(s))
+                    .ok_or(Error::InvalidFunction("/Size is too large"))?;
+                let samples = read_samples(&data()?, bits as u8, count);
+                Kind::Sampled {
+                    size: size,
+                    bits: bits as u8,
+                    encode: encode,
+                    decode: decode,
+                    samples: samples,
+                }
+            }
+            Some(2) => {
+                let c0 = numbers("C0")?.unwrap_or_else(|| vec![0.0]);
+                let c1 = numbers("C1")?.unwrap_or_else(|| vec![1.0]);
+                if c0.len() != c1.len() {
+                    return Err(Error::InvalidFunction("/C0 and /C1 differ in length"));
+                }
+                Kind::Exponential {
+                    c0: c0,
+                    c1: c1,
+                    exponent: doc.get(dict, "N")?.get_real().ok_or(Error::InvalidFunction("exponential function without /N"))?,
+                }
+            }
+            Some(3) => {
+                let functions = match doc.get(dict, "Functions")? {
+                    Primitives::Array(functions) => functions.iter().map(|f| Function::parse_nested(doc, f, depth + 1)).collect::<Result<Vec<_>, _>>()?,
+                    _ => return Err(Error::InvalidFunction("stitching function without /Functions")),
+                };
+                let bounds = numbers("Bounds")?.unwrap_or_default();
+                if functions.is_empty() || bounds.len() != functions.len() - 1 {
+                    return Err(Error::InvalidFunction("/Bounds doesn't match /Functions"));
+                }
+                let encode = numbers("Encode")?.filter(|e| e.len() == 2 * functions.len()).unwrap_or_else(|| [0.0, 1.0].repeat(functions.len()));
+                Kind::Stitching {
+                    functions: functions,
+                    bounds: bounds,
+                    encode: encode,
+                }
+            }
+            Some(4) => {
+                if range.is_none() {
+                    return Err(Error::InvalidFunction("calculator function without /Range"));
+                }
+                Kind::Calculator(parse_program(&data()?)?)
+            }
+            _ => return Err(Error::InvalidFunction("unknown /FunctionType")),
+        };
+        Ok(Function {
+            domain: domain,
+            range: range,
+            kind: kind,
+        })
+    }
+
+    // Evaluates the function with the inputs clipped to the domain and the
+    // outputs clipped to the range. Missing inputs take the minimum of
+    // their domain.
+    pub fn evaluate(&self, input: &[f64]) -> Result<Vec<f64>, Error> {
+        let input: Vec<f64> = self.domain.chunks(2).enumerate().map(|(i, d)| clip(input.get(i).cloned().unwrap_or(d[0]), d[0], d[1])).collect();
+        let mut output = match &self.kind {
+            Kind::Sampled { size, bits, encode, decode, samples } => {
+                let outputs = decode.len() / 2;
+                // The two samples around each input and the weight of the
+                // upper one.
+                let mut lower = Vec::with_capacity(input.len());
+                let mut upper = Vec::with_capacity(input.len());
+                let mut fraction = Vec::with_capacity(input.len());
+                for (i, &x) in input.iter().enumerate() {
+                    let e = clip(interpolate(x, self.domain[2 * i], self.domain[2 * i + 1], encode[2 * i], encode[2 * i + 1]), 0.0, (size[i] - 1) as f64);
+                    let low = e.floor();
+                    lower.push(low as usize);
+                    upper.push((low as usize + 1).min(size[i] - 1));
+                    fraction.push(e - low);
+                }
+                let mut output = vec![0.0; outputs];
+                for corner in 0..1usize << input.len() {
+                    let mut weight = 1.0;
+                    let mut index = 0;
+                    let mut stride = 1;
+                    for i in 0..input.len() {
+                        let up = corner >> i & 1 == 1;
+                        weight *= if up { fraction[i] } else { 1.0 - fraction[i] };
+                        index += if up { upper[i] } else { lower[i] } * stride;
+                        stride *= size[i];
+                    }
+                    if weight == 0.0 {
+                        continue;
+                    }
+                    for (j, out) in output.iter_mut().enumerate() {
+                        *out += weight * samples.get(index * outputs + j).cloned().unwrap_or(0.0);
+                    }
+                }
+                let max = ((1u64 << bits) - 1) as f64;
+                output.iter().enumerate().map(|(j, &v)| interpolate(v, 0.0, max, decode[2 * j], decode[2 * j + 1])).collect()
+            }
+            Kind::Exponential { c0, c1, exponent } => {
+                let x = input[0].powf(*exponent);
+                c0.iter().zip(c1).map(|(c0, c1)| c0 + x * (c1 - c0)).collect()
+            }
+            Kind::Stitching { functions, bounds, encode } => {
+                let x = input[0];
+                let i = bounds.iter().take_while(|&&b| x >= b).count();
+                let low = if i == 0 { self.domain[0] } else { bounds[i - 1] };
+                let high = if i == bounds.len() { self.domain[1] } else { bounds[i] };
+                functions[i].evaluate(&[interpolate(x, low, high, encode[2 * i], encode[2 * i + 1])])?
+            }
+            Kind::Calculator(program) => {
+                let mut stack = Stack(input.iter().map(|&x| Value::Number(x)).collect());
+                stack.execute(program)?;
+                let outputs = self.range.as_ref().map_or(0, |r| r.len() / 2);
+                if stack.0.len() < outputs {
+                    return Err(Error::InvalidFunction("stack underflow"));
+                }
+                let results = stack.0.split_off(stack.0.len() - outputs);
+                results
+                    .into_iter()
+                    .map(|v| match v {
+                        Value::Number(n) => Ok(n),
+                        Value::Bool(_) => Err(Error::InvalidFunction("result isn't a number")),
+                    })
+                    .collect::<Result<Vec<f64>, Error>>()?
+            }
+        };
+        if let Some(range) = &self.range {
+            for (out, r) in output.iter_mut().zip(range.chunks(2)) {
+                *out = clip(*out, r[0], r[1]);
+            }
+        }
+        Ok(output)
+    }
+}
+
+// Unlike `f64::clamp`, doesn't panic on an inverted interval.
+fn clip(x: f64, min: f64, max: f64) -> f64 {
+    x.max(min).min(max)
+}
+
+fn interpolate(x: f64, x_min: f64, x_max: f64, y_min: f64, y_max: f64) -> f64 {
+    if x_max == x_min {
+        return y_min;
+    }
+    y_min + (x - x_min) * (y_max - y_min) / (x_max - x_min)
+}
+
+// Reads up to `count` big-endian samples of `bits` bits, stopping early
+// when the data runs out.
+fn read_samples(data: &[u8], bits: u8, count: usize) -> Vec<f64> {
+    let bits = bits as usize;
+    let count = count.min(data.len() * 8 / bits);
+    let mut samples = Vec::with_capacity(count);
+    let mut buffer = 0u64;
+    let mut buffered = 0;
+    let mut bytes = data.iter();
+    while samples.len() < count {
+        while buffered < bits {
+            buffer = buffer << 8 | *bytes.next().unwrap_or(&0) as u64;
+            buffered += 8;
+        }
+        buffered -= bits;
+        samples.push((buffer >> buffered & ((1u64 << bits) - 1)) as f64);
+    }
+    samples
+}
+
+// Parses a calculator program, a procedure of operands, operators and
+// procedures for `if` and `ifelse`.
+fn parse_program(data: &Vec<u8>) -> Result<Vec<Token>, Error> {
+    let mut lexer = Lexer::new(Stream::from_vec(data));
+    match lexer.get_obj()? {
+        Primitives::Cmd(cmd) if cmd == b"{" => parse_procedure(&mut lexer, 0),
+        _ => Err(Error::InvalidFunction("program doesn't start with {")),
+    }
+}
+
+fn parse_procedure(lexer: &mut Lexer<Cursor<&Vec<u8>>>, depth: usize) -> Result<Vec<Token>, Error> {
+    let mut tokens = Vec::new();
+    // Procedures waiting for their `if` or `ifelse`.
+    let mut procedures = Vec::new();
+    loop {
+        match lexer.get_obj()? {
+            Primitives::Int(n) => tokens.push(Token::Value(Value::Number(n as f64))),
+            Primitives::Real(n) => tokens.push(Token::Value(Value::Number(n))),
+            Primitives::Bool(b) => tokens.push(Token::Value(Value::Bool(b))),
+            Primitives::Cmd(cmd) => match &cmd[..] {
+                b"{" if depth < MAX_DEPTH => procedures.push(parse_procedure(lexer, depth + 1)?),
+                b"}" => break,
+                b"if" => {
+                    let then = procedures.pop().ok_or(Error::InvalidFunction("if without a procedure"))?;
+                    tokens.push(Token::If(then));
+                }
+                b"ifelse" => {
+                    let otherwise = procedures.pop();
+                    let then = procedures.pop().ok_or(Error::InvalidFunction("ifelse without two procedures"))?;
+                    tokens.push(Token::IfElse(then, otherwise.unwrap_or_default()));
+                }
+                operator if OPERATORS.iter().any(|o| o.as_bytes() == operator) => tokens.push(Token::Operator(cmd)),
+                _ => return Err(Error::InvalidFunction("unknown operator")),
+            },
+            Primitives::EOF => return Err(Error::InvalidFunction("procedure isn't closed")),
+            _ => return Err(Error::InvalidFunction("unexpected object in program")),
+        }
+    }
+    if !procedures.is_empty() {
+        return Err(Error::InvalidFunction("procedure without if or ifelse"));
+    }
+    Ok(tokens)
+}
+
+struct Stack(Vec<Value>);
+
+impl Stack {
+    fn execute(&mut self, program: &[Token]) -> Result<(), Error> {
+        for token in program {
+            match token {
+                Token::Value(value) => self.0.push(*value),
+                Token::Operator(operator) => self.apply(operator)?,
+                Token::If(then) => {
+                    if self.pop_bool()? {
+                        self.execute(then)?;
+                    }
+                }
+                Token::IfElse(then, otherwise) => {
+                    let condition = self.pop_bool()?;
+                    self.execute(if condition { then } else { otherwise })?;
+                }
+            }
+            if self.0.len() > MAX_STACK {
+                return Err(Error::InvalidFunction("stack overflow"));
+            }
+        }
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Result<Value, Error> {
+        self.0.pop().ok_or(Error::InvalidFunction("stack underflow"))
+    }
+
+    fn pop_number(&mut self) -> Result<f64, Error> {
+        match self.pop()? {
+            Value::Number(n) => Ok(n),
+            Value::Bool(_) => Err(Error::InvalidFunction("expected a number")),
+        }
+    }
+
+    fn pop_bool(&mut self) -> Result<bool, Error> {
+        match self.pop()? {
+            Value::Bool(b) => Ok(b),
+            Value::Number(_) => Err(Error::InvalidFunction("expected a boolean")),
+        }
+    }
+
+    // Pops a count for the stack operators, which must not exceed the
+    // stack.
+    fn pop_count(&mut self) -> Result<usize, Error> {
+        let n = self.pop_number()?;
+        if n < 0.0 || n as usize > self.0.len() {
+            return Err(Error::InvalidFunction("stack underflow"));
+        }
+        Ok(n as usize)
+    }
+
+    fn apply(&mut self, operator: &[u8]) -> Result<(), Error> {
+        let number = |n: f64| Value::Number(n);
+        let result = match operator {
+            b"abs" => number(self.pop_number()?.abs()),
+            b"neg" => number(-self.pop_number()?),
+            b"ceiling" => number(self.pop_number()?.ceil()),
+            b"floor" => number(self.pop_number()?.floor()),
+            // Halves round up, unlike `f64::round`.
+            b"round" => number((self.pop_number()? + 0.5).floor()),
+            b"truncate" | b"cvi" => number(self.pop_number()?.trunc()),
+            b"cvr" => number(self.pop_number()?),
+            b"sqrt" => number(self.pop_number()?.sqrt()),
+            b"sin" => number(self.pop_number()?.to_radians().sin()),
+            b"cos" => number(self.pop_number()?.to_radians().cos()),
+            b"ln" => number(self.pop_number()?.ln()),
+            b"log" => number(self.pop_number()?.log10()),
+            b"atan" => {
+                let den = self.pop_number()?;
+                let num = self.pop_number()?;
+                // In degrees from 0 to 360.
+                number(num.atan2(den).to_degrees().rem_euclid(360.0))
+            }
+            b"exp" => {
+                let exponent = self.pop_number()?;
+                number(self.pop_number()?.powf(exponent))
+            }
+            b"add" | b"sub" | b"mul" | b"div" => {
+                let b = self.pop_number()?;
+                let a = self.pop_number()?;
+                number(match operator {
+                    b"add" => a + b,
+                    b"sub" => a - b,
+                    b"mul" => a * b,
+                    _ if b == 0.0 => return Err(Error::InvalidFunction("division by zero")),
+                    _ => a / b,
+                })
+            }
+            b"idiv" | b"mod" | b"bitshift" => {
+                let b = self.pop_number()? as i64;
+                let a = self.pop_number()? as i64;
+                number(match operator {
+                    b"bitshift" if b >= 0 => a.checked_shl(b as u32).unwrap_or(0),
+                    b"bitshift" => a.checked_shr(b.unsigned_abs() as u32).unwrap_or(0),
+                    _ if b == 0 => return Err(Error::InvalidFunction("division by zero")),
+                    b"idiv" => a.wrapping_div(b),
+                    _ => a.wrapping_rem(b),
+                } as f64)
+            }
+            b"eq" | b"ne" => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+                Value::Bool((a == b) == (operator == b"eq"))
+            }
+            b"gt" | b"ge" | b"lt" | b"le" => {
+                let b = self.pop_number()?;
+                let a = self.pop_number()?;
+                Value::Bool(match operator {
+                    b"gt" => a > b,
+                    b"ge" => a >= b,
+                    b"lt" => a < b,
+                    _ => a <= b,
+                })
+            }
+            // Logical on booleans, bitwise on integers.
+            b"and" | b"or" | b"xor" => match (self.pop()?, self.pop()?) {
+                (Value::Bool(b), Value::Bool(a)) => Value::Bool(match operator {
+                    b"and" => a & b,
+                    b"or" => a | b,
+                    _ => a ^ b,
+                }),
+                (Value::Number(b), Value::Number(a)) => {
+                    let (a, b) = (a as i64, b as i64);
+                    number(match operator {
+                        b"and" => a & b,
+                        b"or" => a | b,
+                        _ => a ^ b,
+                    } as f64)
+                }
+                _ => return Err(Error::InvalidFunction("operands differ in type")),
+            },
+            b"not" => match self.pop()? {
+                Value::Bool(b) => Value::Bool(!b),
+                Value::Number(n) => number(!(n as i64) as f64),
+            },
+            b"dup" => *self.0.last().ok_or(Error::InvalidFunction("stack underflow"))?,
+            b"pop" => {
+                self.pop()?;
+                return Ok(());
+            }
+            b"exch" => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+                self.0.push(b);
+                a
+            }
+            b"copy" => {
+                let n = self.pop_count()?;
+                let start = self.0.len() - n;
+                self.0.extend_from_within(start..);
+                return Ok(());
+            }
+            b"index" => {
+                let n = self.pop_number()?;
+                if n < 0.0 || n as usize >= self.0.len() {
+                    return Err(Error::InvalidFunction("stack underflow"));
+                }
+                self.0[self.0.len() - 1 - n as usize]
+            }
+            b"roll" => {
+                let j = self.pop_number()? as i64;
+                let n = self.pop_count()?;
+                if n > 0 {
+                    let start = self.0.len() - n;
+                    // Positive amounts move items towards the top.
+                    self.0[start..].rotate_right(j.rem_euclid(n as i64) as usize);
+                }
+                return Ok(());
+            }
+            _ => return Err(Error::InvalidFunction("unknown operator")),
+        };
+        self.0.push(result);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::PdfDocument;
+    use crate::primitives::{Dictionary, Name, PdfStream, Primitives};
+    use crate::error::Error;
+    use super::Function;
+
+    fn numbers(values: &[f64]) -> Primitives {
+        Primitives::Array(values.iter().map(|&v| Primitives::Real(v)).collect())
+    }
+
+    fn function(entries: Vec<(&str, Primitives)>, data: Option<&[u8]>) -> Primitives {
+        let dict: Dictionary = entries.into_iter().map(|(k, v)| (Name::from(k), v)).collect();
+        match data {
+            Some(data) => Primitives::Stream(PdfStream::new(dict, data.to_vec())),
+            None => Primitives::Dict(dict),
+        }
+    }
+
+    fn calculator(program: &str) -> Result<Function, crate::error::Error> {
+        let calculator = function(
+            vec![("FunctionType", Primitives::Int(4)), ("Domain", numbers(&[-10.0, 10.0, -10.0, 10.0])), ("Range", numbers(&[-100.0, 100.0]))],
+            Some(program.as_bytes()),
+        );
+        Function::parse(&PdfDocument::new(), &calculator)
+    }
+
+    #[test]
+    fn evaluate_functions() {
+        let doc = PdfDocument::new();
+        // Two inputs, each with two samples, and two outputs.
+        let sampled = function(vec![
+            ("FunctionType", Primitives::Int(0)), ("Domain", numbers(&[0.0, 1.0, 0.0, 1.0])), ("Range", numbers(&[0.0, 1.0, 0.0, 1.0])),
+            ("Size", numbers(&[2.0, 2.0])), ("BitsPerSample", Primitives::Int(8)),
+        ], Some(b"\x00\xff\xff\xff\x00\x00\xff\x00"));
+        let sampled = Function::parse(&doc, &sampled).unwrap();
+        assert_eq!(sampled.evaluate(&[0.0, 0.0]).unwrap(), [0.0, 1.0]);
+        assert_eq!(sampled.evaluate(&[1.0, 1.0]).unwrap(), [1.0, 0.0]);
+        assert_eq!(sampled.evaluate(&[0.5, 0.5]).unwrap(), [0.5, 0.5]);
+        assert_eq!(sampled.evaluate(&[2.0, -1.0]).unwrap(), [1.0, 1.0]);
+
+        let exponential = function(vec![
+            ("FunctionType", Primitives::Int(2)), ("Domain", numbers(&[0.0, 1.0])),
+            ("C0", numbers(&[1.0, 0.0])), ("C1", numbers(&[0.0, 1.0])), ("N", Primitives::Int(2)),
+        ], None);
+        let exponential = Function::parse(&doc, &exponential).unwrap();
+        assert_eq!(exponential.evaluate(&[0.5]).unwrap(), [0.75, 0.25]);
+        assert_eq!(exponential.evaluate(&[3.0]).unwrap(), [0.0, 1.0]);
+
+        let linear = function(vec![("FunctionType", Primitives::Int(2)), ("Domain", numbers(&[0.0, 1.0])), ("N", Primitives::Int(1))], None);
+        let stitching = function(vec![
+            ("FunctionType", Primitives::Int(3)), ("Domain", numbers(&[0.0, 2.0])),
+            ("Functions", Primitives::Array(vec![linear.clone(), linear])),
+            ("Bounds", numbers(&[1.0])), ("Encode", numbers(&[0.0, 1.0, 1.0, 0.0])), ("Range", numbers(&[0.0, 0.5])),
+        ], None);
+        let stitching = Function::parse(&doc, &stitching).unwrap();
+        assert_eq!(stitching.evaluate(&[0.25]).unwrap(), [0.25]);
+        assert_eq!(stitching.evaluate(&[1.0]).unwrap(), [0.5]);
+        assert_eq!(stitching.evaluate(&[1.75]).unwrap(), [0.25]);
+
+        let huge = function(vec![
+            ("FunctionType", Primitives::Int(0)), ("Domain", numbers(&[0.0, 1.0, 0.0, 1.0])), ("Range", numbers(&[0.0, 1.0])),
+            ("Size", numbers(&[1e12, 1e12])), ("BitsPerSample", Primitives::Int(8)),
+        ], Some(b"\x00"));
+        assert!(matches!(Function::parse(&doc, &huge), Err(Error::InvalidFunction(_))));
+        assert!(Function::parse(&doc, &function(vec![("FunctionType", Primitives::Int(2))], None)).is_err());
+        assert!(Function::parse(&doc, &function(vec![("FunctionType", Primitives::Int(5)), ("Domain", numbers(&[0.0, 1.0]))], None)).is_err());
+    }
+
+    #[test]
+    fn evaluate_calculator_functions() {
+        let evaluate = |program: &str, input: &[f64]| calculator(program).unwrap().evaluate(input).unwrap();
+        assert_eq!(evaluate("{ add 2 div }", &[3.0, 4.0]), [3.5]);
+        assert_eq!(evaluate("{ exch sub abs sqrt }", &[2.0, 6.0]), [2.0]);
+        assert_eq!(evaluate("{ 2 copy gt { pop } { exch pop } ifelse }", &[3.0, 7.0]), [7.0]);
+        assert_eq!(evaluate("{ 1 index 0 lt { neg } if mul }", &[-2.0, 3.0]), [6.0]);
+        assert_eq!(evaluate("{ 5 3 1 roll pop pop }", &[1.0, 2.0]), [5.0]);
+        assert_eq!(evaluate("{ pop 7 2 idiv 7 2 mod 1 3 bitshift add add }", &[0.0, 0.0]), [12.0]);
+        assert_eq!(evaluate("{ pop 1 0 atan 0.5 round cvi add }", &[0.0, 0.0]), [91.0]);
+        assert_eq!(evaluate("{ eq true and false or { 1 } { 0 } ifelse }", &[4.0, 4.0]), [1.0]);
+        // Outputs are clipped to the range.
+        assert_eq!(evaluate("{ mul 10 mul }", &[5.0, 5.0]), [100.0]);
+
+        assert!(calculator("{ 1 foo }").is_err());
+        assert!(calculator("{ 1 { 2 } }").is_err());
+        assert!(calculator("{ add").is_err());
+        let function = calculator("{ pop pop pop }").unwrap();
+        assert!(function.evaluate(&[1.0, 2.0]).is_err());
+        assert!(calculator("{ 0 div }").unwrap().evaluate(&[1.0, 2.0]).is_err());
+        assert!(calculator("{ gt }").unwrap().evaluate(&[1.0, 2.0]).is_err());
+    }
+}
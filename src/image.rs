@@ -0,0 +1,486 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::document::PdfDocument;
+use crate::primitives::{Dictionary, Name, PdfStream, Primitives, Ref};
+use crate::colorspace::ColorSpace;
+use crate::content::{matrix, multiply, ContentParser, IDENTITY};
+use crate::filters::{decode_except_image, filter_names, flate_encode};
+use crate::error::Error;
+
+// Form XObjects nested deeper than this aren't searched for images.
+const MAX_DEPTH: usize = 32;
+
+// Abbreviated keys and values of inline image dictionaries.
+const INLINE_KEYS: [(&str, &str); 10] = [
+    ("BPC", "BitsPerComponent"),
+    ("CS", "ColorSpace"),
+    ("D", "Decode"),
+    ("DP", "DecodeParms"),
+    ("F", "Filter"),
+    ("H", "Height"),
+    ("IM", "ImageMask"),
+    ("I", "Interpolate"),
+    ("L", "Length"),
+    ("W", "Width"),
+];
+const INLINE_VALUES: [(&str, &str); 11] = [
+    ("G", "DeviceGray"),
+    ("RGB", "DeviceRGB"),
+    ("CMYK", "DeviceCMYK"),
+    ("I", "Indexed"),
+    ("AHx", "ASCIIHexDecode"),
+    ("A85", "ASCII85Decode"),
+    ("LZW", "LZWDecode"),
+    ("Fl", "FlateDecode"),
+    ("RL", "RunLengthDecode"),
+    ("CCF", "CCITTFaxDecode"),
+    ("DCT", "DCTDecode"),
+];
+
+// An image painted on a page, directly or through form XObjects. An image
+// drawn several times is listed each time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageImage {
+    // The XObject resource name, `None` for inline images.
+    pub name: Option<String>,
+    pub reference: Option<Ref>,
+    pub width: u32,
+    pub height: u32,
+    // `None` for stencil masks, which use the fill color.
+    pub color_space: Option<ColorSpace>,
+    pub bits_per_component: u8,
+    pub filters: Vec<String>,
+    pub image_mask: bool,
+    // The current transformation matrix when the image is painted, which
+    // maps the unit square onto the page.
+    pub matrix: [f64; 6],
+    // Inline images get their dictionary with the keys spelled out.
+    stream: PdfStream,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    Jpeg,
+    Jpeg2000,
+    Png,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Jpeg2000 => "jp2",
+            ImageFormat::Png => "png",
+        }
+    }
+}
+
+impl PdfDocument {
+    pub fn page_images(&self, page: usize) -> Result<Vec<PageImage>, Error> {
+        let page = self.page(page)?;
+        let mut collector = ImageCollector {
+            doc: self,
+            forms: HashSet::new(),
+            images: Vec::new(),
+        };
+        collector.collect(&page.contents(self)?, &page.resources(self)?, IDENTITY, 0)?;
+        Ok(collector.images)
+    }
+
+    // The image as a file: JPEG and JPEG 2000 data is returned as stored,
+    // anything else is decoded into a PNG with the `/SMask` as alpha.
+    pub fn export_image(&self, image: &PageImage) -> Result<(ImageFormat, Vec<u8>), Error> {
+        let (data, codec) = decode_except_image(&image.stream.dict, &image.stream.data)?;
+        match codec.as_deref() {
+            Some(b"DCTDecode") | Some(b"DCT") => return Ok((ImageFormat::Jpeg, data)),
+            Some(b"JPXDecode") => return Ok((ImageFormat::Jpeg2000, data)),
+            Some(codec) => return Err(Error::UnsupportedFilter(String::from_utf8_lossy(codec).into_owned())),
+            None => (),
+        }
+        let (width, height) = (image.width as usize, image.height as usize);
+        if width == 0 || height == 0 {
+            return Err(Error::DecodeError("image has no pixels"));
+        }
+
+        let (channels, pixels, alpha) = if image.image_mask {
+            // Samples of 0 are painted unless `/Decode` is [1 0].
+            let inverted = self.get(&image.stream.dict, "Decode")?.get_array().and_then(|d| d.first()).and_then(|d| d.get_real()) == Some(1.0);
+            let samples = unpack(&data, width, height, 1, 1);
+            let alpha = samples.iter().map(|&s| if (s == 0) != inverted { 255 } else { 0 }).collect();
+            (1, vec![0; width * height], Some(alpha))
+        } else {
+            let color_space = image.color_space.as_ref().ok_or(Error::DecodeError("image has no color space"))?;
+            let samples = unpack(&data, width, height, color_space.components(), image.bits_per_component);
+            let decode = self.get(&image.stream.dict, "Decode")?.get_array().map(|d| d.iter().filter_map(|d| d.get_real()).collect::<Vec<f64>>());
+            let rgb = color_space.image_to_rgb(&samples, image.bits_per_component, decode.as_deref());
+            let (channels, pixels) = if color_space.is_gray() { (1, rgb.into_iter().step_by(3).collect()) } else { (3, rgb) };
+            let alpha = match self.get(&image.stream.dict, "SMask")? {
+                Primitives::Stream(smask) => Some(self.soft_mask(&smask, width, height)?),
+                _ => None,
+            };
+            (channels, pixels, alpha)
+        };
+        let pixels = match alpha {
+            Some(alpha) => pixels
+                .chunks(channels)
+                .zip(alpha)
+                .flat_map(|(pixel, alpha)| pixel.iter().cloned().chain(Some(alpha)))
+                .collect(),
+            None => pixels,
+        };
+        let channels = pixels.len() / (width * height);
+        Ok((ImageFormat::Png, encode_png(image.width, image.height, channels, &pixels)))
+    }
+
+    // Writes the exported image to `path` with the extension of its format
+    // and returns the path written.
+    pub fn save_image<P: AsRef<Path>>(&self, image: &PageImage, path: P) -> Result<PathBuf, Error> {
+        let (format, data) = self.export_image(image)?;
+        let path = path.as_ref().with_extension(format.extension());
+        std::fs::write(&path, data)?;
+        Ok(path)
+    }
+
+    // The soft mask's gray levels, scaled to the image's size.
+    fn soft_mask(&self, smask: &PdfStream, width: usize, height: usize) -> Result<Vec<u8>, Error> {
+        let mask_width = self.get(&smask.dict, "Width")?.get_integer().unwrap_or(0).max(0) as usize;
+        let mask_height = self.get(&smask.dict, "Height")?.get_integer().unwrap_or(0).max(0) as usize;
+        let bits = bits_per_component(self.get(&smask.dict, "BitsPerComponent")?.get_integer().unwrap_or(8))?;
+        if mask_width == 0 || mask_height == 0 {
+            return Ok(vec![255; width * height]);
+        }
+        let samples = unpack(&smask.decode()?, mask_width, mask_height, 1, bits);
+        let max = ((1u32 << bits) - 1) as f64;
+        let mut alpha = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let sample = samples[y * mask_height / height * mask_width + x * mask_width / width];
+                alpha.push((sample as f64 * 255.0 / max).round() as u8);
+            }
+        }
+        Ok(alpha)
+    }
+}
+
+struct ImageCollector<'a> {
+    doc: &'a PdfDocument,
+    // Forms being searched, to stop at forms that draw themselves.
+    forms: HashSet<Ref>,
+    images: Vec<PageImage>,
+}
+
+impl<'a> ImageCollector<'a> {
+    fn collect(&mut self, content: &Vec<u8>, resources: &Dictionary, ctm: [f64; 6], depth: usize) -> Result<(), Error> {
+        let mut parser = ContentParser::new(content);
+        let mut saved = Vec::new();
+        let mut ctm = ctm;
+        while let Some(op) = parser.next_operation()? {
+            match &op.operator[..] {
+                b"q" => saved.push(ctm),
+                b"Q" => ctm = saved.pop().unwrap_or(ctm),
+                b"cm" => {
+                    if let Some(m) = matrix(&Primitives::Array(op.operands)) {
+                        ctm = multiply(&m, &ctm);
+                    }
+                }
+                b"Do" => {
+                    if let Some(Primitives::Name(name)) = op.operands.last() {
+                        self.xobject(name, resources, ctm, depth)?;
+                    }
+                }
+                b"EI" => {
+                    if let [Primitives::Dict(dict), Primitives::Str(data)] = &op.operands[..] {
+                        let stream = PdfStream::new(expand_inline(dict), data.clone());
+                        let image = self.image(None, None, stream, resources, ctm)?;
+                        self.images.push(image);
+                    }
+                }
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn xobject(&mut self, name: &Name, resources: &Dictionary, ctm: [f64; 6], depth: usize) -> Result<(), Error> {
+        let doc = self.doc;
+        let entry = match doc.get(resources, "XObject")?.get_dict().and_then(|x| x.get(&name.0[..])) {
+            Some(entry) => entry.clone(),
+            None => return Ok(()),
+        };
+        let reference = entry.get_ref();
+        let stream = match doc.resolve(&entry)? {
+            Primitives::Stream(stream) => stream,
+            _ => return Ok(()),
+        };
+        match doc.get(&stream.dict, "Subtype")?.get_name() {
+            Some(b"Image") => {
+                let name = Some(String::from_utf8_lossy(&name.0).into_owned());
+                let image = self.image(name, reference, stream, resources, ctm)?;
+                self.images.push(image);
+            }
+            Some(b"Form") if depth < MAX_DEPTH => {
+                if reference.is_some_and(|r| !self.forms.insert(r)) {
+                    return Ok(());
+                }
+                let form_matrix = matrix(&doc.get(&stream.dict, "Matrix")?).unwrap_or(IDENTITY);
+                // Forms without resources use those of the page, as older
+                // files expect.
+                let form_resources = match doc.get(&stream.dict, "Resources")? {
+                    Primitives::Dict(form_resources) => form_resources,
+                    _ => resources.clone(),
+                };
+                self.collect(&stream.decode()?, &form_resources, multiply(&form_matrix, &ctm), depth + 1)?;
+                if let Some(r) = reference {
+                    self.forms.remove(&r);
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn image(&self, name: Option<String>, reference: Option<Ref>, stream: PdfStream, resources: &Dictionary, ctm: [f64; 6]) -> Result<PageImage, Error> {
+        let doc = self.doc;
+        let dict = &stream.dict;
+        let image_mask = doc.get(dict, "ImageMask")?.get_bool().unwrap_or(false);
+        let color_space = match dict.get(&b"ColorSpace"[..]) {
+            Some(color_space) if !image_mask => Some(ColorSpace::parse(doc, color_space, resources)?),
+            _ => None,
+        };
+        let bits_per_component = if image_mask { 1 } else { bits_per_component(doc.get(dict, "BitsPerComponent")?.get_integer().unwrap_or(8))? };
+        let size = |key: &str| -> Result<u32, Error> { Ok(doc.get(dict, key)?.get_integer().unwrap_or(0).max(0) as u32) };
+        Ok(PageImage {
+            name: name,
+            reference: reference,
+            width: size("Width")?,
+            height: size("Height")?,
+            color_space: color_space,
+            bits_per_component: bits_per_component,
+            filters: filter_names(dict).iter().map(|f| String::from_utf8_lossy(f).into_owned()).collect(),
+            image_mask: image_mask,
+            matrix: ctm,
+            stream: stream.clone(),
+        })
+    }
+}
+
+// The dictionary of an inline image with full keys, and full names for the
+// color space and filters.
+fn expand_inline(dict: &Dictionary) -> Dictionary {
+    let expand_value = |value: &Primitives| -> Primitives {
+        let expand = |name: &Name| match INLINE_VALUES.iter().find(|(short, _)| short.as_bytes() == &name.0[..]) {
+            Some((_, full)) => Primitives::Name(Name::from(full)),
+            None => Primitives::Name(name.clone()),
+        };
+        match value {
+            Primitives::Name(name) => expand(name),
+            Primitives::Array(items) => Primitives::Array(items.iter().map(|i| if let Primitives::Name(n) = i { expand(n) } else { i.clone() }).collect()),
+            other => other.clone(),
+        }
+    };
+    let mut expanded = Dictionary::new();
+    for (key, value) in dict {
+        let key = match INLINE_KEYS.iter().find(|(short, _)| short.as_bytes() == &key.0[..]) {
+            Some((_, full)) => Name::from(full),
+            None => key.clone(),
+        };
+        let value = match &key.0[..] {
+            b"ColorSpace" | b"Filter" => expand_value(value),
+            _ => value.clone(),
+        };
+        expanded.insert(key, value);
+    }
+    expanded
+}
+
+// `unpack` only knows the sample sizes PDF allows.
+fn bits_per_component(bits: i64) -> Result<u8, Error> {
+    match bits {
+        1 | 2 | 4 | 8 | 16 => Ok(bits as u8),
+        _ => Err(Error::DecodeError("unsupported /BitsPerComponent")),
+    }
+}
+
+// Unpacks `components` samples of `bits` bits per pixel. Rows start on byte
+// boundaries, missing data reads as zero.
+fn unpack(data: &[u8], width: usize, height: usize, components: usize, bits: u8) -> Vec<u16> {
+    let bits = bits as usize;
+    let per_row = width * components;
+    let row_bytes = (per_row * bits).div_ceil(8);
+    let mut samples = Vec::with_capacity(per_row * height);
+    for y in 0..height {
+        let row = data.get(y * row_bytes..).unwrap_or(&[]);
+        for i in 0..per_row {
+            let sample = match bits {
+                8 => row.get(i).cloned().unwrap_or(0) as u16,
+                16 => u16::from_be_bytes([row.get(2 * i).cloned().unwrap_or(0), row.get(2 * i + 1).cloned().unwrap_or(0)]),
+                _ => {
+                    let bit = i * bits;
+                    let byte = row.get(bit / 8).cloned().unwrap_or(0) as u16;
+                    (byte >> (8 - bits - bit % 8)) & ((1 << bits) - 1)
+                }
+            };
+            samples.push(sample);
+        }
+    }
+    samples
+}
+
+// Gray, gray and alpha, RGB or RGBA pixels with 8 bits per channel as PNG.
+fn encode_png(width: u32, height: u32, channels: usize, pixels: &[u8]) -> Vec<u8> {
+    let color_type = match channels {
+        1 => 0,
+        2 => 4,
+        3 => 2,
+        _ => 6,
+    };
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    header.extend_from_slice(&[8, color_type, 0, 0, 0]);
+    // Each row starts with the filter type, 0 for none.
+    let row_bytes = width as usize * channels;
+    let mut raw = Vec::with_capacity((row_bytes + 1) * height as usize);
+    for row in pixels.chunks(row_bytes) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    for (kind, data) in [(&b"IHDR"[..], header), (b"IDAT", flate_encode(&raw)), (b"IEND", Vec::new())].iter() {
+        png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        let start = png.len();
+        png.extend_from_slice(kind);
+        png.extend_from_slice(data);
+        let crc = crc32(&png[start..]);
+        png.extend_from_slice(&crc.to_be_bytes());
+    }
+    png
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { 0xedb8_8320 ^ (crc >> 1) } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::PdfDocument;
+    use crate::primitives::{Dictionary, Name, PdfStream, Primitives};
+    use crate::filters::flate_decode;
+    use crate::colorspace::ColorSpace;
+    use crate::error::Error;
+    use super::{crc32, ImageFormat};
+
+    // The size, color type and inflated pixel rows of a PNG written by
+    // `encode_png`.
+    fn read_png(png: &[u8]) -> (u32, u32, u8, Vec<u8>) {
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&png[12..16], b"IHDR");
+        let width = u32::from_be_bytes([png[16], png[17], png[18], png[19]]);
+        let height = u32::from_be_bytes([png[20], png[21], png[22], png[23]]);
+        let idat_length = u32::from_be_bytes([png[33], png[34], png[35], png[36]]) as usize;
+        assert_eq!(&png[37..41], b"IDAT");
+        let crc = u32::from_be_bytes([png[41 + idat_length], png[42 + idat_length], png[43 + idat_length], png[44 + idat_length]]);
+        assert_eq!(crc, crc32(&png[37..41 + idat_length]));
+        (width, height, png[25], flate_decode(&png[41..41 + idat_length]).unwrap())
+    }
+
+    #[test]
+    fn read_page_images() {
+        let doc = PdfDocument::open("tests/examples/PDF_sample.pdf").unwrap();
+        let images = doc.page_images(0).unwrap();
+        assert_eq!(images.len(), 1);
+        let image = &images[0];
+        assert_eq!(image.name.as_deref(), Some("Image14"));
+        assert_eq!((image.width, image.height, image.bits_per_component), (200, 200, 8));
+        assert_eq!(image.filters, ["FlateDecode"]);
+        assert!(matches!(&image.color_space, Some(ColorSpace::Indexed { base, hival: 15, .. }) if **base == ColorSpace::DeviceRgb));
+        let (format, png) = doc.export_image(image).unwrap();
+        assert_eq!(format, ImageFormat::Png);
+        let (width, height, color_type, rows) = read_png(&png);
+        assert_eq!((width, height, color_type, rows.len()), (200, 200, 2, 200 * 601));
+
+        // A stencil mask drawn twice from a form XObject.
+        let doc = PdfDocument::open("tests/examples/140514041111253731pdf1.pdf").unwrap();
+        let images = doc.page_images(0).unwrap();
+        let matrices: Vec<[f64; 6]> = images.iter().map(|i| i.matrix).collect();
+        assert_eq!(matrices, [[1.0, 0.0, 0.0, 1.0, 108.0, 72.0], [1.0, 0.0, 0.0, 1.0, 487.0, 770.0]]);
+        assert!(images[0].image_mask && images[0].color_space.is_none());
+        let (_, _, color_type, _) = read_png(&doc.export_image(&images[0]).unwrap().1);
+        assert_eq!(color_type, 4);
+    }
+
+    #[test]
+    fn export_images() {
+        let mut doc = PdfDocument::open("tests/examples/sample.pdf").unwrap();
+        let stream = |entries: Vec<(&str, Primitives)>, data: &[u8]| {
+            let dict: Dictionary = entries.into_iter().map(|(k, v)| (Name::from(k), v)).collect();
+            Primitives::Stream(PdfStream::new(dict, data.to_vec()))
+        };
+        let n = |name: &str| Primitives::name(name.as_bytes().to_vec());
+        let smask = doc.add_object(stream(vec![("Width", Primitives::Int(1)), ("Height", Primitives::Int(1)), ("BitsPerComponent", Primitives::Int(8)), ("ColorSpace", n("DeviceGray"))], b"\x80"));
+        let gray = doc.add_object(stream(vec![
+            ("Subtype", n("Image")), ("Width", Primitives::Int(2)), ("Height", Primitives::Int(1)),
+            ("BitsPerComponent", Primitives::Int(4)), ("ColorSpace", n("Gray")), ("SMask", smask.into()),
+        ], b"\x0f"));
+        let jpeg = doc.add_object(stream(vec![
+            ("Subtype", n("Image")), ("Width", Primitives::Int(1)), ("Height", Primitives::Int(1)),
+            ("ColorSpace", n("DeviceRGB")), ("Filter", Primitives::Array(vec![n("ASCIIHexDecode"), n("DCTDecode")])),
+        ], b"FFD8FFD9>"));
+        let content = doc.add_object(stream(vec![], b"q 20 0 0 10 5 5 cm /Im1 Do /Im2 Do Q\nBI /W 2 /H 1 /CS /RGB /BPC 8 ID \xff\x00\x00\x00\x00\xff EI"));
+
+        let mut color_spaces = Dictionary::new();
+        color_spaces.insert(Name::from("Gray"), n("DeviceGray"));
+        let mut xobjects = Dictionary::new();
+        xobjects.insert(Name::from("Im1"), gray.into());
+        xobjects.insert(Name::from("Im2"), jpeg.into());
+        let mut resources = Dictionary::new();
+        resources.insert(Name::from("ColorSpace"), Primitives::Dict(color_spaces));
+        resources.insert(Name::from("XObject"), Primitives::Dict(xobjects));
+        let page = doc.page(0).unwrap();
+        let mut dict = page.dict;
+        dict.insert(Name::from("Contents"), content.into());
+        dict.insert(Name::from("Resources"), Primitives::Dict(resources));
+        doc.replace_object(page.reference, Primitives::Dict(dict)).unwrap();
+
+        let images = doc.page_images(0).unwrap();
+        assert_eq!(images.len(), 3);
+        assert_eq!(images[0].color_space, Some(ColorSpace::DeviceGray));
+        assert_eq!(images[0].matrix, [20.0, 0.0, 0.0, 10.0, 5.0, 5.0]);
+        let (_, _, color_type, rows) = read_png(&doc.export_image(&images[0]).unwrap().1);
+        assert_eq!((color_type, rows), (4, vec![0, 0, 128, 255, 128]));
+
+        assert_eq!(images[1].filters, ["ASCIIHexDecode", "DCTDecode"]);
+        assert_eq!(doc.export_image(&images[1]).unwrap(), (ImageFormat::Jpeg, b"\xff\xd8\xff\xd9".to_vec()));
+
+        let inline = &images[2];
+        assert_eq!((inline.name.as_ref(), inline.reference), (None, None));
+        assert_eq!(inline.color_space, Some(ColorSpace::DeviceRgb));
+        assert_eq!(inline.matrix, [1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+        let (_, _, color_type, rows) = read_png(&doc.export_image(inline).unwrap().1);
+        assert_eq!((color_type, rows), (2, vec![0, 255, 0, 0, 0, 0, 255]));
+
+        let path = std::env::temp_dir().join(format!("pdfbat-image-{}", std::process::id()));
+        let saved = doc.save_image(inline, &path).unwrap();
+        assert_eq!(saved.extension().and_then(|e| e.to_str()), Some("png"));
+        std::fs::remove_file(saved).unwrap();
+
+        // Sample sizes other than 1, 2, 4, 8 and 16 bits are rejected.
+        doc.replace_object(smask, stream(vec![("Width", Primitives::Int(1)), ("Height", Primitives::Int(1)), ("BitsPerComponent", Primitives::Int(32))], b"\x80\x80\x80\x80")).unwrap();
+        let images = doc.page_images(0).unwrap();
+        assert!(matches!(doc.export_image(&images[0]), Err(Error::DecodeError(_))));
+        doc.replace_object(gray, stream(vec![
+            ("Subtype", n("Image")), ("Width", Primitives::Int(2)), ("Height", Primitives::Int(1)),
+            ("BitsPerComponent", Primitives::Int(3)), ("ColorSpace", n("Gray")),
+        ], b"\xff")).unwrap();
+        assert!(matches!(doc.page_images(0), Err(Error::DecodeError(_))));
+    }
+}
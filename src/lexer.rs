@@ -79,6 +79,20 @@ impl<T: ReadSeek> Lexer<T> {
         l
     }
 
+    pub fn stream(&self) -> &Stream<T> {
+        &self.stream
+    }
+
+    pub fn stream_mut(&mut self) -> &mut Stream<T> {
+        &mut self.stream
+    }
+
+    // Moves the lexer to `pos` and loads the character found there.
+    pub fn set_pos(&mut self, pos: u64) {
+        self.stream.set_pos(pos);
+        self.next_char();
+    }
+
     fn next_char(&mut self) -> Option<u8> {
         self.current_char = self.stream.get_byte();
         self.current_char
@@ -452,6 +466,12 @@ impl<T: ReadSeek> Lexer<T> {
             str = possible_command;
             known_command_found = self.known_commands.as_ref().map_or(false, |map| map.contains_key(&str[..]));
         }
+        match &str[..] {
+            b"true" => return Ok(Primitives::Bool(true)),
+            b"false" => return Ok(Primitives::Bool(false)),
+            b"null" => return Ok(Primitives::Null),
+            _ => (),
+        }
         if str == b"BI" {
             // Keep track of the current stream position, since it's needed in order to correctly cache inline images;
             // see `Parser.makeInlineImage`.
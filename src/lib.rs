@@ -1,10 +1,56 @@
 mod document;
 mod reader;
-mod stream;
+pub mod stream;
+mod cache;
+pub mod chunked;
 mod primitives;
 mod lexer;
 mod parser;
+mod filters;
+pub mod linearization;
 mod xref;
+mod page;
+pub mod serializer;
+mod writer;
+mod merge;
+mod names;
+mod destination;
+mod outline;
+mod annotation;
+mod link;
+mod form;
+mod appearance;
+mod fdf;
+mod attachment;
+mod content;
+mod image;
+mod colorspace;
+mod function;
+mod page_labels;
+mod date;
+mod metadata;
+mod xml;
 mod utils;
 mod error;
 
+pub use document::PdfDocument;
+pub use page::Page;
+pub use writer::SaveOptions;
+pub use merge::Importer;
+pub use names::{NameTree, NumberTree, Tree, TreeKey};
+pub use destination::{Destination, View};
+pub use outline::{OutlineItem, OutlineStyle};
+pub use annotation::{Annotation, AnnotationColor, AnnotationOptions, AnnotationType, Appearances};
+pub use image::{ImageFormat, PageImage};
+pub use colorspace::ColorSpace;
+pub use function::Function;
+pub use attachment::{Attachment, AttachmentOptions};
+pub use fdf::{FieldData, FormData};
+pub use form::{FieldOption, FieldType, FieldValue, FormField, Widget};
+pub use form::{FIELD_READ_ONLY, FIELD_REQUIRED, FIELD_NO_EXPORT, FIELD_MULTILINE, FIELD_PASSWORD, FIELD_NO_TOGGLE_TO_OFF, FIELD_RADIO, FIELD_PUSH_BUTTON, FIELD_COMBO, FIELD_EDIT, FIELD_MULTI_SELECT};
+pub use link::{Link, LinkTarget, RemoteDestination};
+pub use date::PdfDate;
+pub use metadata::{DocumentInfo, XmpMetadata, XmpValue};
+pub use page_labels::{LabelStyle, PageLabelRange};
+pub use primitives::{Dictionary, Name, PdfStream, Primitives, Ref};
+pub use error::Error;
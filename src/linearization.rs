@@ -0,0 +1,366 @@
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::stream::{Stream, ReadSeek};
+use crate::primitives::Primitives;
+use crate::error::Error;
+
+// The linearization parameter dictionary, which must be the first object in
+// a linearized ("fast web view") file, e.g.
+// 26 0 obj
+// << /Linearized 1 /L 8355 /O 28 /E 3386 /N 1 /T 8050 /H [ 458 161 ] >>
+// endobj
+#[derive(Debug, Clone, PartialEq)]
+pub struct Linearization {
+    // Length of the entire file in bytes (/L).
+    pub length: u64,
+    // Offset and length of the primary hint stream (/H).
+    pub hints_offset: u64,
+    pub hints_length: u64,
+    // Offset and length of the optional overflow hint stream.
+    pub hints_offset2: Option<u64>,
+    pub hints_length2: Option<u64>,
+    // Object number of the first page's page object (/O).
+    pub object_number_first: u32,
+    // Offset of the end of the first page (/E).
+    pub end_first: u64,
+    // Number of pages in the document (/N).
+    pub num_pages: u32,
+    // Offset of the first entry in the main cross-reference table (/T).
+    pub main_xref_entries_offset: u64,
+    // Page number of the first page (/P), usually 0.
+    pub page_first: u32,
+}
+
+fn get_u64(dict: &Primitives, key: &str) -> Result<u64, Error> {
+    match dict.get(key).and_then(|v| v.get_integer()) {
+        Some(num) if num >= 0 => Ok(num as u64),
+        _ => Err(Error::InvalidFile("invalid linearization dictionary")),
+    }
+}
+
+impl Linearization {
+    // Looks at the first object of `stream`. Returns `None` if the file is
+    // not linearized, or if `/L` doesn't match the file length, which means
+    // it was modified afterwards and the linearization data can't be used.
+    pub fn create<T: ReadSeek>(stream: Stream<T>) -> Result<Option<Linearization>, Error> {
+        let length = stream.len();
+        Linearization::create_with_length(stream, length)
+    }
+
+    // Same as `create`, for when `stream` only holds the beginning of a file
+    // that is `length` bytes long.
+    pub fn create_with_length<T: ReadSeek>(stream: Stream<T>, length: u64) -> Result<Option<Linearization>, Error> {
+        let lexer = Lexer::new(stream);
+        let mut parser = Parser::new(lexer, false);
+
+        let obj1 = parser.get_obj()?;
+        let obj2 = parser.get_obj()?;
+        let obj3 = parser.get_obj()?;
+        let dict = parser.get_obj()?;
+        if !obj1.is_integer() || !obj2.is_integer() || !obj3.is_cmd("obj") {
+            return Ok(None);
+        }
+        match dict.get("Linearized").and_then(|v| v.get_real()) {
+            Some(version) if version > 0.0 => (),
+            _ => return Ok(None),
+        }
+
+        if get_u64(&dict, "L")? != length {
+            eprintln!("The \"L\" parameter in the linearization dictionary does not equal the stream length.");
+            return Ok(None);
+        }
+
+        let hints = dict.get("H").and_then(|h| h.get_array()).cloned().unwrap_or_default();
+        let hints: Vec<u64> = hints.iter().filter_map(|h| h.get_integer()).map(|h| h as u64).collect();
+        if hints.len() != 2 && hints.len() != 4 {
+            return Err(Error::InvalidFile("Hint array in the linearization dictionary is invalid."));
+        }
+
+        Ok(Some(Linearization {
+            length: length,
+            hints_offset: hints[0],
+            hints_length: hints[1],
+            hints_offset2: hints.get(2).copied(),
+            hints_length2: hints.get(3).copied(),
+            object_number_first: get_u64(&dict, "O")? as u32,
+            end_first: get_u64(&dict, "E")?,
+            num_pages: get_u64(&dict, "N")? as u32,
+            main_xref_entries_offset: get_u64(&dict, "T")?,
+            page_first: dict.get("P").and_then(|p| p.get_integer()).unwrap_or(0) as u32,
+        }))
+    }
+
+    // Reads the primary hint stream and decodes the page offset and shared
+    // object hint tables.
+    pub fn hint_tables<T: ReadSeek+Clone>(&self, stream: &Stream<T>) -> Result<HintTables, Error> {
+        let lexer = Lexer::new(stream.new_sub(self.hints_offset));
+        let mut parser = Parser::new(lexer, true);
+        parser.get_obj()?;
+        parser.get_obj()?;
+        if !parser.get_obj()?.is_cmd("obj") {
+            return Err(Error::InvalidFile("hint stream not found"));
+        }
+        let hint_stream = parser.get_obj()?;
+        let hint_stream = hint_stream.get_stream().ok_or(Error::InvalidFile("hint stream not found"))?;
+        let data = hint_stream.decode()?;
+
+        let shared_offset = hint_stream.get("S").and_then(|s| s.get_integer()).unwrap_or(-1);
+        if shared_offset < 0 || shared_offset as usize > data.len() {
+            return Err(Error::InvalidFile("hint stream has no shared object hint table"));
+        }
+
+        let page_offsets = self.read_page_offsets(&data)?;
+        let shared_objects = self.read_shared_objects(&data[shared_offset as usize..])?;
+        Ok(HintTables {
+            page_offsets: page_offsets,
+            shared_objects: shared_objects,
+        })
+    }
+
+    // Offsets inside the hint tables are computed as if the hint stream
+    // wasn't there, so anything after it has to be moved by its length.
+    fn adjust_offset(&self, offset: u64) -> u64 {
+        if offset >= self.hints_offset {
+            offset + self.hints_length
+        } else {
+            offset
+        }
+    }
+
+    fn read_page_offsets(&self, data: &[u8]) -> Result<PageOffsetHints, Error> {
+        let mut bits = BitReader::new(data);
+        let min_objects = bits.read(32)?;
+        let first_page_offset = bits.read(32)? as u64;
+        let bits_objects = bits.read(16)?;
+        let min_page_length = bits.read(32)? as u64;
+        let bits_page_length = bits.read(16)?;
+        let min_content_offset = bits.read(32)? as u64;
+        let bits_content_offset = bits.read(16)?;
+        let min_content_length = bits.read(32)? as u64;
+        let bits_content_length = bits.read(16)?;
+        let bits_shared_refs = bits.read(16)?;
+        let bits_shared_id = bits.read(16)?;
+        let bits_numerator = bits.read(16)?;
+        let denominator = bits.read(16)?;
+
+        // Each item is stored for all pages before the next item starts, and
+        // every item begins on a byte boundary.
+        let pages = self.num_pages as usize;
+        let num_objects = bits.read_items(pages, bits_objects)?;
+        let page_lengths = bits.read_items(pages, bits_page_length)?;
+        let shared_counts = bits.read_items(pages, bits_shared_refs)?;
+
+        let mut shared_ids = Vec::with_capacity(pages);
+        for &count in &shared_counts {
+            let ids = (0..count).map(|_| bits.read(bits_shared_id)).collect::<Result<Vec<_>, _>>()?;
+            shared_ids.push(ids);
+        }
+        bits.align();
+        let mut numerators = Vec::with_capacity(pages);
+        for &count in &shared_counts {
+            let nums = (0..count).map(|_| bits.read(bits_numerator)).collect::<Result<Vec<_>, _>>()?;
+            numerators.push(nums);
+        }
+        bits.align();
+        let content_offsets = bits.read_items(pages, bits_content_offset)?;
+        let content_lengths = bits.read_items(pages, bits_content_length)?;
+
+        let mut entries = Vec::with_capacity(pages);
+        let mut offset = first_page_offset;
+        for i in 0..pages {
+            let length = min_page_length + page_lengths[i] as u64;
+            entries.push(PageOffsetEntry {
+                num_objects: min_objects + num_objects[i],
+                offset: self.adjust_offset(offset),
+                length: length,
+                shared_objects: shared_ids[i].iter().zip(numerators[i].iter()).map(|(&id, &num)| (id, num)).collect(),
+                content_offset: min_content_offset + content_offsets[i] as u64,
+                content_length: min_content_length + content_lengths[i] as u64,
+            });
+            offset += length;
+        }
+
+        Ok(PageOffsetHints {
+            first_page_offset: self.adjust_offset(first_page_offset),
+            denominator: denominator,
+            entries: entries,
+        })
+    }
+
+    fn read_shared_objects(&self, data: &[u8]) -> Result<SharedObjectHints, Error> {
+        let mut bits = BitReader::new(data);
+        let first_object_number = bits.read(32)?;
+        let first_object_offset = bits.read(32)? as u64;
+        let first_page_entries = bits.read(32)?;
+        let total_entries = bits.read(32)?;
+        let bits_group_objects = bits.read(16)?;
+        let min_group_length = bits.read(32)? as u64;
+        let bits_group_length = bits.read(16)?;
+
+        let count = total_entries as usize;
+        let lengths = bits.read_items(count, bits_group_length)?;
+        let signed = bits.read_items(count, 1)?;
+        for &flag in &signed {
+            if flag != 0 {
+                // A 128-bit MD5 signature of the group; nobody checks it.
+                for _ in 0..4 {
+                    bits.read(32)?;
+                }
+            }
+        }
+        bits.align();
+        let objects = bits.read_items(count, bits_group_objects)?;
+
+        // Groups used by the first page live in the first page section and
+        // have no offset of their own, the others are laid out back to back
+        // starting at `first_object_offset`.
+        let mut groups = Vec::with_capacity(count);
+        let mut offset = first_object_offset;
+        for i in 0..count {
+            let length = min_group_length + lengths[i] as u64;
+            let group_offset = if i < first_page_entries as usize {
+                None
+            } else {
+                let group_offset = self.adjust_offset(offset);
+                offset += length;
+                Some(group_offset)
+            };
+            groups.push(SharedObjectGroup {
+                offset: group_offset,
+                length: length,
+                num_objects: objects[i] + 1,
+            });
+        }
+
+        Ok(SharedObjectHints {
+            first_object_number: first_object_number,
+            first_object_offset: self.adjust_offset(first_object_offset),
+            first_page_entries: first_page_entries,
+            groups: groups,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HintTables {
+    pub page_offsets: PageOffsetHints,
+    pub shared_objects: SharedObjectHints,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageOffsetHints {
+    // Offset of the first page's page object.
+    pub first_page_offset: u64,
+    // Denominator of the fractional positions in `shared_objects`.
+    pub denominator: u32,
+    pub entries: Vec<PageOffsetEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageOffsetEntry {
+    pub num_objects: u32,
+    // Location of the page's objects in the file.
+    pub offset: u64,
+    pub length: u64,
+    // Shared object group identifiers used by the page, each with the
+    // numerator of the fractional position of its first reference.
+    pub shared_objects: Vec<(u32, u32)>,
+    // Location of the content stream relative to `offset`.
+    pub content_offset: u64,
+    pub content_length: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SharedObjectHints {
+    pub first_object_number: u32,
+    pub first_object_offset: u64,
+    // The first `first_page_entries` groups belong to the first page
+    // section, the rest to the shared objects section.
+    pub first_page_entries: u32,
+    pub groups: Vec<SharedObjectGroup>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SharedObjectGroup {
+    pub offset: Option<u64>,
+    pub length: u64,
+    pub num_objects: u32,
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    bit_pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        BitReader {
+            data: data,
+            bit_pos: 0,
+        }
+    }
+
+    fn read(&mut self, bits: u32) -> Result<u32, Error> {
+        if bits > 32 {
+            return Err(Error::InvalidFile("hint table field is too wide"));
+        }
+        let mut value: u64 = 0;
+        for _ in 0..bits {
+            let byte = *self.data.get(self.bit_pos / 8).ok_or(Error::InvalidFile("hint table is truncated"))?;
+            let bit = (byte >> (7 - self.bit_pos % 8)) & 1;
+            value = (value << 1) | bit as u64;
+            self.bit_pos += 1;
+        }
+        Ok(value as u32)
+    }
+
+    fn read_items(&mut self, count: usize, bits: u32) -> Result<Vec<u32>, Error> {
+        let items = (0..count).map(|_| self.read(bits)).collect();
+        self.align();
+        items
+    }
+
+    fn align(&mut self) {
+        self.bit_pos = self.bit_pos.div_ceil(8) * 8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::stream::Stream;
+    use super::Linearization;
+
+    #[test]
+    fn read_linearization_dictionary() {
+        // The first 8355 bytes are the original linearized file, an
+        // incremental update follows.
+        let data = fs::read("tests/examples/140514041111253731pdf1.pdf").unwrap();
+        let stream = Stream::from_bytes(&data[..8355]);
+        let linearization = Linearization::create(stream.clone()).unwrap().unwrap();
+        assert_eq!(linearization.length, 8355);
+        assert_eq!(linearization.object_number_first, 28);
+        assert_eq!(linearization.end_first, 3386);
+        assert_eq!(linearization.num_pages, 1);
+        assert_eq!(linearization.main_xref_entries_offset, 8050);
+        assert_eq!((linearization.hints_offset, linearization.hints_length), (458, 161));
+
+        let hints = linearization.hint_tables(&stream).unwrap();
+        assert_eq!(hints.page_offsets.entries.len(), 1);
+        let first = &hints.page_offsets.entries[0];
+        assert_eq!(first.offset, hints.page_offsets.first_page_offset);
+        assert!(first.offset + first.length <= linearization.end_first);
+        assert!(hints.shared_objects.groups.len() as u32 >= hints.shared_objects.first_page_entries);
+
+        // After the update `/L` no longer matches.
+        let stream = Stream::from_bytes(data);
+        assert_eq!(Linearization::create(stream).unwrap(), None);
+    }
+
+    #[test]
+    fn ignore_regular_files() {
+        let stream = Stream::from_bytes(fs::read("tests/examples/sample.pdf").unwrap());
+        assert_eq!(Linearization::create(stream).unwrap(), None);
+    }
+}
@@ -0,0 +1,235 @@
+use crate::document::PdfDocument;
+use crate::primitives::{Dictionary, Primitives};
+use crate::annotation::AnnotationType;
+use crate::destination::{Destination, View};
+use crate::error::Error;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkTarget {
+    Uri(String),
+    // `None` when the destination doesn't lead to a page of this document,
+    // i.e. the link is broken.
+    GoTo(Option<Destination>),
+    // A destination in another PDF file.
+    GoToRemote {
+        file: String,
+        destination: Option<RemoteDestination>,
+        new_window: bool,
+    },
+    // Opens or runs a file.
+    Launch { file: String, new_window: bool },
+    // Any other action, by its `/S` type, e.g. "Named" or "JavaScript".
+    Other(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RemoteDestination {
+    // Pages of other files are given by index.
+    Page(usize, View),
+    Named(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link {
+    pub page: usize,
+    pub rect: [f64; 4],
+    pub target: LinkTarget,
+}
+
+impl PdfDocument {
+    // The link annotations of all pages in page order. Links without a
+    // destination or action are left out.
+    pub fn links(&self) -> Result<Vec<Link>, Error> {
+        let mut links = Vec::new();
+        for page in 0..self.page_count()? {
+            links.extend(self.page_links(page)?);
+        }
+        Ok(links)
+    }
+
+    pub fn page_links(&self, page: usize) -> Result<Vec<Link>, Error> {
+        let mut links = Vec::new();
+        for annotation in self.annotations(page)? {
+            if annotation.subtype != AnnotationType::Link {
+                continue;
+            }
+            let target = match annotation.dict.get(&b"Dest"[..]) {
+                Some(dest) => Some(LinkTarget::GoTo(self.resolve_destination(dest)?)),
+                None => match self.get(&annotation.dict, "A")? {
+                    Primitives::Dict(action) => Some(self.link_action(&action)?),
+                    _ => None,
+                },
+            };
+            if let Some(target) = target {
+                links.push(Link {
+                    page: page,
+                    rect: annotation.rect,
+                    target: target,
+                });
+            }
+        }
+        Ok(links)
+    }
+
+    fn link_action(&self, action: &Dictionary) -> Result<LinkTarget, Error> {
+        let kind = self.get(action, "S")?.get_name().map(|s| s.to_vec()).unwrap_or_default();
+        let new_window = self.get(action, "NewWindow")?.get_bool().unwrap_or(false);
+        Ok(match &kind[..] {
+            b"URI" => {
+                let uri = self.get(action, "URI")?.get_string_bytes().unwrap_or_default();
+                LinkTarget::Uri(self.absolute_uri(&String::from_utf8_lossy(&uri))?)
+            }
+            b"GoTo" => LinkTarget::GoTo(match action.get(&b"D"[..]) {
+                Some(dest) => self.resolve_destination(dest)?,
+                None => None,
+            }),
+            b"GoToR" => {
+                let destination = match self.get(action, "D")? {
+                    Primitives::Array(dest) => match dest.first().and_then(|p| p.get_integer()) {
+                        Some(page) if page >= 0 => {
+                            let view = View::parse(&dest[1..]).unwrap_or(View::Fit);
+                            Some(RemoteDestination::Page(page as usize, view))
+                        }
+                        _ => None,
+                    },
+                    Primitives::Name(name) => Some(RemoteDestination::Named(String::from_utf8_lossy(&name.0).into_owned())),
+                    other => other.get_text().map(RemoteDestination::Named),
+                };
+                LinkTarget::GoToRemote {
+                    file: self.file_name(&self.get(action, "F")?)?.unwrap_or_default(),
+                    destination: destination,
+                    new_window: new_window,
+                }
+            }
+            b"Launch" => {
+                // Older files only give the file in the Windows specific /Win.
+                let mut file = self.file_name(&self.get(action, "F")?)?;
+                if file.is_none() {
+                    if let Primitives::Dict(win) = self.get(action, "Win")? {
+                        file = self.file_name(&self.get(&win, "F")?)?;
+                    }
+                }
+                LinkTarget::Launch {
+                    file: file.unwrap_or_default(),
+                    new_window: new_window,
+                }
+            }
+            other => LinkTarget::Other(String::from_utf8_lossy(other).into_owned()),
+        })
+    }
+
+    // Relative URIs are resolved against the catalog's `/URI` `/Base`.
+    fn absolute_uri(&self, uri: &str) -> Result<String, Error> {
+        let is_relative = !uri.contains(':');
+        if is_relative {
+            if let Some(base) = self.get(&self.catalog()?, "URI")?.get("Base") {
+                if let Some(base) = self.resolve(base)?.get_string_bytes() {
+                    return Ok(format!("{}{}", String::from_utf8_lossy(&base), uri));
+                }
+            }
+        }
+        Ok(uri.to_string())
+    }
+
+    // The name of a file specification: a string, or a dictionary whose
+    // `/UF` is preferred over the byte string `/F` and the legacy platform
+    // entries.
+    pub(crate) fn file_name(&self, spec: &Primitives) -> Result<Option<String>, Error> {
+        let spec = match spec {
+            Primitives::Dict(spec) => spec,
+            other => return Ok(other.get_text()),
+        };
+        for key in ["UF", "F", "Unix", "DOS", "Mac"].iter() {
+            if let Some(name) = self.get(spec, key)?.get_text() {
+                return Ok(Some(name));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::PdfDocument;
+    use crate::primitives::{Dictionary, Name, Primitives};
+    use crate::destination::{Destination, View};
+    use super::{LinkTarget, RemoteDestination};
+
+    fn link(doc: &mut PdfDocument, key: &str, value: Primitives) -> Primitives {
+        let mut dict = Dictionary::new();
+        dict.insert(Name::from("Type"), Primitives::name(b"Annot".to_vec()));
+        dict.insert(Name::from("Subtype"), Primitives::name(b"Link".to_vec()));
+        dict.insert(Name::from("Rect"), Primitives::Array(vec![Primitives::Int(10), Primitives::Int(20), Primitives::Int(30), Primitives::Int(40)]));
+        dict.insert(Name::from(key), value);
+        doc.add_object(Primitives::Dict(dict)).into()
+    }
+
+    fn action(entries: Vec<(&str, Primitives)>) -> Primitives {
+        Primitives::Dict(entries.into_iter().map(|(k, v)| (Name::from(k), v)).collect())
+    }
+
+    #[test]
+    fn uri_links() {
+        let doc = PdfDocument::open("tests/examples/PDF_sample.pdf").unwrap();
+        let links = doc.links().unwrap();
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].page, 0);
+        assert_eq!(links[0].rect, [220.67, 493.27, 374.63, 523.97]);
+        assert_eq!(links[0].target, LinkTarget::Uri("http://windows8.a-windows.com/".to_string()));
+    }
+
+    #[test]
+    fn link_targets() {
+        let mut doc = PdfDocument::open("tests/examples/sample.pdf").unwrap();
+        let second = doc.page_refs().unwrap()[1];
+        let mut file = Dictionary::new();
+        file.insert(Name::from("F"), Primitives::Str(b"other.pdf".to_vec()));
+        let annots = vec![
+            link(&mut doc, "Dest", Primitives::Array(vec![second.into(), Primitives::name(b"Fit".to_vec())])),
+            link(&mut doc, "A", action(vec![
+                ("S", Primitives::name(b"GoTo".to_vec())),
+                ("D", Primitives::Str(b"missing".to_vec())),
+            ])),
+            link(&mut doc, "A", action(vec![
+                ("S", Primitives::name(b"GoToR".to_vec())),
+                ("F", Primitives::Dict(file)),
+                ("D", Primitives::Array(vec![Primitives::Int(3), Primitives::name(b"FitH".to_vec()), Primitives::Int(500)])),
+                ("NewWindow", Primitives::Bool(true)),
+            ])),
+            link(&mut doc, "A", action(vec![
+                ("S", Primitives::name(b"Launch".to_vec())),
+                ("F", Primitives::Str(b"notes.txt".to_vec())),
+            ])),
+            link(&mut doc, "A", action(vec![
+                ("S", Primitives::name(b"URI".to_vec())),
+                ("URI", Primitives::Str(b"index.html".to_vec())),
+            ])),
+            link(&mut doc, "A", action(vec![
+                ("S", Primitives::name(b"Named".to_vec())),
+                ("N", Primitives::name(b"NextPage".to_vec())),
+            ])),
+        ];
+        let page = doc.page(0).unwrap();
+        let mut dict = page.dict;
+        dict.insert(Name::from("Annots"), Primitives::Array(annots));
+        doc.replace_object(page.reference, Primitives::Dict(dict)).unwrap();
+        let catalog_ref = doc.catalog_ref().unwrap();
+        let mut catalog = doc.catalog().unwrap();
+        catalog.insert(Name::from("URI"), action(vec![("Base", Primitives::Str(b"https://example.com/".to_vec()))]));
+        doc.replace_object(catalog_ref, Primitives::Dict(catalog)).unwrap();
+
+        let targets: Vec<LinkTarget> = doc.page_links(0).unwrap().into_iter().map(|l| l.target).collect();
+        assert_eq!(targets, [
+            LinkTarget::GoTo(Some(Destination { page: 1, view: View::Fit })),
+            LinkTarget::GoTo(None),
+            LinkTarget::GoToRemote {
+                file: "other.pdf".to_string(),
+                destination: Some(RemoteDestination::Page(3, View::FitH { top: Some(500.0) })),
+                new_window: true,
+            },
+            LinkTarget::Launch { file: "notes.txt".to_string(), new_window: false },
+            LinkTarget::Uri("https://example.com/index.html".to_string()),
+            LinkTarget::Other("Named".to_string()),
+        ]);
+    }
+}
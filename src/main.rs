@@ -0,0 +1,55 @@
+use std::env;
+use std::process;
+
+use anyhow::{bail, Context, Result};
+
+use pdfbat::{PdfDocument, SaveOptions};
+
+const USAGE: &str = "usage: pdfbat merge -o <output.pdf> <input.pdf>...";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Err(e) = run(&args) {
+        eprintln!("pdfbat: {:#}", e);
+        process::exit(1);
+    }
+}
+
+fn run(args: &[String]) -> Result<()> {
+    match args.first().map(|a| a.as_str()) {
+        Some("merge") => merge(&args[1..]),
+        Some("-h") | Some("--help") => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => bail!("{}", USAGE),
+    }
+}
+
+fn merge(args: &[String]) -> Result<()> {
+    let mut output = None;
+    let mut inputs = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => output = args.next(),
+            _ => inputs.push(arg),
+        }
+    }
+    let output = match output {
+        Some(output) if !inputs.is_empty() => output,
+        _ => bail!("{}", USAGE),
+    };
+
+    let mut docs = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        docs.push(PdfDocument::open(input).with_context(|| format!("can't read {}", input))?);
+    }
+    let merged = PdfDocument::merge(&docs.iter().collect::<Vec<_>>())?;
+    let options = SaveOptions {
+        object_streams: true,
+        ..SaveOptions::default()
+    };
+    merged.save(output, options).with_context(|| format!("can't write {}", output))?;
+    Ok(())
+}
@@ -0,0 +1,772 @@
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+use crate::document::PdfDocument;
+use crate::primitives::{Dictionary, Name, PdfStream, Primitives, Ref};
+use crate::serializer::serialize;
+use crate::names::named_dests;
+use crate::error::Error;
+
+// Copies objects from another document, giving each one a new number in the
+// destination. References are followed recursively; each source object is
+// copied once, and fonts and images with identical content are stored only
+// once, also across documents.
+pub struct Importer {
+    // Destination document and content hash of shareable objects to their
+    // copy in that document.
+    shared: HashMap<(u64, [u8; 16]), Ref>,
+}
+
+// Per source document state of an `Importer`.
+struct ImportMap<'a> {
+    src: &'a PdfDocument,
+    map: HashMap<Ref, Ref>,
+    // Content hashes of source objects, `None` for objects that are part of
+    // a cycle or refer to pages.
+    hashes: HashMap<Ref, Option<[u8; 16]>>,
+    // New names of named destinations that the destination document
+    // already uses.
+    renamed: HashMap<Vec<u8>, Vec<u8>>,
+}
+
+impl Default for Importer {
+    fn default() -> Self {
+        Importer::new()
+    }
+}
+
+impl Importer {
+    pub fn new() -> Self {
+        Importer {
+            shared: HashMap::new(),
+        }
+    }
+
+    // Appends all pages of `src` to the page tree of `dst` and merges its
+    // outline and named destinations.
+    pub fn append(&mut self, dst: &mut PdfDocument, src: &PdfDocument) -> Result<(), Error> {
+        let indexes: Vec<usize> = (0..src.page_count()?).collect();
+        self.append_pages(dst, src, &indexes)
+    }
+
+    // Like `append` for the pages at `indexes`, in that order. Outline items
+    // and named destinations that point to other pages are left out.
+    pub fn append_pages(&mut self, dst: &mut PdfDocument, src: &PdfDocument, indexes: &[usize]) -> Result<(), Error> {
+        let mut copy = ImportMap {
+            src: src,
+            map: HashMap::new(),
+            hashes: HashMap::new(),
+            renamed: rename_dests(dst, src)?,
+        };
+
+        // Pages are registered first so destinations and annotations that
+        // point at them are mapped instead of pulling in the source tree.
+        let mut pages = Vec::with_capacity(indexes.len());
+        let mut seen = HashSet::new();
+        for &i in indexes {
+            let page = src.page(i)?;
+            if !seen.insert(page.reference) {
+                eprintln!("append_pages - page {} is listed twice, skipping.", i);
+                continue;
+            }
+            pages.push(page);
+        }
+        let mut new_refs = Vec::with_capacity(pages.len());
+        for page in &pages {
+            let r = dst.add_object(Primitives::Null);
+            copy.map.insert(page.reference, r);
+            new_refs.push(r);
+        }
+
+        let root = dst
+            .catalog()?
+            .get(&b"Pages"[..])
+            .and_then(|p| p.get_ref())
+            .ok_or(Error::InvalidFile("catalog has no /Pages"))?;
+        for (page, &r) in pages.iter().zip(new_refs.iter()) {
+            // Inherited attributes are made explicit, the page gets a new
+            // parent in the destination.
+            let mut dict = page.dict.clone();
+            for key in ["Resources", "MediaBox", "CropBox", "Rotate"].iter() {
+                if let Some(value) = page.get(key) {
+                    dict.entry(Name::from(key)).or_insert_with(|| value.clone());
+                }
+            }
+            dict.remove(&b"Parent"[..]);
+            let mut dict = match self.copy_value(dst, &mut copy, &Primitives::Dict(dict))? {
+                Primitives::Dict(dict) => dict,
+                _ => unreachable!(),
+            };
+            dict.insert(Name::from("Parent"), root.into());
+            dst.replace_object(r, Primitives::Dict(dict))?;
+        }
+
+        let mut tree = dst.fetch(root)?.get_dict().cloned().unwrap_or_default();
+        let mut kids = match tree.get(&b"Kids"[..]) {
+            Some(kids) => dst.resolve(kids)?.get_array().cloned().unwrap_or_default(),
+            None => Vec::new(),
+        };
+        kids.extend(new_refs.iter().map(|&r| Primitives::from(r)));
+        let count = tree.get(&b"Count"[..]).and_then(|c| c.get_integer()).unwrap_or(0);
+        tree.insert(Name::from("Kids"), Primitives::Array(kids));
+        tree.insert(Name::from("Count"), Primitives::Int(count + new_refs.len() as i64));
+        dst.replace_object(root, Primitives::Dict(tree))?;
+
+        self.merge_outlines(dst, &mut copy)?;
+        self.merge_dests(dst, &mut copy)?;
+        Ok(())
+    }
+
+    fn copy_ref(&mut self, dst: &mut PdfDocument, copy: &mut ImportMap, r: Ref) -> Result<Primitives, Error> {
+        if let Some(&new) = copy.map.get(&r) {
+            return Ok(new.into());
+        }
+        let obj = copy.src.fetch(r)?;
+        let ty = obj.get("Type").and_then(|t| t.get_name()).unwrap_or(b"");
+        if obj == Primitives::Null || ty == b"Page" || ty == b"Pages" {
+            // Pages that weren't imported and the source page tree stay
+            // behind.
+            return Ok(Primitives::Null);
+        }
+
+        let hash = if is_shareable(&obj) { content_hash(copy, r)? } else { None };
+        if let Some(&new) = hash.and_then(|hash| self.shared.get(&(dst.id, hash))) {
+            copy.map.insert(r, new);
+            return Ok(new.into());
+        }
+
+        // Objects may be part of cycles, so they are numbered before their
+        // children are copied.
+        let new = dst.add_object(Primitives::Null);
+        copy.map.insert(r, new);
+        if let Some(hash) = hash {
+            self.shared.insert((dst.id, hash), new);
+        }
+        let copied = self.copy_value(dst, copy, &obj)?;
+        dst.replace_object(new, copied)?;
+        Ok(new.into())
+    }
+
+    fn copy_value(&mut self, dst: &mut PdfDocument, copy: &mut ImportMap, obj: &Primitives) -> Result<Primitives, Error> {
+        Ok(match obj {
+            Primitives::Ref(num, gen) => self.copy_ref(dst, copy, Ref::new(*num, *gen))?,
+            Primitives::Array(items) => {
+                let mut copied = Vec::with_capacity(items.len());
+                for item in items {
+                    copied.push(self.copy_value(dst, copy, item)?);
+                }
+                Primitives::Array(copied)
+            }
+            Primitives::Dict(dict) => Primitives::Dict(self.copy_dict(dst, copy, dict)?),
+            Primitives::Stream(stream) => Primitives::Stream(PdfStream {
+                dict: self.copy_dict(dst, copy, &stream.dict)?,
+                data: stream.data.clone(),
+            }),
+            _ => obj.clone(),
+        })
+    }
+
+    fn copy_dict(&mut self, dst: &mut PdfDocument, copy: &mut ImportMap, dict: &Dictionary) -> Result<Dictionary, Error> {
+        let mut copied = Dictionary::new();
+        for (key, value) in dict {
+            let value = match renamed_dest(copy, dict, key, value) {
+                Some(renamed) => renamed,
+                None => self.copy_value(dst, copy, value)?,
+            };
+            copied.insert(key.clone(), value);
+        }
+        Ok(copied)
+    }
+
+    // Appends the top-level items of the source outline to the destination
+    // outline, creating it if needed.
+    fn merge_outlines(&mut self, dst: &mut PdfDocument, copy: &mut ImportMap) -> Result<(), Error> {
+        let src_root = match copy.src.get(&copy.src.catalog()?, "Outlines")? {
+            Primitives::Dict(root) => root,
+            _ => return Ok(()),
+        };
+        let dests: HashMap<Vec<u8>, Primitives> = named_dests(copy.src)?.into_iter().collect();
+        let mut visited = HashSet::new();
+        let items = self.copy_outline_items(dst, copy, src_root.get(&b"First"[..]), &dests, &mut visited)?;
+        if items.is_empty() {
+            return Ok(());
+        }
+
+        let catalog_ref = dst.catalog_ref()?;
+        let mut catalog = dst.catalog()?;
+        let root_ref = match catalog.get(&b"Outlines"[..]).and_then(|o| o.get_ref()) {
+            Some(r) => r,
+            None => {
+                let mut root = Dictionary::new();
+                root.insert(Name::from("Type"), Primitives::name(b"Outlines".to_vec()));
+                let r = dst.add_object(Primitives::Dict(root));
+                catalog.insert(Name::from("Outlines"), r.into());
+                dst.replace_object(catalog_ref, Primitives::Dict(catalog))?;
+                r
+            }
+        };
+        let mut root = dst.fetch(root_ref)?.get_dict().cloned().unwrap_or_default();
+        let count = root.get(&b"Count"[..]).and_then(|c| c.get_integer()).unwrap_or(0).abs();
+        let prev_last = root.get(&b"Last"[..]).and_then(|l| l.get_ref());
+        let (first, last, visible) = link_items(dst, root_ref, &items, prev_last)?;
+        if prev_last.is_none() {
+            root.insert(Name::from("First"), first.into());
+        }
+        root.insert(Name::from("Last"), last.into());
+        root.insert(Name::from("Count"), Primitives::Int(count + visible));
+        dst.replace_object(root_ref, Primitives::Dict(root))
+    }
+
+    // Copies the outline items in the sibling chain starting at `first`
+    // with their children. Returns each copy with its number of visible
+    // descendants; they still have to be linked to a parent.
+    fn copy_outline_items(
+        &mut self,
+        dst: &mut PdfDocument,
+        copy: &mut ImportMap,
+        first: Option<&Primitives>,
+        dests: &HashMap<Vec<u8>, Primitives>,
+        visited: &mut HashSet<Ref>,
+    ) -> Result<Vec<(Ref, i64)>, Error> {
+        let mut items = Vec::new();
+        let mut next = first.and_then(|f| f.get_ref());
+        while let Some(r) = next {
+            if !visited.insert(r) {
+                eprintln!("copy_outline_items - outline contains a cycle at {:?}.", r);
+                break;
+            }
+            let item = match copy.src.fetch(r)? {
+                Primitives::Dict(item) => item,
+                _ => break,
+            };
+            next = item.get(&b"Next"[..]).and_then(|n| n.get_ref());
+
+            let children = self.copy_outline_items(dst, copy, item.get(&b"First"[..]), dests, visited)?;
+            let removed = targets_removed_page(copy, &item, dests)?;
+            if removed && children.is_empty() {
+                continue;
+            }
+
+            let mut dict = Dictionary::new();
+            for (key, value) in &item {
+                let skip = [&b"Parent"[..], b"First", b"Last", b"Next", b"Prev", b"Count"].contains(&&key.0[..])
+                    || (removed && (key.0 == b"Dest" || key.0 == b"A"));
+                if !skip {
+                    let value = match renamed_dest(copy, &item, key, value) {
+                        Some(renamed) => renamed,
+                        None => self.copy_value(dst, copy, value)?,
+                    };
+                    dict.insert(key.clone(), value);
+                }
+            }
+            let new = dst.add_object(Primitives::Null);
+            let open = item.get(&b"Count"[..]).and_then(|c| c.get_integer()).unwrap_or(0) > 0;
+            let mut visible = 0;
+            if !children.is_empty() {
+                let (first, last, descendants) = link_items(dst, new, &children, None)?;
+                dict.insert(Name::from("First"), first.into());
+                dict.insert(Name::from("Last"), last.into());
+                // Closed items store the negated count of the descendants
+                // that would show when opened.
+                let count = if open { descendants } else { -descendants };
+                dict.insert(Name::from("Count"), Primitives::Int(count));
+                visible = if open { descendants } else { 0 };
+            }
+            dst.replace_object(new, Primitives::Dict(dict))?;
+            items.push((new, visible));
+        }
+        Ok(items)
+    }
+
+    // Copies the named destinations of the source into the `/Dests` name
+    // tree of the destination. Names that are already taken get the new
+    // name picked by `rename_dests`.
+    fn merge_dests(&mut self, dst: &mut PdfDocument, copy: &mut ImportMap) -> Result<(), Error> {
+        let src_dests = named_dests(copy.src)?;
+        if src_dests.is_empty() {
+            return Ok(());
+        }
+        let mut dests = named_dests(dst)?;
+        let lookup: HashMap<Vec<u8>, Primitives> = src_dests.iter().cloned().collect();
+        for (name, dest) in src_dests {
+            if points_to_removed_page(copy, &dest, &lookup)? {
+                continue;
+            }
+            let name = copy.renamed.get(&name).cloned().unwrap_or(name);
+            dests.push((name, self.copy_value(dst, copy, &dest)?));
+        }
+        dests.sort_by(|a, b| a.0.cmp(&b.0));
+
+        // Both forms are replaced by a single flat name tree.
+        let mut names = Vec::with_capacity(dests.len() * 2);
+        for (name, dest) in dests {
+            names.push(Primitives::Str(name));
+            names.push(dest);
+        }
+        let mut tree = Dictionary::new();
+        tree.insert(Name::from("Names"), Primitives::Array(names));
+        let tree = dst.add_object(Primitives::Dict(tree));
+
+        let catalog_ref = dst.catalog_ref()?;
+        let mut catalog = dst.catalog()?;
+        let mut name_dict = dst.get(&catalog, "Names")?.get_dict().cloned().unwrap_or_default();
+        name_dict.insert(Name::from("Dests"), tree.into());
+        catalog.insert(Name::from("Names"), Primitives::Dict(name_dict));
+        catalog.remove(&b"Dests"[..]);
+        dst.replace_object(catalog_ref, Primitives::Dict(catalog))
+    }
+}
+
+impl PdfDocument {
+    // A new document with the pages in `ranges` (zero-based, end exclusive)
+    // in the given order. Only objects these pages use are copied, outline
+    // items and named destinations for other pages are dropped.
+    pub fn extract_pages(&self, ranges: &[Range<usize>]) -> Result<PdfDocument, Error> {
+        let count = self.page_count()?;
+        if ranges.iter().any(|range| range.end > count) {
+            return Err(Error::InvalidArgument("page index out of range"));
+        }
+        let indexes: Vec<usize> = ranges.iter().flat_map(|range| range.clone()).collect();
+        let mut doc = PdfDocument::new();
+        Importer::new().append_pages(&mut doc, self, &indexes)?;
+        Ok(doc)
+    }
+
+    // Concatenates the pages of `docs` into a new document.
+    pub fn merge(docs: &[&PdfDocument]) -> Result<PdfDocument, Error> {
+        let mut merged = PdfDocument::new();
+        let mut importer = Importer::new();
+        for doc in docs {
+            importer.append(&mut merged, doc)?;
+        }
+        Ok(merged)
+    }
+}
+
+// Sets parent and sibling links of `items`, appending them after `prev` if
+// given. Returns the first and last item and the number of visible items
+// including open descendants.
+fn link_items(dst: &mut PdfDocument, parent: Ref, items: &[(Ref, i64)], prev: Option<Ref>) -> Result<(Ref, Ref, i64), Error> {
+    let refs: Vec<Ref> = prev.into_iter().chain(items.iter().map(|&(r, _)| r)).collect();
+    for (i, &r) in refs.iter().enumerate() {
+        let mut dict = dst.fetch(r)?.get_dict().cloned().unwrap_or_default();
+        if prev.is_none() || i > 0 {
+            dict.insert(Name::from("Parent"), parent.into());
+        }
+        if i > 0 {
+            dict.insert(Name::from("Prev"), refs[i - 1].into());
+        }
+        if let Some(&next) = refs.get(i + 1) {
+            dict.insert(Name::from("Next"), next.into());
+        }
+        dst.replace_object(r, Primitives::Dict(dict))?;
+    }
+    let visible = items.iter().map(|&(_, visible)| 1 + visible).sum();
+    Ok((items[0].0, items[items.len() - 1].0, visible))
+}
+
+// Names of the source's named destinations that the destination already
+// uses, each mapped to the name with the first free suffix, e.g. "intro-1".
+fn rename_dests(dst: &PdfDocument, src: &PdfDocument) -> Result<HashMap<Vec<u8>, Vec<u8>>, Error> {
+    let existing: HashSet<Vec<u8>> = named_dests(dst)?.into_iter().map(|(name, _)| name).collect();
+    let mut renamed = HashMap::new();
+    if existing.is_empty() {
+        return Ok(renamed);
+    }
+    let src_names: Vec<Vec<u8>> = named_dests(src)?.into_iter().map(|(name, _)| name).collect();
+    let mut taken: HashSet<Vec<u8>> = existing.iter().chain(src_names.iter()).cloned().collect();
+    for name in src_names {
+        if !existing.contains(&name) || renamed.contains_key(&name) {
+            continue;
+        }
+        let new = (1..)
+            .map(|i| [&name[..], format!("-{}", i).as_bytes()].concat())
+            .find(|new| !taken.contains(new))
+            .unwrap();
+        taken.insert(new.clone());
+        renamed.insert(name, new);
+    }
+    Ok(renamed)
+}
+
+// The new name for `value` if it is the `/Dest` of an annotation or outline
+// item, or the `/D` of a GoTo action, and refers to a renamed destination.
+fn renamed_dest(copy: &ImportMap, dict: &Dictionary, key: &Name, value: &Primitives) -> Option<Primitives> {
+    let goto = dict.get(&b"S"[..]).and_then(|s| s.get_name()) == Some(b"GoTo");
+    if key.0 != b"Dest" && !(key.0 == b"D" && goto) {
+        return None;
+    }
+    let name = match value {
+        Primitives::Name(name) => &name.0,
+        Primitives::Str(name) => name,
+        _ => return None,
+    };
+    copy.renamed.get(name).map(|new| Primitives::Str(new.clone()))
+}
+
+// Whether the outline item jumps to a page that isn't being imported.
+fn targets_removed_page(copy: &ImportMap, item: &Dictionary, dests: &HashMap<Vec<u8>, Primitives>) -> Result<bool, Error> {
+    if let Some(dest) = item.get(&b"Dest"[..]) {
+        return points_to_removed_page(copy, dest, dests);
+    }
+    if let Some(action) = item.get(&b"A"[..]) {
+        let action = copy.src.resolve(action)?;
+        if action.get("S").and_then(|s| s.get_name()) == Some(b"GoTo") {
+            if let Some(dest) = action.get("D") {
+                return points_to_removed_page(copy, dest, dests);
+            }
+        }
+    }
+    Ok(false)
+}
+
+// Whether `dest` (explicit, named, or a dictionary with `/D`) refers to a
+// page of the source that isn't being imported.
+fn points_to_removed_page(copy: &ImportMap, dest: &Primitives, dests: &HashMap<Vec<u8>, Primitives>) -> Result<bool, Error> {
+    let mut dest = copy.src.resolve(dest)?;
+    // Named destinations may map to another name only through a dictionary,
+    // so two lookups are enough.
+    for _ in 0..2 {
+        let name = match &dest {
+            Primitives::Name(name) => Some(name.0.clone()),
+            other => other.get_string_bytes(),
+        };
+        if let Some(name) = name {
+            dest = match dests.get(&name) {
+                Some(dest) => copy.src.resolve(dest)?,
+                None => return Ok(false),
+            };
+        }
+        if let Some(d) = dest.get("D") {
+            dest = copy.src.resolve(d)?;
+        }
+    }
+    let page = match dest.get_array().and_then(|d| d.first()).and_then(|p| p.get_ref()) {
+        Some(page) => page,
+        None => return Ok(false),
+    };
+    if copy.map.contains_key(&page) {
+        return Ok(false);
+    }
+    Ok(copy.src.fetch(page)?.get("Type").and_then(|t| t.get_name()) == Some(b"Page"))
+}
+
+// Fonts, font descriptors and all streams (font programs, images, forms) are
+// shared when identical; they are immutable and make up most of the size.
+fn is_shareable(obj: &Primitives) -> bool {
+    match obj {
+        Primitives::Stream(_) => true,
+        Primitives::Dict(dict) => matches!(
+            dict.get(&b"Type"[..]).and_then(|t| t.get_name()),
+            Some(b"Font") | Some(b"FontDescriptor")
+        ),
+        _ => false,
+    }
+}
+
+// Hash of the object's serialized form with every reference replaced by the
+// hash of its target, so equal content in different files or under
+// different numbers hashes the same.
+fn content_hash(copy: &mut ImportMap, r: Ref) -> Result<Option<[u8; 16]>, Error> {
+    if let Some(hash) = copy.hashes.get(&r) {
+        return Ok(*hash);
+    }
+    // Marks the object as in progress, a cycle back to it gives `None`.
+    copy.hashes.insert(r, None);
+    let obj = copy.src.fetch(r)?;
+    let ty = obj.get("Type").and_then(|t| t.get_name()).unwrap_or(b"");
+    let hash = if ty == b"Page" || ty == b"Pages" {
+        None
+    } else {
+        hash_refs(copy, &obj)?.map(|obj| md5::compute(serialize(&obj)).0)
+    };
+    copy.hashes.insert(r, hash);
+    Ok(hash)
+}
+
+fn hash_refs(copy: &mut ImportMap, obj: &Primitives) -> Result<Option<Primitives>, Error> {
+    Ok(match obj {
+        Primitives::Ref(num, gen) => content_hash(copy, Ref::new(*num, *gen))?
+            .map(|hash| Primitives::HexStr(hash.iter().map(|&b| b as u16).collect())),
+        Primitives::Array(items) => {
+            let mut hashed = Vec::with_capacity(items.len());
+            for item in items {
+                match hash_refs(copy, item)? {
+                    Some(item) => hashed.push(item),
+                    None => return Ok(None),
+                }
+            }
+            Some(Primitives::Array(hashed))
+        }
+        Primitives::Dict(dict) => hash_dict_refs(copy, dict)?.map(Primitives::Dict),
+        Primitives::Stream(stream) => hash_dict_refs(copy, &stream.dict)?.map(|dict| Primitives::Stream(PdfStream {
+            dict: dict,
+            data: stream.data.clone(),
+        })),
+        _ => Some(obj.clone()),
+    })
+}
+
+fn hash_dict_refs(copy: &mut ImportMap, dict: &Dictionary) -> Result<Option<Dictionary>, Error> {
+    let mut hashed = Dictionary::new();
+    for (key, value) in dict {
+        match hash_refs(copy, value)? {
+            Some(value) => hashed.insert(key.clone(), value),
+            None => return Ok(None),
+        };
+    }
+    Ok(Some(hashed))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::PdfDocument;
+    use crate::primitives::{Dictionary, Name, Primitives, Ref};
+    use crate::writer::SaveOptions;
+    use crate::names::named_dests;
+    use crate::error::Error;
+    use super::Importer;
+
+    fn reload(doc: &PdfDocument) -> PdfDocument {
+        let mut data = Vec::new();
+        doc.write_to(&mut data, &SaveOptions::default()).unwrap();
+        PdfDocument::load_data(data).unwrap()
+    }
+
+    #[test]
+    fn merge_documents() {
+        let paths = [
+            "tests/examples/sample.pdf",
+            "tests/examples/dummy.pdf",
+            "tests/examples/PDF_sample.pdf",
+        ];
+        let docs: Vec<PdfDocument> = paths.iter().map(|p| PdfDocument::open(p).unwrap()).collect();
+        let merged = PdfDocument::merge(&docs.iter().collect::<Vec<_>>()).unwrap();
+        let merged = reload(&merged);
+
+        let expected: Vec<(Vec<u8>, [f64; 4])> = docs
+            .iter()
+            .flat_map(|doc| doc.pages().unwrap().into_iter().map(move |p| (p.contents(doc).unwrap(), p.media_box(doc).unwrap())))
+            .collect();
+        let actual: Vec<(Vec<u8>, [f64; 4])> = merged
+            .pages()
+            .unwrap()
+            .iter()
+            .map(|p| (p.contents(&merged).unwrap(), p.media_box(&merged).unwrap()))
+            .collect();
+        assert_eq!(actual, expected);
+
+        // sample.pdf has an outline, its items now hang off the new root.
+        let outlines = merged.get(&merged.catalog().unwrap(), "Outlines").unwrap();
+        let first = merged.get(outlines.get_dict().unwrap(), "First").unwrap();
+        assert!(first.get("Title").is_some());
+        assert_eq!(first.get("Parent"), merged.catalog().unwrap().get(&b"Outlines"[..]));
+    }
+
+    #[test]
+    fn share_identical_fonts_and_images() {
+        let doc = PdfDocument::open("tests/examples/PDF_sample.pdf").unwrap();
+        let once = reload(&PdfDocument::merge(&[&doc]).unwrap());
+        let twice = reload(&PdfDocument::merge(&[&doc, &doc]).unwrap());
+        assert_eq!(twice.page_count().unwrap(), 2);
+
+        let resources = |doc: &PdfDocument, i| doc.page(i).unwrap().resources(doc).unwrap();
+        assert_eq!(resources(&twice, 0), resources(&twice, 1));
+        // Only the page, its content and whatever isn't shareable is copied
+        // a second time.
+        let count = |doc: &PdfDocument| (1..doc.xref.size()).filter(|&n| doc.fetch(Ref::new(n, 0)).unwrap() != Primitives::Null).count();
+        let xobjects = resources(&twice, 0).get(&b"XObject"[..]).cloned();
+        assert!(xobjects.is_some());
+        assert!(count(&twice) < 2 * count(&once));
+    }
+
+    #[test]
+    fn append_into_several_documents() {
+        let src = PdfDocument::open("tests/examples/PDF_sample.pdf").unwrap();
+        let mut importer = Importer::new();
+        let mut first = PdfDocument::new();
+        let mut second = PdfDocument::new();
+        importer.append(&mut first, &src).unwrap();
+        importer.append(&mut second, &src).unwrap();
+
+        // The second copy can't share the fonts and images of the first.
+        for doc in [reload(&first), reload(&second)].iter() {
+            let resources = doc.page(0).unwrap().resources(doc).unwrap();
+            let xobjects = doc.get(&resources, "XObject").unwrap();
+            let image = doc.get(xobjects.get_dict().unwrap(), "Image14").unwrap();
+            assert_eq!(image.get_stream().unwrap().get("Width"), Some(&Primitives::Int(200)));
+        }
+    }
+
+    fn item(title: &str, dest: Primitives) -> Primitives {
+        let mut dict = Dictionary::new();
+        dict.insert(Name::from("Title"), Primitives::Str(title.as_bytes().to_vec()));
+        dict.insert(Name::from("Dest"), dest);
+        Primitives::Dict(dict)
+    }
+
+    fn set(doc: &mut PdfDocument, r: Ref, key: &str, value: Primitives) {
+        let mut dict = doc.fetch(r).unwrap().get_dict().cloned().unwrap();
+        dict.insert(Name::from(key), value);
+        doc.replace_object(r, Primitives::Dict(dict)).unwrap();
+    }
+
+    // sample.pdf with outline items and named destinations for both pages.
+    fn document_with_outline() -> PdfDocument {
+        let mut doc = PdfDocument::open("tests/examples/sample.pdf").unwrap();
+        let pages = doc.page_refs().unwrap();
+        let fit = |page: Ref| Primitives::Array(vec![page.into(), Primitives::name(b"Fit".to_vec())]);
+
+        let one = doc.add_object(item("One", fit(pages[0])));
+        let two = doc.add_object(item("Two", Primitives::Str(b"second".to_vec())));
+        let child = doc.add_object(item("Child", fit(pages[0])));
+        set(&mut doc, one, "Next", two.into());
+        set(&mut doc, two, "Prev", one.into());
+        set(&mut doc, two, "First", child.into());
+        set(&mut doc, two, "Last", child.into());
+        set(&mut doc, two, "Count", Primitives::Int(1));
+
+        let mut root = Dictionary::new();
+        root.insert(Name::from("First"), one.into());
+        root.insert(Name::from("Last"), two.into());
+        root.insert(Name::from("Count"), Primitives::Int(3));
+        let root = doc.add_object(Primitives::Dict(root));
+
+        let mut tree = Dictionary::new();
+        tree.insert(Name::from("Names"), Primitives::Array(vec![
+            Primitives::Str(b"first".to_vec()),
+            fit(pages[0]),
+            Primitives::Str(b"second".to_vec()),
+            fit(pages[1]),
+        ]));
+        let mut names = Dictionary::new();
+        names.insert(Name::from("Dests"), Primitives::Dict(tree));
+
+        let catalog = doc.catalog_ref().unwrap();
+        set(&mut doc, catalog, "Outlines", root.into());
+        set(&mut doc, catalog, "Names", Primitives::Dict(names));
+        doc
+    }
+
+    fn outline_titles(doc: &PdfDocument, first: Option<&Primitives>) -> Vec<String> {
+        let mut titles = Vec::new();
+        let mut next = first.cloned();
+        while let Some(item) = next {
+            let item = doc.resolve(&item).unwrap();
+            let title = String::from_utf8(item.get("Title").unwrap().get_str().unwrap().to_vec()).unwrap();
+            let children = outline_titles(doc, item.get("First"));
+            titles.push(if children.is_empty() { title } else { format!("{} {:?}", title, children) });
+            next = item.get("Next").cloned();
+        }
+        titles
+    }
+
+    #[test]
+    fn copy_closed_outline_items() {
+        let mut doc = PdfDocument::open("tests/examples/sample.pdf").unwrap();
+        let page = doc.page_refs().unwrap()[0];
+        let fit = Primitives::Array(vec![page.into(), Primitives::name(b"Fit".to_vec())]);
+        let closed = doc.add_object(item("Closed", fit.clone()));
+        let open = doc.add_object(item("Open", fit.clone()));
+        let leaf = doc.add_object(item("Leaf", fit));
+        for (parent, child, count) in [(closed, open, -2), (open, leaf, 1)] {
+            set(&mut doc, parent, "First", child.into());
+            set(&mut doc, parent, "Last", child.into());
+            set(&mut doc, parent, "Count", Primitives::Int(count));
+        }
+        let mut root = Dictionary::new();
+        root.insert(Name::from("First"), closed.into());
+        root.insert(Name::from("Last"), closed.into());
+        root.insert(Name::from("Count"), Primitives::Int(1));
+        let root = doc.add_object(Primitives::Dict(root));
+        let catalog = doc.catalog_ref().unwrap();
+        set(&mut doc, catalog, "Outlines", root.into());
+
+        let merged = reload(&PdfDocument::merge(&[&doc]).unwrap());
+        let root = merged.get(&merged.catalog().unwrap(), "Outlines").unwrap();
+        assert_eq!(root.get("Count"), Some(&Primitives::Int(1)));
+        // Opening the item would show its child and the child's child.
+        let closed = merged.get(root.get_dict().unwrap(), "First").unwrap();
+        assert_eq!(closed.get("Count"), Some(&Primitives::Int(-2)));
+        let open = merged.get(closed.get_dict().unwrap(), "First").unwrap();
+        assert_eq!(open.get("Count"), Some(&Primitives::Int(1)));
+    }
+
+    #[test]
+    fn rename_shared_destination_names() {
+        let mut doc = document_with_outline();
+        let mut action = Dictionary::new();
+        action.insert(Name::from("S"), Primitives::name(b"GoTo".to_vec()));
+        action.insert(Name::from("D"), Primitives::Str(b"first".to_vec()));
+        let mut link = Dictionary::new();
+        link.insert(Name::from("Subtype"), Primitives::name(b"Link".to_vec()));
+        link.insert(Name::from("A"), Primitives::Dict(action));
+        let link = doc.add_object(Primitives::Dict(link));
+        let page = doc.page_refs().unwrap()[0];
+        set(&mut doc, page, "Annots", Primitives::Array(vec![link.into()]));
+
+        let merged = reload(&PdfDocument::merge(&[&doc, &doc]).unwrap());
+        let pages = merged.page_refs().unwrap();
+        let dests: Vec<(String, Option<Ref>)> = named_dests(&merged)
+            .unwrap()
+            .into_iter()
+            .map(|(name, dest)| (String::from_utf8(name).unwrap(), merged.resolve(&dest).unwrap().get_array().and_then(|d| d[0].get_ref())))
+            .collect();
+        assert_eq!(dests, [
+            ("first".to_string(), Some(pages[0])),
+            ("first-1".to_string(), Some(pages[2])),
+            ("second".to_string(), Some(pages[1])),
+            ("second-1".to_string(), Some(pages[3])),
+        ]);
+
+        // Outline items and links of the second copy use the new names.
+        let root = merged.get(&merged.catalog().unwrap(), "Outlines").unwrap();
+        let last = merged.get(root.get_dict().unwrap(), "Last").unwrap();
+        assert_eq!(last.get("Dest"), Some(&Primitives::Str(b"second-1".to_vec())));
+        let target = |i: usize| {
+            let annots = merged.get(&merged.page(i).unwrap().dict, "Annots").unwrap();
+            let link = merged.resolve(&annots.get_array().unwrap()[0]).unwrap();
+            merged.get(link.get_dict().unwrap(), "A").unwrap().get("D").cloned()
+        };
+        assert_eq!(target(0), Some(Primitives::Str(b"first".to_vec())));
+        assert_eq!(target(2), Some(Primitives::Str(b"first-1".to_vec())));
+    }
+
+    #[test]
+    fn extract_page_ranges() {
+        let doc = document_with_outline();
+        let outline = |doc: &PdfDocument| {
+            let root = doc.get(&doc.catalog().unwrap(), "Outlines").unwrap();
+            outline_titles(doc, root.get("First"))
+        };
+        let dest_names = |doc: &PdfDocument| {
+            let names = doc.get(&doc.catalog().unwrap(), "Names").unwrap();
+            let tree = doc.resolve(names.get("Dests").unwrap()).unwrap();
+            tree.get("Names").unwrap().get_array().unwrap().iter().step_by(2).map(|n| n.get_str().unwrap().to_vec()).collect::<Vec<_>>()
+        };
+        assert_eq!(outline(&doc), ["One", "Two [\"Child\"]"]);
+
+        // "Two" points to the second page through a name but keeps its child.
+        let first = reload(&doc.extract_pages(&[0..1]).unwrap());
+        assert_eq!(first.page_count().unwrap(), 1);
+        assert_eq!(first.page(0).unwrap().contents(&first).unwrap(), doc.page(0).unwrap().contents(&doc).unwrap());
+        assert_eq!(outline(&first), ["One", "Two [\"Child\"]"]);
+        let two = first.get(&first.get(&first.catalog().unwrap(), "Outlines").unwrap().get_dict().unwrap().clone(), "Last").unwrap();
+        assert!(two.get("Dest").is_none());
+        assert_eq!(dest_names(&first), [b"first".to_vec()]);
+
+        let second = reload(&doc.extract_pages(&[1..2]).unwrap());
+        assert_eq!(second.page(0).unwrap().contents(&second).unwrap(), doc.page(1).unwrap().contents(&doc).unwrap());
+        assert_eq!(outline(&second), ["Two"]);
+        assert_eq!(dest_names(&second), [b"second".to_vec()]);
+
+        // Reordered, and the source page tree isn't copied along.
+        let both = reload(&doc.extract_pages(&[1..2, 0..1]).unwrap());
+        assert_eq!(both.page(1).unwrap().contents(&both).unwrap(), doc.page(0).unwrap().contents(&doc).unwrap());
+        let trees = (1..both.xref.size())
+            .filter(|&n| both.fetch(Ref::new(n, 0)).unwrap().get("Type").and_then(|t| t.get_name()) == Some(b"Pages"))
+            .count();
+        assert_eq!(trees, 1);
+        assert!(matches!(doc.extract_pages(&[1..3]), Err(Error::InvalidArgument(_))));
+    }
+}
@@ -0,0 +1,483 @@
+use crate::document::PdfDocument;
+use crate::primitives::{Dictionary, Name, PdfStream, Primitives};
+use crate::date::PdfDate;
+use crate::utils::encode_text;
+use crate::xml::{self, XmlNode};
+use crate::error::Error;
+
+// The standard entries of the document information dictionary.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DocumentInfo {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub keywords: Option<String>,
+    // The application that created the original document.
+    pub creator: Option<String>,
+    // The application that converted it to PDF.
+    pub producer: Option<String>,
+    pub creation_date: Option<PdfDate>,
+    pub modification_date: Option<PdfDate>,
+}
+
+// Namespaces of the properties the info dictionary maps to, and other common
+// ones.
+const NAMESPACES: [(&str, &str); 8] = [
+    ("rdf", "http://www.w3.org/1999/02/22-rdf-syntax-ns#"),
+    ("dc", "http://purl.org/dc/elements/1.1/"),
+    ("xmp", "http://ns.adobe.com/xap/1.0/"),
+    ("pdf", "http://ns.adobe.com/pdf/1.3/"),
+    ("xmpMM", "http://ns.adobe.com/xap/1.0/mm/"),
+    ("xmpRights", "http://ns.adobe.com/xap/1.0/rights/"),
+    ("pdfaid", "http://www.aiim.org/pdfa/ns/id/"),
+    ("photoshop", "http://ns.adobe.com/photoshop/1.0/"),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum XmpValue {
+    Text(String),
+    // Ordered array, e.g. `dc:creator`.
+    Seq(Vec<String>),
+    // Unordered array, e.g. `dc:subject`.
+    Bag(Vec<String>),
+    // Language alternatives, e.g. `dc:title`. The default language comes
+    // first.
+    Alt(Vec<String>),
+}
+
+impl XmpValue {
+    // The value as one string, array items joined by ", " except for
+    // alternatives where it is the default one.
+    pub fn text(&self) -> String {
+        match self {
+            XmpValue::Text(text) => text.clone(),
+            XmpValue::Seq(items) | XmpValue::Bag(items) => items.join(", "),
+            XmpValue::Alt(items) => items.first().cloned().unwrap_or_default(),
+        }
+    }
+}
+
+// An XMP metadata packet. Properties are looked up by namespace, so
+// "xmp:CreateDate" also finds a `xap:CreateDate` written with the old
+// prefix. Edits keep everything else in the packet as it is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct XmpMetadata {
+    root: XmlNode,
+}
+
+impl Default for XmpMetadata {
+    fn default() -> Self {
+        XmpMetadata::new()
+    }
+}
+
+impl XmpMetadata {
+    pub fn new() -> XmpMetadata {
+        let mut description = XmlNode::new("rdf:Description");
+        description.attributes.push(("rdf:about".to_string(), String::new()));
+        let mut rdf = XmlNode::new("rdf:RDF");
+        rdf.attributes.push(("xmlns:rdf".to_string(), NAMESPACES[0].1.to_string()));
+        rdf.children.push(description);
+        let mut root = XmlNode::new("x:xmpmeta");
+        root.attributes.push(("xmlns:x".to_string(), "adobe:ns:meta/".to_string()));
+        root.children.push(rdf);
+        XmpMetadata { root: root }
+    }
+
+    pub fn parse(data: &[u8]) -> Result<XmpMetadata, Error> {
+        let root = xml::parse(data)?;
+        if root.find("rdf:RDF").is_none() {
+            return Err(Error::InvalidXml("XMP packet has no rdf:RDF element"));
+        }
+        Ok(XmpMetadata { root: root })
+    }
+
+    // The packet with its `xpacket` wrapper, ready to be stored in a
+    // metadata stream.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = String::from("<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n");
+        self.root.write(&mut out);
+        out.push_str("\n<?xpacket end=\"w\"?>");
+        out.into_bytes()
+    }
+
+    // Looks up a property by its qualified name, e.g. "dc:title".
+    pub fn get(&self, name: &str) -> Option<XmpValue> {
+        let (uri, local) = split_name(name)?;
+        let namespaces = self.namespaces();
+        for description in self.descriptions() {
+            // Simple properties may also be written as attributes.
+            for (attr, value) in &description.attributes {
+                if matches_name(attr, uri, local, &namespaces) {
+                    return Some(XmpValue::Text(value.clone()));
+                }
+            }
+            for property in &description.children {
+                if matches_name(&property.name, uri, local, &namespaces) {
+                    return Some(read_value(property));
+                }
+            }
+        }
+        None
+    }
+
+    // Sets a property, replacing any previous value. Only prefixes of the
+    // common namespaces or ones declared in the packet can be used.
+    pub fn set(&mut self, name: &str, value: XmpValue) -> Result<(), Error> {
+        let (uri, _) = split_name(name).ok_or(Error::InvalidArgument("unknown XMP namespace prefix"))?;
+        self.remove(name);
+        let prefix = &name[..name.find(':').unwrap()];
+        let rdf = self.rdf_mut();
+        if rdf.child("rdf:Description").is_none() {
+            let mut description = XmlNode::new("rdf:Description");
+            description.attributes.push(("rdf:about".to_string(), String::new()));
+            rdf.children.push(description);
+        }
+        let description = rdf.children.iter_mut().find(|c| c.name == "rdf:Description").unwrap();
+        let declaration = format!("xmlns:{}", prefix);
+        if description.attribute(&declaration).is_none() {
+            description.attributes.push((declaration, uri.to_string()));
+        }
+
+        let mut property = XmlNode::new(name);
+        let (container, items) = match value {
+            XmpValue::Text(text) => {
+                property.text = text;
+                description.children.push(property);
+                return Ok(());
+            }
+            XmpValue::Seq(items) => ("rdf:Seq", items),
+            XmpValue::Bag(items) => ("rdf:Bag", items),
+            XmpValue::Alt(items) => ("rdf:Alt", items),
+        };
+        let mut array = XmlNode::new(container);
+        for (i, item) in items.into_iter().enumerate() {
+            let mut li = XmlNode::new("rdf:li");
+            if container == "rdf:Alt" && i == 0 {
+                li.attributes.push(("xml:lang".to_string(), "x-default".to_string()));
+            }
+            li.text = item;
+            array.children.push(li);
+        }
+        property.children.push(array);
+        description.children.push(property);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        let (uri, local) = match split_name(name) {
+            Some(name) => name,
+            None => return,
+        };
+        let namespaces = self.namespaces();
+        for description in self.rdf_mut().children.iter_mut().filter(|c| c.name == "rdf:Description") {
+            description.attributes.retain(|(attr, _)| !matches_name(attr, uri, local, &namespaces));
+            description.children.retain(|p| !matches_name(&p.name, uri, local, &namespaces));
+        }
+    }
+
+    pub fn title(&self) -> Option<String> {
+        self.get("dc:title").map(|v| v.text())
+    }
+
+    pub fn creators(&self) -> Vec<String> {
+        match self.get("dc:creator") {
+            Some(XmpValue::Seq(items)) | Some(XmpValue::Bag(items)) | Some(XmpValue::Alt(items)) => items,
+            Some(XmpValue::Text(text)) => vec![text],
+            None => Vec::new(),
+        }
+    }
+
+    pub fn description(&self) -> Option<String> {
+        self.get("dc:description").map(|v| v.text())
+    }
+
+    pub fn keywords(&self) -> Option<String> {
+        self.get("pdf:Keywords").map(|v| v.text())
+    }
+
+    pub fn creator_tool(&self) -> Option<String> {
+        self.get("xmp:CreatorTool").map(|v| v.text())
+    }
+
+    pub fn producer(&self) -> Option<String> {
+        self.get("pdf:Producer").map(|v| v.text())
+    }
+
+    pub fn create_date(&self) -> Option<PdfDate> {
+        self.get("xmp:CreateDate").and_then(|v| PdfDate::parse_xmp(&v.text()))
+    }
+
+    pub fn modify_date(&self) -> Option<PdfDate> {
+        self.get("xmp:ModifyDate").and_then(|v| PdfDate::parse_xmp(&v.text()))
+    }
+
+    pub fn metadata_date(&self) -> Option<PdfDate> {
+        self.get("xmp:MetadataDate").and_then(|v| PdfDate::parse_xmp(&v.text()))
+    }
+
+    // Makes the properties that mirror the info dictionary match `info`.
+    fn update(&mut self, info: &DocumentInfo) -> Result<(), Error> {
+        let text = |value: &Option<String>| value.clone().map(XmpValue::Text);
+        let date = |value: &Option<PdfDate>| value.map(|d| XmpValue::Text(d.to_xmp_string()));
+        let properties = [
+            ("dc:title", info.title.clone().map(|t| XmpValue::Alt(vec![t]))),
+            ("dc:creator", info.author.clone().map(|a| XmpValue::Seq(vec![a]))),
+            ("dc:description", info.subject.clone().map(|s| XmpValue::Alt(vec![s]))),
+            ("pdf:Keywords", text(&info.keywords)),
+            ("xmp:CreatorTool", text(&info.creator)),
+            ("pdf:Producer", text(&info.producer)),
+            ("xmp:CreateDate", date(&info.creation_date)),
+            ("xmp:ModifyDate", date(&info.modification_date)),
+            ("xmp:MetadataDate", date(&info.modification_date)),
+        ];
+        for (name, value) in properties.iter() {
+            match value {
+                Some(value) => self.set(name, value.clone())?,
+                None => self.remove(name),
+            }
+        }
+        Ok(())
+    }
+
+    fn rdf_mut(&mut self) -> &mut XmlNode {
+        fn find(node: &mut XmlNode) -> Option<&mut XmlNode> {
+            if node.name == "rdf:RDF" {
+                return Some(node);
+            }
+            node.children.iter_mut().find_map(find)
+        }
+        // `parse` and `new` make sure there is one.
+        find(&mut self.root).unwrap()
+    }
+
+    fn descriptions(&self) -> impl Iterator<Item = &XmlNode> {
+        self.root.find("rdf:RDF").into_iter().flat_map(|rdf| rdf.children_named("rdf:Description"))
+    }
+
+    // Every `xmlns:` declaration in the packet as (prefix, URI). Packets
+    // declare each prefix once in practice, so scoping is ignored.
+    fn namespaces(&self) -> Vec<(String, String)> {
+        fn collect(node: &XmlNode, namespaces: &mut Vec<(String, String)>) {
+            for (name, value) in &node.attributes {
+                if let Some(prefix) = name.strip_prefix("xmlns:") {
+                    namespaces.push((prefix.to_string(), value.clone()));
+                }
+            }
+            for child in &node.children {
+                collect(child, namespaces);
+            }
+        }
+        let mut namespaces = Vec::new();
+        collect(&self.root, &mut namespaces);
+        namespaces
+    }
+}
+
+// The namespace URI and local part of a qualified property name.
+fn split_name(name: &str) -> Option<(&'static str, &str)> {
+    let colon = name.find(':')?;
+    let uri = NAMESPACES.iter().find(|(prefix, _)| *prefix == &name[..colon])?.1;
+    Some((uri, &name[colon + 1..]))
+}
+
+fn matches_name(name: &str, uri: &str, local: &str, namespaces: &[(String, String)]) -> bool {
+    let colon = match name.find(':') {
+        Some(colon) => colon,
+        None => return false,
+    };
+    if &name[colon + 1..] != local {
+        return false;
+    }
+    let prefix = &name[..colon];
+    match namespaces.iter().find(|(p, _)| p == prefix) {
+        Some((_, declared)) => declared == uri,
+        None => NAMESPACES.iter().any(|(p, u)| *p == prefix && *u == uri),
+    }
+}
+
+fn read_value(property: &XmlNode) -> XmpValue {
+    let array = property.children.iter().find(|c| matches!(c.name.as_str(), "rdf:Seq" | "rdf:Bag" | "rdf:Alt"));
+    let array = match array {
+        Some(array) => array,
+        None => return XmpValue::Text(property.text.trim().to_string()),
+    };
+    let mut items: Vec<&XmlNode> = array.children_named("rdf:li").collect();
+    if array.name == "rdf:Alt" {
+        // Put the default language first.
+        items.sort_by_key(|li| li.attribute("xml:lang") != Some("x-default"));
+    }
+    let items = items.iter().map(|li| li.text.trim().to_string()).collect();
+    match array.name.as_str() {
+        "rdf:Seq" => XmpValue::Seq(items),
+        "rdf:Bag" => XmpValue::Bag(items),
+        _ => XmpValue::Alt(items),
+    }
+}
+
+impl PdfDocument {
+    // The entries of the trailer's /Info dictionary. Dates that can't be
+    // parsed are left out.
+    pub fn info(&self) -> Result<DocumentInfo, Error> {
+        let info = match self.resolve(self.trailer().get(&b"Info"[..]).unwrap_or(&Primitives::Null))? {
+            Primitives::Dict(info) => info,
+            _ => return Ok(DocumentInfo::default()),
+        };
+        let text = |key: &str| -> Result<Option<String>, Error> { Ok(self.get(&info, key)?.get_text()) };
+        let date = |key: &str| -> Result<Option<PdfDate>, Error> {
+            Ok(self.get(&info, key)?.get_text().and_then(|d| PdfDate::parse(&d)))
+        };
+        Ok(DocumentInfo {
+            title: text("Title")?,
+            author: text("Author")?,
+            subject: text("Subject")?,
+            keywords: text("Keywords")?,
+            creator: text("Creator")?,
+            producer: text("Producer")?,
+            creation_date: date("CreationDate")?,
+            modification_date: date("ModDate")?,
+        })
+    }
+
+    // The XMP packet of the catalog's /Metadata stream.
+    pub fn metadata(&self) -> Result<Option<XmpMetadata>, Error> {
+        match self.get(&self.catalog()?, "Metadata")? {
+            Primitives::Stream(stream) => Ok(Some(XmpMetadata::parse(&stream.decode()?)?)),
+            _ => Ok(None),
+        }
+    }
+
+    // Replaces the standard info entries and updates the matching XMP
+    // properties, creating the packet if needed, so both say the same.
+    // Other info entries and XMP properties are kept.
+    pub fn set_info(&mut self, info: &DocumentInfo) -> Result<(), Error> {
+        let info_ref = self.trailer().get(&b"Info"[..]).and_then(|r| r.get_ref());
+        // `/Info` may also be stored directly in the trailer.
+        let mut dict = match self.get(self.trailer(), "Info")? {
+            Primitives::Dict(dict) => dict,
+            _ => Dictionary::new(),
+        };
+        let entries = [
+            ("Title", info.title.as_ref().map(|t| encode_text(t))),
+            ("Author", info.author.as_ref().map(|a| encode_text(a))),
+            ("Subject", info.subject.as_ref().map(|s| encode_text(s))),
+            ("Keywords", info.keywords.as_ref().map(|k| encode_text(k))),
+            ("Creator", info.creator.as_ref().map(|c| encode_text(c))),
+            ("Producer", info.producer.as_ref().map(|p| encode_text(p))),
+            ("CreationDate", info.creation_date.map(|d| d.to_pdf_string().into_bytes())),
+            ("ModDate", info.modification_date.map(|d| d.to_pdf_string().into_bytes())),
+        ];
+        for (key, value) in entries.iter() {
+            match value {
+                Some(value) => dict.insert(Name::from(key), Primitives::Str(value.clone())),
+                None => dict.remove(key.as_bytes()),
+            };
+        }
+        match info_ref {
+            Some(r) => self.replace_object(r, Primitives::Dict(dict))?,
+            None => {
+                let r = self.add_object(Primitives::Dict(dict));
+                self.xref.set_trailer_entry("Info", Some(r.into()));
+            }
+        }
+
+        let mut metadata = self.metadata()?.unwrap_or_default();
+        metadata.update(info)?;
+        self.set_metadata(&metadata)
+    }
+
+    // Stores `metadata` as the catalog's /Metadata stream, uncompressed so
+    // that tools which don't parse PDF can still find it.
+    pub fn set_metadata(&mut self, metadata: &XmpMetadata) -> Result<(), Error> {
+        let mut dict = Dictionary::new();
+        dict.insert(Name::from("Type"), Primitives::name(b"Metadata".to_vec()));
+        dict.insert(Name::from("Subtype"), Primitives::name(b"XML".to_vec()));
+        let stream = Primitives::Stream(PdfStream::new(dict, metadata.to_bytes()));
+
+        let catalog_ref = self.catalog_ref()?;
+        let mut catalog = self.catalog()?;
+        match catalog.get(&b"Metadata"[..]).and_then(|r| r.get_ref()) {
+            Some(r) => self.replace_object(r, stream),
+            None => {
+                let r = self.add_object(stream);
+                catalog.insert(Name::from("Metadata"), r.into());
+                self.replace_object(catalog_ref, Primitives::Dict(catalog))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::PdfDocument;
+    use crate::date::PdfDate;
+    use crate::writer::SaveOptions;
+    use crate::primitives::{Dictionary, Name, Primitives};
+    use super::{DocumentInfo, XmpValue};
+
+    #[test]
+    fn read_info_and_metadata() {
+        let doc = PdfDocument::open("tests/examples/7a79c35f7ce0704dec63be82440c8182.pdf").unwrap();
+        let info = doc.info().unwrap();
+        assert_eq!(info.producer.as_deref(), Some("Adobe PDF Library 9.9"));
+        assert_eq!(info.creation_date, Some(PdfDate::new(2011, 10, 11, 16, 9, 56, Some(-420))));
+        assert_eq!(info.title, None);
+
+        let metadata = doc.metadata().unwrap().unwrap();
+        assert_eq!(metadata.creator_tool(), info.creator);
+        assert_eq!(metadata.create_date(), info.creation_date);
+        assert_eq!(metadata.modify_date(), info.modification_date);
+        assert_eq!(metadata.get("dc:subject"), Some(XmpValue::Bag(vec!["null".to_string()])));
+        assert_eq!(metadata.get("dc:format"), Some(XmpValue::Text("application/pdf".to_string())));
+    }
+
+    #[test]
+    fn update_info_and_metadata() {
+        let mut doc = PdfDocument::open("tests/examples/7a79c35f7ce0704dec63be82440c8182.pdf").unwrap();
+        let mut info = doc.info().unwrap();
+        info.title = Some("Überblick".to_string());
+        info.author = Some("Jane Doe".to_string());
+        info.modification_date = Some(PdfDate::new(2024, 2, 29, 12, 0, 0, Some(60)));
+        doc.set_info(&info).unwrap();
+
+        let mut data = Vec::new();
+        doc.write_incremental(&mut data).unwrap();
+        let saved = PdfDocument::load_data(data).unwrap();
+        assert_eq!(saved.info().unwrap(), info);
+        let metadata = saved.metadata().unwrap().unwrap();
+        assert_eq!(metadata.title(), info.title);
+        assert_eq!(metadata.creators(), ["Jane Doe"]);
+        assert_eq!(metadata.modify_date(), info.modification_date);
+        assert_eq!(metadata.metadata_date(), info.modification_date);
+        // Untouched properties survive.
+        assert_eq!(metadata.get("dc:format"), Some(XmpValue::Text("application/pdf".to_string())));
+        let trapped = saved.get(&saved.resolve(saved.trailer().get(&b"Info"[..]).unwrap()).unwrap().get_dict().unwrap().clone(), "Trapped");
+        assert_eq!(trapped.unwrap().get_name(), Some(&b"False"[..]));
+    }
+
+    #[test]
+    fn create_info_and_metadata() {
+        let mut doc = PdfDocument::open("tests/examples/sample.pdf").unwrap();
+        // A direct /Info dictionary is carried over into the new object.
+        let mut direct = Dictionary::new();
+        direct.insert(Name::from("Trapped"), Primitives::name(b"True".to_vec()));
+        direct.insert(Name::from("Author"), Primitives::Str(b"Someone".to_vec()));
+        doc.xref.set_trailer_entry("Info", Some(Primitives::Dict(direct)));
+        let info = DocumentInfo {
+            title: Some("Report".to_string()),
+            keywords: Some("a, b".to_string()),
+            creation_date: Some(PdfDate::new(2020, 1, 2, 3, 4, 5, None)),
+            ..DocumentInfo::default()
+        };
+        doc.set_info(&info).unwrap();
+        let mut data = Vec::new();
+        doc.write_to(&mut data, &SaveOptions::default()).unwrap();
+        let saved = PdfDocument::load_data(data).unwrap();
+        assert_eq!(saved.info().unwrap(), info);
+        let metadata = saved.metadata().unwrap().unwrap();
+        assert_eq!(metadata.keywords().as_deref(), Some("a, b"));
+        assert_eq!(metadata.create_date(), info.creation_date);
+        let saved_info = saved.get(saved.trailer(), "Info").unwrap();
+        assert_eq!(saved_info.get("Trapped").and_then(|t| t.get_name()), Some(&b"True"[..]));
+    }
+}
@@ -0,0 +1,281 @@
+use std::collections::{HashSet, VecDeque};
+use std::marker::PhantomData;
+
+use crate::document::PdfDocument;
+use crate::primitives::{Primitives, Ref};
+use crate::error::Error;
+
+// Trees deeper than this are treated as broken, independently of the cycle
+// check.
+const MAX_DEPTH: usize = 64;
+
+// Key type of a name tree (strings, stored in `/Names`) or a number tree
+// (integers, stored in `/Nums`).
+pub trait TreeKey: Ord + Sized {
+    const ENTRIES: &'static str;
+    fn from_obj(obj: &Primitives) -> Option<Self>;
+}
+
+impl TreeKey for Vec<u8> {
+    const ENTRIES: &'static str = "Names";
+    fn from_obj(obj: &Primitives) -> Option<Self> {
+        obj.get_string_bytes()
+    }
+}
+
+impl TreeKey for i64 {
+    const ENTRIES: &'static str = "Nums";
+    fn from_obj(obj: &Primitives) -> Option<Self> {
+        obj.get_integer()
+    }
+}
+
+// Read access to a name or number tree rooted at `root`.
+pub struct Tree<'a, K> {
+    doc: &'a PdfDocument,
+    root: Primitives,
+    key: PhantomData<K>,
+}
+
+pub type NameTree<'a> = Tree<'a, Vec<u8>>;
+pub type NumberTree<'a> = Tree<'a, i64>;
+
+impl<'a, K: TreeKey> Tree<'a, K> {
+    pub fn new(doc: &'a PdfDocument, root: Primitives) -> Self {
+        Tree {
+            doc: doc,
+            root: root,
+            key: PhantomData,
+        }
+    }
+
+    // Finds the value for `key`, descending only into kids whose `/Limits`
+    // cover it.
+    pub fn get(&self, key: &K) -> Result<Option<Primitives>, Error> {
+        let mut visited = HashSet::new();
+        self.lookup(&self.root, key, &mut visited, 0)
+    }
+
+    fn lookup(&self, node: &Primitives, key: &K, visited: &mut HashSet<Ref>, depth: usize) -> Result<Option<Primitives>, Error> {
+        if let Some(r) = node.get_ref() {
+            if !visited.insert(r) {
+                eprintln!("lookup - tree contains a cycle at {:?}.", r);
+                return Ok(None);
+            }
+        }
+        if depth > MAX_DEPTH {
+            return Ok(None);
+        }
+        let node = self.doc.resolve(node)?;
+
+        if let Some(kids) = node.get("Kids") {
+            let kids = self.doc.resolve(kids)?.get_array().cloned().unwrap_or_default();
+            let mut limits = Vec::with_capacity(kids.len());
+            for kid in &kids {
+                limits.push(self.limits(kid)?);
+            }
+            if limits.iter().all(|l| l.is_some()) {
+                // Kids are sorted by their ranges.
+                let found = limits.binary_search_by(|l| {
+                    let (low, high) = l.as_ref().unwrap();
+                    if high < key {
+                        std::cmp::Ordering::Less
+                    } else if low > key {
+                        std::cmp::Ordering::Greater
+                    } else {
+                        std::cmp::Ordering::Equal
+                    }
+                });
+                return match found {
+                    Ok(i) => self.lookup(&kids[i], key, visited, depth + 1),
+                    Err(_) => Ok(None),
+                };
+            }
+            // Without limits every kid has to be searched.
+            for kid in &kids {
+                if let Some(value) = self.lookup(kid, key, visited, depth + 1)? {
+                    return Ok(Some(value));
+                }
+            }
+            return Ok(None);
+        }
+
+        let entries = match node.get(K::ENTRIES) {
+            Some(entries) => self.doc.resolve(entries)?.get_array().cloned().unwrap_or_default(),
+            None => return Ok(None),
+        };
+        let pairs: Vec<&[Primitives]> = entries.chunks_exact(2).collect();
+        let found = pairs.binary_search_by(|pair| match K::from_obj(&pair[0]) {
+            Some(k) => k.cmp(key),
+            None => std::cmp::Ordering::Less,
+        });
+        let index = match found {
+            Ok(i) => Some(i),
+            // Some writers don't sort the entries.
+            Err(_) => pairs.iter().position(|pair| K::from_obj(&pair[0]).as_ref() == Some(key)),
+        };
+        Ok(index.map(|i| pairs[i][1].clone()))
+    }
+
+    fn limits(&self, kid: &Primitives) -> Result<Option<(K, K)>, Error> {
+        let kid = self.doc.resolve(kid)?;
+        let limits = match kid.get("Limits") {
+            Some(limits) => self.doc.resolve(limits)?,
+            None => return Ok(None),
+        };
+        Ok(match limits.get_array().map(|l| l.as_slice()) {
+            Some([low, high]) => K::from_obj(low).zip(K::from_obj(high)),
+            _ => None,
+        })
+    }
+
+    // All entries in tree order.
+    pub fn iter(&self) -> TreeIter<'a, K> {
+        TreeIter {
+            doc: self.doc,
+            nodes: vec![(self.root.clone(), 0)],
+            entries: VecDeque::new(),
+            visited: HashSet::new(),
+        }
+    }
+}
+
+pub struct TreeIter<'a, K> {
+    doc: &'a PdfDocument,
+    // Nodes still to visit, with their depth, last one first.
+    nodes: Vec<(Primitives, usize)>,
+    // Entries of the current leaf.
+    entries: VecDeque<(K, Primitives)>,
+    visited: HashSet<Ref>,
+}
+
+impl<'a, K: TreeKey> TreeIter<'a, K> {
+    fn visit(&mut self, node: Primitives, depth: usize) -> Result<(), Error> {
+        if let Some(r) = node.get_ref() {
+            if !self.visited.insert(r) {
+                eprintln!("visit - tree contains a cycle at {:?}.", r);
+                return Ok(());
+            }
+        }
+        if depth > MAX_DEPTH {
+            return Ok(());
+        }
+        let node = self.doc.resolve(&node)?;
+        if let Some(kids) = node.get("Kids") {
+            let kids = self.doc.resolve(kids)?.get_array().cloned().unwrap_or_default();
+            self.nodes.extend(kids.into_iter().rev().map(|kid| (kid, depth + 1)));
+        }
+        if let Some(entries) = node.get(K::ENTRIES) {
+            let entries = self.doc.resolve(entries)?.get_array().cloned().unwrap_or_default();
+            for pair in entries.chunks_exact(2) {
+                if let Some(key) = K::from_obj(&pair[0]) {
+                    self.entries.push_back((key, pair[1].clone()));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a, K: TreeKey> Iterator for TreeIter<'a, K> {
+    type Item = Result<(K, Primitives), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.entries.pop_front() {
+                return Some(Ok(entry));
+            }
+            let (node, depth) = self.nodes.pop()?;
+            if let Err(e) = self.visit(node, depth) {
+                self.nodes.clear();
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+impl PdfDocument {
+    // The name tree under `key` in the catalog's `/Names` dictionary, e.g.
+    // "Dests" or "EmbeddedFiles".
+    pub fn name_tree(&self, key: &str) -> Result<Option<NameTree<'_>>, Error> {
+        let names = self.get(&self.catalog()?, "Names")?;
+        Ok(names.get(key).map(|root| NameTree::new(self, root.clone())))
+    }
+}
+
+// All named destinations, from the PDF 1.1 `/Dests` dictionary and the
+// `/Dests` name tree.
+pub(crate) fn named_dests(doc: &PdfDocument) -> Result<Vec<(Vec<u8>, Primitives)>, Error> {
+    let mut dests = Vec::new();
+    if let Primitives::Dict(dict) = doc.get(&doc.catalog()?, "Dests")? {
+        for (name, dest) in dict {
+            dests.push((name.0.clone(), dest.clone()));
+        }
+    }
+    if let Some(tree) = doc.name_tree("Dests")? {
+        for entry in tree.iter() {
+            dests.push(entry?);
+        }
+    }
+    Ok(dests)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::PdfDocument;
+    use crate::primitives::{Dictionary, Name, Primitives};
+    use super::{NameTree, NumberTree};
+
+    fn node(doc: &mut PdfDocument, key: &str, entries: Vec<Primitives>, kids: Vec<Primitives>, limits: Option<(Primitives, Primitives)>) -> Primitives {
+        let mut dict = Dictionary::new();
+        if !entries.is_empty() {
+            dict.insert(Name::from(key), Primitives::Array(entries));
+        }
+        if !kids.is_empty() {
+            dict.insert(Name::from("Kids"), Primitives::Array(kids));
+        }
+        if let Some((low, high)) = limits {
+            dict.insert(Name::from("Limits"), Primitives::Array(vec![low, high]));
+        }
+        doc.add_object(Primitives::Dict(dict)).into()
+    }
+
+    fn s(text: &str) -> Primitives {
+        Primitives::Str(text.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn name_tree_lookup() {
+        let mut doc = PdfDocument::new();
+        let a = node(&mut doc, "Names", vec![s("apple"), Primitives::Int(1), s("banana"), Primitives::Int(2)], vec![], Some((s("apple"), s("banana"))));
+        let b = node(&mut doc, "Names", vec![s("cherry"), Primitives::Int(3), s("date"), Primitives::Int(4)], vec![], Some((s("cherry"), s("date"))));
+        let mid = node(&mut doc, "Names", vec![], vec![b], Some((s("cherry"), s("date"))));
+        let root = node(&mut doc, "Names", vec![], vec![a, mid], None);
+
+        let tree = NameTree::new(&doc, root);
+        assert_eq!(tree.get(&b"banana".to_vec()).unwrap(), Some(Primitives::Int(2)));
+        assert_eq!(tree.get(&b"date".to_vec()).unwrap(), Some(Primitives::Int(4)));
+        assert_eq!(tree.get(&b"bz".to_vec()).unwrap(), None);
+        let keys: Vec<Vec<u8>> = tree.iter().map(|e| e.unwrap().0).collect();
+        assert_eq!(keys, [&b"apple"[..], b"banana", b"cherry", b"date"]);
+    }
+
+    #[test]
+    fn number_tree_with_cycle() {
+        let mut doc = PdfDocument::new();
+        let leaf = node(&mut doc, "Nums", vec![Primitives::Int(0), s("zero"), Primitives::Int(10), s("ten")], vec![], None);
+        let mid = node(&mut doc, "Nums", vec![], vec![leaf], None);
+        let root = node(&mut doc, "Nums", vec![], vec![mid.clone()], None);
+        // Make the middle node point back at the root before the leaf.
+        let mut dict = doc.resolve(&mid).unwrap().get_dict().cloned().unwrap();
+        let mut kids = dict[&b"Kids"[..]].get_array().cloned().unwrap();
+        kids.insert(0, root.clone());
+        dict.insert(Name::from("Kids"), Primitives::Array(kids));
+        doc.replace_object(mid.get_ref().unwrap(), Primitives::Dict(dict)).unwrap();
+
+        let tree = NumberTree::new(&doc, root);
+        assert_eq!(tree.get(&10).unwrap(), Some(s("ten")));
+        assert_eq!(tree.get(&5).unwrap(), None);
+        assert_eq!(tree.iter().count(), 2);
+    }
+}
@@ -0,0 +1,297 @@
+use std::collections::HashSet;
+
+use crate::document::PdfDocument;
+use crate::primitives::{Dictionary, Name, Primitives, Ref};
+use crate::utils::encode_text;
+use crate::error::Error;
+
+// One entry of the document outline (bookmarks).
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutlineItem {
+    pub title: String,
+    // Index of the target page, `None` when the item has no destination
+    // inside this document (e.g. a URI action).
+    pub dest: Option<usize>,
+    pub children: Vec<OutlineItem>,
+    // Whether the children are shown initially.
+    pub open: bool,
+    // RGB components from 0 to 1, `None` for the default black.
+    pub color: Option<[f64; 3]>,
+    pub style: OutlineStyle,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct OutlineStyle {
+    pub italic: bool,
+    pub bold: bool,
+}
+
+impl OutlineItem {
+    pub fn new(title: &str, dest: Option<usize>) -> OutlineItem {
+        OutlineItem {
+            title: title.to_string(),
+            dest: dest,
+            children: Vec::new(),
+            open: false,
+            color: None,
+            style: OutlineStyle::default(),
+        }
+    }
+
+    // Number of items shown below this one when it is open.
+    fn visible_descendants(&self) -> i64 {
+        self.children.iter().map(|child| 1 + if child.open { child.visible_descendants() } else { 0 }).sum()
+    }
+}
+
+impl PdfDocument {
+    // The top-level outline items, empty if the document has no outline.
+    pub fn outline(&self) -> Result<Vec<OutlineItem>, Error> {
+        let root = match self.get(&self.catalog()?, "Outlines")? {
+            Primitives::Dict(root) => root,
+            _ => return Ok(Vec::new()),
+        };
+        let mut reader = OutlineReader {
+            doc: self,
+            visited: HashSet::new(),
+        };
+        reader.read_items(root.get(&b"First"[..]))
+    }
+
+    // Replaces the outline. An empty list removes it. The objects of the
+    // old outline are deleted.
+    pub fn set_outline(&mut self, items: &[OutlineItem]) -> Result<(), Error> {
+        let pages = self.page_refs()?;
+        check_dests(items, pages.len())?;
+        let replaced = self.outline_objects()?;
+        let catalog_ref = self.catalog_ref()?;
+        let mut catalog = self.catalog()?;
+        if items.is_empty() {
+            catalog.remove(&b"Outlines"[..]);
+        } else {
+            let mut root = Dictionary::new();
+            root.insert(Name::from("Type"), Primitives::name(b"Outlines".to_vec()));
+            let root_ref = self.add_object(Primitives::Null);
+            let (first, last) = self.write_items(root_ref, items, &pages)?;
+            root.insert(Name::from("First"), first.into());
+            root.insert(Name::from("Last"), last.into());
+            let visible: i64 = items.iter().map(|item| 1 + if item.open { item.visible_descendants() } else { 0 }).sum();
+            root.insert(Name::from("Count"), Primitives::Int(visible));
+            self.replace_object(root_ref, Primitives::Dict(root))?;
+            catalog.insert(Name::from("Outlines"), root_ref.into());
+        }
+        self.replace_object(catalog_ref, Primitives::Dict(catalog))?;
+        for r in replaced {
+            self.delete_object(r)?;
+        }
+        Ok(())
+    }
+
+    // The outline root and items that are indirect objects. Only objects
+    // with a `/Title` are taken for items, so a broken `/Next` can't take a
+    // page along.
+    fn outline_objects(&self) -> Result<Vec<Ref>, Error> {
+        let mut pending = match self.catalog()?.get(&b"Outlines"[..]) {
+            Some(root) => vec![(root.clone(), true)],
+            None => return Ok(Vec::new()),
+        };
+        let mut visited = HashSet::new();
+        let mut objects = Vec::new();
+        while let Some((obj, is_root)) = pending.pop() {
+            let dict = match self.resolve(&obj)? {
+                Primitives::Dict(dict) if is_root || dict.contains_key(&b"Title"[..]) => dict,
+                _ => continue,
+            };
+            if let Some(r) = obj.get_ref() {
+                if !visited.insert(r) {
+                    continue;
+                }
+                objects.push(r);
+            }
+            for key in ["First", "Next"].iter() {
+                if let Some(next) = dict.get(key.as_bytes()) {
+                    pending.push((next.clone(), false));
+                }
+            }
+        }
+        Ok(objects)
+    }
+
+    // Writes `items` as children of `parent`, returns the first and last.
+    fn write_items(&mut self, parent: Ref, items: &[OutlineItem], pages: &[Ref]) -> Result<(Ref, Ref), Error> {
+        let refs: Vec<Ref> = items.iter().map(|_| self.add_object(Primitives::Null)).collect();
+        for (i, item) in items.iter().enumerate() {
+            let mut dict = Dictionary::new();
+            dict.insert(Name::from("Title"), Primitives::Str(encode_text(&item.title)));
+            dict.insert(Name::from("Parent"), parent.into());
+            if i > 0 {
+                dict.insert(Name::from("Prev"), refs[i - 1].into());
+            }
+            if let Some(&next) = refs.get(i + 1) {
+                dict.insert(Name::from("Next"), next.into());
+            }
+            if let Some(index) = item.dest {
+                let page = *pages.get(index).ok_or(Error::InvalidArgument("outline destination out of range"))?;
+                // Keep the viewer's position and zoom, only change the page.
+                dict.insert(Name::from("Dest"), Primitives::Array(vec![
+                    page.into(),
+                    Primitives::name(b"XYZ".to_vec()),
+                    Primitives::Null,
+                    Primitives::Null,
+                    Primitives::Null,
+                ]));
+            }
+            if !item.children.is_empty() {
+                let (first, last) = self.write_items(refs[i], &item.children, pages)?;
+                dict.insert(Name::from("First"), first.into());
+                dict.insert(Name::from("Last"), last.into());
+                // Closed items store the negated count of what opening them
+                // would show.
+                let count = item.visible_descendants();
+                dict.insert(Name::from("Count"), Primitives::Int(if item.open { count } else { -count }));
+            }
+            if let Some(color) = item.color {
+                dict.insert(Name::from("C"), Primitives::Array(color.iter().map(|&c| Primitives::Real(c)).collect()));
+            }
+            let flags = item.style.italic as i64 | (item.style.bold as i64) << 1;
+            if flags != 0 {
+                dict.insert(Name::from("F"), Primitives::Int(flags));
+            }
+            self.replace_object(refs[i], Primitives::Dict(dict))?;
+        }
+        Ok((refs[0], refs[refs.len() - 1]))
+    }
+}
+
+// Fails if an item points to a page the document doesn't have.
+fn check_dests(items: &[OutlineItem], page_count: usize) -> Result<(), Error> {
+    for item in items {
+        if item.dest.is_some_and(|index| index >= page_count) {
+            return Err(Error::InvalidArgument("outline destination out of range"));
+        }
+        check_dests(&item.children, page_count)?;
+    }
+    Ok(())
+}
+
+struct OutlineReader<'a> {
+    doc: &'a PdfDocument,
+    visited: HashSet<Ref>,
+}
+
+impl<'a> OutlineReader<'a> {
+    // Reads the sibling chain starting at `first`.
+    fn read_items(&mut self, first: Option<&Primitives>) -> Result<Vec<OutlineItem>, Error> {
+        let mut items = Vec::new();
+        let mut next = first.and_then(|f| f.get_ref());
+        while let Some(r) = next {
+            if !self.visited.insert(r) {
+                eprintln!("read_items - outline contains a cycle at {:?}.", r);
+                break;
+            }
+            let dict = match self.doc.fetch(r)? {
+                Primitives::Dict(dict) => dict,
+                _ => break,
+            };
+            next = dict.get(&b"Next"[..]).and_then(|n| n.get_ref());
+
+            let title = self.doc.get(&dict, "Title")?.get_text().unwrap_or_default();
+            let dest = match dict.get(&b"Dest"[..]) {
+                Some(dest) => self.dest_page(dest)?,
+                None => self.action_page(&dict)?,
+            };
+            let color = match self.doc.get(&dict, "C")?.get_array() {
+                Some(c) if c.len() == 3 => {
+                    let c: Vec<f64> = c.iter().map(|v| v.get_real().unwrap_or(0.0)).collect();
+                    Some([c[0], c[1], c[2]])
+                }
+                _ => None,
+            };
+            let flags = self.doc.get(&dict, "F")?.get_integer().unwrap_or(0);
+            items.push(OutlineItem {
+                title: title,
+                dest: dest,
+                children: self.read_items(dict.get(&b"First"[..]))?,
+                open: self.doc.get(&dict, "Count")?.get_integer().unwrap_or(0) > 0,
+                color: color,
+                style: OutlineStyle {
+                    italic: flags & 1 != 0,
+                    bold: flags & 2 != 0,
+                },
+            });
+        }
+        Ok(items)
+    }
+
+    fn action_page(&self, dict: &Dictionary) -> Result<Option<usize>, Error> {
+        let action = self.doc.get(dict, "A")?;
+        if action.get("S").and_then(|s| s.get_name()) != Some(b"GoTo") {
+            return Ok(None);
+        }
+        match action.get("D") {
+            Some(dest) => self.dest_page(dest),
+            None => Ok(None),
+        }
+    }
+
+    fn dest_page(&self, dest: &Primitives) -> Result<Option<usize>, Error> {
+        Ok(self.doc.resolve_destination(dest)?.map(|d| d.page))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::PdfDocument;
+    use crate::primitives::{Primitives, Ref};
+    use crate::writer::SaveOptions;
+    use crate::error::Error;
+    use super::{OutlineItem, OutlineStyle};
+
+    #[test]
+    fn read_outline() {
+        let doc = PdfDocument::open("tests/examples/dummy.pdf").unwrap();
+        assert_eq!(doc.outline().unwrap(), [OutlineItem::new("Dummy PDF file", Some(0))]);
+
+        let doc = PdfDocument::open("tests/examples/sample.pdf").unwrap();
+        assert!(doc.outline().unwrap().is_empty());
+    }
+
+    #[test]
+    fn write_outline() {
+        let mut doc = PdfDocument::open("tests/examples/sample.pdf").unwrap();
+        let mut chapter = OutlineItem::new("Chapter — one", Some(0));
+        chapter.open = true;
+        chapter.color = Some([1.0, 0.0, 0.5]);
+        chapter.style = OutlineStyle { italic: false, bold: true };
+        chapter.children.push(OutlineItem::new("Section", Some(1)));
+        let mut closed = OutlineItem::new("Appendix", None);
+        closed.children.push(OutlineItem::new("Notes", Some(1)));
+        let outline = vec![chapter, closed];
+        doc.set_outline(&outline).unwrap();
+        assert_eq!(doc.outline().unwrap(), outline);
+
+        // Nothing changes when a destination is out of range, and the items
+        // of a replaced outline are deleted.
+        let size = doc.xref.size();
+        let mut bad = outline.clone();
+        bad[1].children.push(OutlineItem::new("Missing", Some(2)));
+        assert!(matches!(doc.set_outline(&bad), Err(Error::InvalidArgument(_))));
+        assert_eq!((doc.xref.size(), doc.outline().unwrap()), (size, outline.clone()));
+        let old_root = doc.catalog().unwrap().get(&b"Outlines"[..]).and_then(|r| r.get_ref()).unwrap();
+        let old_items: Vec<Ref> = (old_root.num + 1..size).map(|n| Ref::new(n, 0)).collect();
+        doc.set_outline(&outline).unwrap();
+        assert_eq!(doc.fetch(old_root).unwrap(), Primitives::Null);
+        assert!(old_items.iter().all(|&r| doc.fetch(r).unwrap() == Primitives::Null));
+        assert_eq!(doc.outline().unwrap(), outline);
+
+        let mut data = Vec::new();
+        doc.write_to(&mut data, &SaveOptions::default()).unwrap();
+        let saved = PdfDocument::load_data(data).unwrap();
+        assert_eq!(saved.outline().unwrap(), outline);
+        let root = saved.get(&saved.catalog().unwrap(), "Outlines").unwrap();
+        assert_eq!(root.get("Count").and_then(|c| c.get_integer()), Some(3));
+
+        doc.set_outline(&[]).unwrap();
+        assert!(doc.outline().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,419 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::document::PdfDocument;
+use crate::primitives::{Dictionary, Name, Primitives, Ref};
+use crate::error::Error;
+
+// Attributes a page inherits from its ancestors in the page tree when it
+// doesn't set them itself.
+const INHERITABLE: [&str; 4] = ["Resources", "MediaBox", "CropBox", "Rotate"];
+
+#[derive(Debug, Clone)]
+pub struct Page {
+    pub index: usize,
+    pub reference: Ref,
+    // The page object as stored in the file.
+    pub dict: Dictionary,
+    // Values picked up from `/Parent` nodes for keys in `INHERITABLE`.
+    inherited: Dictionary,
+}
+
+impl Page {
+    // Looks up `key` on the page, falling back to inherited attributes.
+    pub fn get(&self, key: &str) -> Option<&Primitives> {
+        self.dict.get(key.as_bytes()).or_else(|| self.inherited.get(key.as_bytes()))
+    }
+
+    pub fn media_box(&self, doc: &PdfDocument) -> Result<[f64; 4], Error> {
+        match self.rectangle(doc, "MediaBox")? {
+            Some(rect) => Ok(rect),
+            // US Letter, like other readers do when /MediaBox is missing.
+            None => Ok([0.0, 0.0, 612.0, 792.0]),
+        }
+    }
+
+    pub fn crop_box(&self, doc: &PdfDocument) -> Result<[f64; 4], Error> {
+        match self.rectangle(doc, "CropBox")? {
+            Some(rect) => Ok(rect),
+            None => self.media_box(doc),
+        }
+    }
+
+    fn rectangle(&self, doc: &PdfDocument, key: &str) -> Result<Option<[f64; 4]>, Error> {
+        let obj = match self.get(key) {
+            Some(obj) => doc.resolve(obj)?,
+            None => return Ok(None),
+        };
+        let nums: Vec<f64> = obj.get_array().map_or(Vec::new(), |a| a.iter().filter_map(|n| n.get_real()).collect());
+        if nums.len() != 4 {
+            return Ok(None);
+        }
+        Ok(Some([nums[0].min(nums[2]), nums[1].min(nums[3]), nums[0].max(nums[2]), nums[1].max(nums[3])]))
+    }
+
+    // The page dictionary with inherited attributes made explicit, for
+    // moving the page to another parent.
+    pub(crate) fn flattened(&self) -> Dictionary {
+        let mut dict = self.dict.clone();
+        for (key, value) in &self.inherited {
+            dict.insert(key.clone(), value.clone());
+        }
+        dict
+    }
+
+    // Rotation in degrees, normalized to 0, 90, 180 or 270.
+    pub fn rotate(&self) -> i64 {
+        let rotate = self.get("Rotate").and_then(|r| r.get_integer()).unwrap_or(0);
+        if rotate % 90 != 0 {
+            return 0;
+        }
+        rotate.rem_euclid(360)
+    }
+
+    pub fn resources(&self, doc: &PdfDocument) -> Result<Dictionary, Error> {
+        match self.get("Resources") {
+            Some(obj) => Ok(doc.resolve(obj)?.get_dict().cloned().unwrap_or_default()),
+            None => Ok(Dictionary::new()),
+        }
+    }
+
+    // The decoded content stream. When `/Contents` is an array the streams
+    // are concatenated with a newline in between.
+    pub fn contents(&self, doc: &PdfDocument) -> Result<Vec<u8>, Error> {
+        let contents = match self.dict.get(&b"Contents"[..]) {
+            Some(obj) => doc.resolve(obj)?,
+            None => return Ok(Vec::new()),
+        };
+        let parts = match contents {
+            Primitives::Array(parts) => parts,
+            other => vec![other],
+        };
+
+        let mut data = Vec::new();
+        for part in &parts {
+            if let Primitives::Stream(stream) = doc.resolve(part)? {
+                if !data.is_empty() {
+                    data.push(b'\n');
+                }
+                data.extend(stream.decode()?);
+            }
+        }
+        Ok(data)
+    }
+}
+
+impl PdfDocument {
+    pub fn page_count(&self) -> Result<usize, Error> {
+        Ok(self.page_refs()?.len())
+    }
+
+    pub fn page(&self, index: usize) -> Result<Page, Error> {
+        let refs = self.page_refs()?;
+        let reference = *refs.get(index).ok_or(Error::InvalidArgument("page index out of range"))?;
+        let dict = match self.fetch(reference)? {
+            Primitives::Dict(dict) => dict,
+            _ => return Err(Error::InvalidFile("page is not a dictionary")),
+        };
+
+        // Walk up the tree for inherited attributes, guarding against loops.
+        let mut inherited = Dictionary::new();
+        let mut visited = HashSet::new();
+        let mut parent = dict.get(&b"Parent"[..]).and_then(|p| p.get_ref());
+        while let Some(r) = parent {
+            if !visited.insert(r) {
+                break;
+            }
+            let node = self.fetch(r)?;
+            let node = match node.get_dict() {
+                Some(node) => node,
+                None => break,
+            };
+            for key in INHERITABLE.iter() {
+                if dict.contains_key(key.as_bytes()) || inherited.contains_key(key.as_bytes()) {
+                    continue;
+                }
+                if let Some(value) = node.get(key.as_bytes()) {
+                    inherited.insert(Name::from(key), value.clone());
+                }
+            }
+            parent = node.get(&b"Parent"[..]).and_then(|p| p.get_ref());
+        }
+
+        Ok(Page {
+            index: index,
+            reference: reference,
+            dict: dict,
+            inherited: inherited,
+        })
+    }
+
+    // Moves pages so that `order[i]` is the index of the page that ends up
+    // at position `i`. `order` must be a permutation of all page indexes.
+    pub fn reorder_pages(&mut self, order: &[usize]) -> Result<(), Error> {
+        let pages = self.pages()?;
+        let mut seen = vec![false; pages.len()];
+        for &i in order {
+            if i >= pages.len() || std::mem::replace(&mut seen[i], true) {
+                return Err(Error::InvalidArgument("page order is not a permutation"));
+            }
+        }
+        if order.len() != pages.len() {
+            return Err(Error::InvalidArgument("page order is not a permutation"));
+        }
+        let pages = order.iter().map(|&i| (pages[i].reference, pages[i].flattened())).collect();
+        self.set_pages(pages)
+    }
+
+    // Sets `/Rotate` of one page, `degrees` must be a multiple of 90.
+    pub fn set_page_rotation(&mut self, index: usize, degrees: i64) -> Result<(), Error> {
+        if degrees % 90 != 0 {
+            return Err(Error::InvalidArgument("rotation must be a multiple of 90"));
+        }
+        let page = self.page(index)?;
+        let mut dict = page.dict;
+        dict.insert(Name::from("Rotate"), Primitives::Int(degrees.rem_euclid(360)));
+        self.replace_object(page.reference, Primitives::Dict(dict))
+    }
+
+    pub fn delete_page(&mut self, index: usize) -> Result<(), Error> {
+        let mut pages = self.flattened_pages()?;
+        if index >= pages.len() {
+            return Err(Error::InvalidArgument("page index out of range"));
+        }
+        let (reference, _) = pages.remove(index);
+        self.set_pages(pages)?;
+        self.delete_object(reference)
+    }
+
+    // Inserts an empty page of `width` x `height` points before `index`;
+    // `index` equal to the page count appends.
+    pub fn insert_blank_page(&mut self, index: usize, width: f64, height: f64) -> Result<Ref, Error> {
+        let mut pages = self.flattened_pages()?;
+        if index > pages.len() {
+            return Err(Error::InvalidArgument("page index out of range"));
+        }
+        let mut dict = Dictionary::new();
+        dict.insert(Name::from("Type"), Primitives::name(b"Page".to_vec()));
+        dict.insert(Name::from("MediaBox"), Primitives::Array(vec![
+            Primitives::Int(0),
+            Primitives::Int(0),
+            Primitives::Real(width),
+            Primitives::Real(height),
+        ]));
+        dict.insert(Name::from("Resources"), Primitives::Dict(Dictionary::new()));
+        let reference = self.add_object(Primitives::Dict(dict.clone()));
+        pages.insert(index, (reference, dict));
+        self.set_pages(pages)?;
+        Ok(reference)
+    }
+
+    // Inserts a copy of the page right after it. Content and resources are
+    // shared; annotations are copied since each belongs to a single page,
+    // except form field widgets which can't appear twice.
+    pub fn duplicate_page(&mut self, index: usize) -> Result<Ref, Error> {
+        let mut pages = self.flattened_pages()?;
+        let mut dict = match pages.get(index) {
+            Some((_, dict)) => dict.clone(),
+            None => return Err(Error::InvalidArgument("page index out of range")),
+        };
+        let reference = self.add_object(Primitives::Null);
+        if let Some(annots) = dict.get(&b"Annots"[..]).cloned() {
+            let mut copies = Vec::new();
+            for annot in self.resolve(&annots)?.get_array().cloned().unwrap_or_default() {
+                let mut annot = match self.resolve(&annot)? {
+                    Primitives::Dict(annot) => annot,
+                    _ => continue,
+                };
+                if annot.get(&b"Subtype"[..]).and_then(|s| s.get_name()) == Some(b"Widget") {
+                    continue;
+                }
+                annot.insert(Name::from("P"), reference.into());
+                // A popup and its parent annotation refer to each other and
+                // are dropped from the copy rather than shared.
+                annot.remove(&b"Popup"[..]);
+                annot.remove(&b"IRT"[..]);
+                copies.push(self.add_object(Primitives::Dict(annot)).into());
+            }
+            dict.insert(Name::from("Annots"), Primitives::Array(copies));
+        }
+        self.replace_object(reference, Primitives::Dict(dict.clone()))?;
+        pages.insert(index + 1, (reference, dict));
+        self.set_pages(pages)?;
+        Ok(reference)
+    }
+
+    fn flattened_pages(&self) -> Result<Vec<(Ref, Dictionary)>, Error> {
+        Ok(self.pages()?.iter().map(|page| (page.reference, page.flattened())).collect())
+    }
+
+    // Makes `pages` the only kids of the page tree root, in order. The
+    // dictionaries must have their inherited attributes made explicit,
+    // intermediate nodes are dropped.
+    fn set_pages(&mut self, pages: Vec<(Ref, Dictionary)>) -> Result<(), Error> {
+        let root = self
+            .catalog()?
+            .get(&b"Pages"[..])
+            .and_then(|p| p.get_ref())
+            .ok_or(Error::InvalidFile("catalog has no /Pages"))?;
+        let mut kids = Vec::with_capacity(pages.len());
+        for (reference, mut dict) in pages {
+            dict.insert(Name::from("Parent"), root.into());
+            let dict = Primitives::Dict(dict);
+            // Unchanged pages are left alone to keep incremental updates
+            // small.
+            if self.fetch(reference)? != dict {
+                self.replace_object(reference, dict)?;
+            }
+            kids.push(Primitives::from(reference));
+        }
+
+        let mut node = self.fetch(root)?.get_dict().cloned().unwrap_or_default();
+        // Pages carry their own attributes now, inserted blank pages must
+        // not pick up anything from the root.
+        for key in INHERITABLE.iter() {
+            node.remove(key.as_bytes());
+        }
+        node.insert(Name::from("Count"), Primitives::Int(kids.len() as i64));
+        node.insert(Name::from("Kids"), Primitives::Array(kids));
+        self.replace_object(root, Primitives::Dict(node))
+    }
+
+    pub fn pages(&self) -> Result<Vec<Page>, Error> {
+        (0..self.page_count()?).map(|i| self.page(i)).collect()
+    }
+
+    // References of all page objects in document order. The page tree is
+    // walked once and the result shared by all callers.
+    pub fn page_refs(&self) -> Result<Arc<Vec<Ref>>, Error> {
+        if let Some(refs) = self.page_refs.read().unwrap().as_ref() {
+            return Ok(refs.clone());
+        }
+        let refs = Arc::new(self.read_page_tree()?);
+        *self.page_refs.write().unwrap() = Some(refs.clone());
+        Ok(refs)
+    }
+
+    fn read_page_tree(&self) -> Result<Vec<Ref>, Error> {
+        let catalog = self.catalog()?;
+        let root = catalog
+            .get(&b"Pages"[..])
+            .and_then(|p| p.get_ref())
+            .ok_or(Error::InvalidFile("catalog has no /Pages"))?;
+
+        let mut refs = Vec::new();
+        let mut visited = HashSet::new();
+        // Depth-first, with kids pushed in reverse to keep document order.
+        let mut queue = vec![root];
+        while let Some(r) = queue.pop() {
+            if !visited.insert(r) {
+                eprintln!("read_page_tree - pages tree contains a cycle at {:?}.", r);
+                continue;
+            }
+            let node = self.fetch(r)?;
+            let node = match node.get_dict() {
+                Some(node) => node,
+                None => continue,
+            };
+            let is_page = match node.get(&b"Type"[..]).and_then(|t| t.get_name()) {
+                Some(ty) => ty == b"Page",
+                // Some writers leave out /Type, a node without /Kids is a leaf.
+                None => !node.contains_key(&b"Kids"[..]),
+            };
+            if is_page {
+                refs.push(r);
+                continue;
+            }
+            let kids = match node.get(&b"Kids"[..]) {
+                Some(kids) => self.resolve(kids)?,
+                None => continue,
+            };
+            if let Some(kids) = kids.get_array() {
+                queue.extend(kids.iter().rev().filter_map(|kid| kid.get_ref()));
+            }
+        }
+        Ok(refs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::PdfDocument;
+    use crate::writer::SaveOptions;
+    use crate::error::Error;
+
+    fn contents(doc: &PdfDocument) -> Vec<Vec<u8>> {
+        doc.pages().unwrap().iter().map(|p| p.contents(doc).unwrap()).collect()
+    }
+
+    // Saves both ways and checks that the reloaded files agree.
+    fn reload(doc: &PdfDocument) -> PdfDocument {
+        let mut incremental = Vec::new();
+        doc.write_incremental(&mut incremental).unwrap();
+        let incremental = PdfDocument::load_data(incremental).unwrap();
+        let mut full = Vec::new();
+        doc.write_to(&mut full, &SaveOptions::default()).unwrap();
+        let full = PdfDocument::load_data(full).unwrap();
+        assert_eq!(contents(&incremental), contents(&full));
+        full
+    }
+
+    #[test]
+    fn reorder_and_delete_pages() {
+        let mut doc = PdfDocument::open("tests/examples/sample.pdf").unwrap();
+        let original = contents(&doc);
+
+        doc.reorder_pages(&[1, 0]).unwrap();
+        assert_eq!(contents(&reload(&doc)), [original[1].clone(), original[0].clone()]);
+        assert!(doc.reorder_pages(&[0, 0]).is_err());
+        assert!(doc.reorder_pages(&[0]).is_err());
+
+        doc.delete_page(1).unwrap();
+        let saved = reload(&doc);
+        assert_eq!(contents(&saved), [original[1].clone()]);
+        assert!(matches!(doc.delete_page(1), Err(Error::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn rotate_pages() {
+        let mut doc = PdfDocument::open("tests/examples/sample.pdf").unwrap();
+        doc.set_page_rotation(1, -90).unwrap();
+        let saved = reload(&doc);
+        assert_eq!(saved.page(0).unwrap().rotate(), 0);
+        assert_eq!(saved.page(1).unwrap().rotate(), 270);
+        assert!(doc.set_page_rotation(0, 45).is_err());
+        assert!(matches!(doc.set_page_rotation(2, 90), Err(Error::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn insert_and_duplicate_pages() {
+        let mut doc = PdfDocument::open("tests/examples/sample.pdf").unwrap();
+        let original = contents(&doc);
+
+        doc.insert_blank_page(1, 200.0, 300.5).unwrap();
+        doc.duplicate_page(0).unwrap();
+        let saved = reload(&doc);
+        assert_eq!(saved.page_count().unwrap(), 4);
+        assert_eq!(contents(&saved), [original[0].clone(), original[0].clone(), Vec::new(), original[1].clone()]);
+        assert_eq!(saved.page(2).unwrap().media_box(&saved).unwrap(), [0.0, 0.0, 200.0, 300.5]);
+        let media_box = doc.page(0).unwrap().media_box(&doc).unwrap();
+        assert_eq!(saved.page(1).unwrap().media_box(&saved).unwrap(), media_box);
+        assert!(matches!(doc.insert_blank_page(5, 100.0, 100.0), Err(Error::InvalidArgument(_))));
+        assert!(matches!(doc.duplicate_page(9), Err(Error::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn duplicate_page_annotations() {
+        let mut doc = PdfDocument::open("tests/examples/PDF_sample.pdf").unwrap();
+        let copy = doc.duplicate_page(0).unwrap();
+        let saved = reload(&doc);
+        let annots = |i| {
+            let page = saved.page(i).unwrap();
+            saved.get(&page.dict, "Annots").unwrap().get_array().cloned().unwrap()
+        };
+        assert_eq!(annots(0).len(), annots(1).len());
+        assert_ne!(annots(0), annots(1));
+        let annot = doc.resolve(&doc.page(1).unwrap().get("Annots").unwrap().get_array().unwrap()[0]).unwrap();
+        assert_eq!(annot.get("P").and_then(|p| p.get_ref()), Some(copy));
+    }
+}
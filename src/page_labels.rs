@@ -0,0 +1,243 @@
+use crate::document::PdfDocument;
+use crate::primitives::{Dictionary, Name, Primitives};
+use crate::names::NumberTree;
+use crate::utils::encode_text;
+use crate::error::Error;
+
+// Largest value written as roman numerals or letters; 10000 is "M" ten times
+// or a letter repeated 385 times.
+const MAX_SPELLED: i64 = 10000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LabelStyle {
+    Decimal,
+    UpperRoman,
+    LowerRoman,
+    // A to Z, then AA to ZZ, AAA...
+    UpperLetters,
+    LowerLetters,
+}
+
+// Labels for the pages from `start` up to the start of the next range.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageLabelRange {
+    // Index of the first page of the range.
+    pub start: usize,
+    // `None` labels pages with the prefix only.
+    pub style: Option<LabelStyle>,
+    pub prefix: String,
+    // Numeric value of the first page's label, at least 1.
+    pub first: i64,
+}
+
+impl LabelStyle {
+    fn from_name(name: &[u8]) -> Option<LabelStyle> {
+        match name {
+            b"D" => Some(LabelStyle::Decimal),
+            b"R" => Some(LabelStyle::UpperRoman),
+            b"r" => Some(LabelStyle::LowerRoman),
+            b"A" => Some(LabelStyle::UpperLetters),
+            b"a" => Some(LabelStyle::LowerLetters),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            LabelStyle::Decimal => "D",
+            LabelStyle::UpperRoman => "R",
+            LabelStyle::LowerRoman => "r",
+            LabelStyle::UpperLetters => "A",
+            LabelStyle::LowerLetters => "a",
+        }
+    }
+
+    // Roman numerals and letters grow with the value, so values above
+    // `MAX_SPELLED` are written in decimal.
+    pub fn format(self, value: i64) -> String {
+        match self {
+            _ if value > MAX_SPELLED => value.to_string(),
+            LabelStyle::Decimal => value.to_string(),
+            LabelStyle::UpperRoman => to_roman(value),
+            LabelStyle::LowerRoman => to_roman(value).to_lowercase(),
+            LabelStyle::UpperLetters => to_letters(value),
+            LabelStyle::LowerLetters => to_letters(value).to_lowercase(),
+        }
+    }
+}
+
+impl PageLabelRange {
+    pub fn new(start: usize, style: Option<LabelStyle>) -> PageLabelRange {
+        PageLabelRange {
+            start: start,
+            style: style,
+            prefix: String::new(),
+            first: 1,
+        }
+    }
+
+    fn label(&self, page: usize) -> String {
+        match self.style {
+            Some(style) => format!("{}{}", self.prefix, style.format(self.first.saturating_add((page - self.start) as i64))),
+            None => self.prefix.clone(),
+        }
+    }
+}
+
+fn to_roman(mut value: i64) -> String {
+    const NUMERALS: [(i64, &str); 13] = [
+        (1000, "M"), (900, "CM"), (500, "D"), (400, "CD"), (100, "C"), (90, "XC"),
+        (50, "L"), (40, "XL"), (10, "X"), (9, "IX"), (5, "V"), (4, "IV"), (1, "I"),
+    ];
+    let mut roman = String::new();
+    for &(n, numeral) in NUMERALS.iter() {
+        while value >= n {
+            roman.push_str(numeral);
+            value -= n;
+        }
+    }
+    roman
+}
+
+fn to_letters(value: i64) -> String {
+    let index = (value.max(1) - 1) as usize;
+    let letter = (b'A' + (index % 26) as u8) as char;
+    letter.to_string().repeat(index / 26 + 1)
+}
+
+impl PdfDocument {
+    // The ranges of the `/PageLabels` number tree, sorted by start page.
+    pub fn page_label_ranges(&self) -> Result<Vec<PageLabelRange>, Error> {
+        let root = match self.get(&self.catalog()?, "PageLabels")? {
+            Primitives::Null => return Ok(Vec::new()),
+            root => root,
+        };
+        let page_count = self.page_count()?;
+        let mut ranges = Vec::new();
+        for entry in NumberTree::new(self, root).iter() {
+            let (start, label) = entry?;
+            if start < 0 || start as usize >= page_count {
+                continue;
+            }
+            let label = self.resolve(&label)?;
+            let style = label.get("S").and_then(|s| s.get_name()).and_then(LabelStyle::from_name);
+            let prefix = match label.get("P") {
+                Some(p) => self.resolve(p)?.get_text().unwrap_or_default(),
+                None => String::new(),
+            };
+            let first = label.get("St").and_then(|s| s.get_integer()).filter(|&s| s >= 1).unwrap_or(1);
+            ranges.push(PageLabelRange {
+                start: start as usize,
+                style: style,
+                prefix: prefix,
+                first: first,
+            });
+        }
+        ranges.sort_by_key(|r| r.start);
+        ranges.dedup_by_key(|r| r.start);
+        Ok(ranges)
+    }
+
+    // One label per page, or `None` if the document doesn't define labels.
+    // Pages before the first range get an empty label.
+    pub fn page_labels(&self) -> Result<Option<Vec<String>>, Error> {
+        let ranges = self.page_label_ranges()?;
+        if ranges.is_empty() {
+            return Ok(None);
+        }
+        let mut labels = Vec::new();
+        let mut current: Option<&PageLabelRange> = None;
+        let mut next = ranges.iter().peekable();
+        for page in 0..self.page_count()? {
+            while let Some(range) = next.next_if(|r| r.start <= page) {
+                current = Some(range);
+            }
+            labels.push(current.map(|r| r.label(page)).unwrap_or_default());
+        }
+        Ok(Some(labels))
+    }
+
+    // Replaces the page labels. An empty list removes them.
+    pub fn set_page_labels(&mut self, ranges: &[PageLabelRange]) -> Result<(), Error> {
+        let page_count = self.page_count()?;
+        if ranges.windows(2).any(|w| w[0].start >= w[1].start) {
+            return Err(Error::InvalidArgument("page label ranges must be sorted by start page"));
+        }
+        if ranges.iter().any(|r| r.start >= page_count || r.first < 1) {
+            return Err(Error::InvalidArgument("page label range out of bounds"));
+        }
+
+        let catalog_ref = self.catalog_ref()?;
+        let mut catalog = self.catalog()?;
+        if ranges.is_empty() {
+            catalog.remove(&b"PageLabels"[..]);
+            return self.replace_object(catalog_ref, Primitives::Dict(catalog));
+        }
+        let mut nums = Vec::with_capacity(ranges.len() * 2);
+        for range in ranges {
+            let mut label = Dictionary::new();
+            if let Some(style) = range.style {
+                label.insert(Name::from("S"), Primitives::name(style.name().as_bytes().to_vec()));
+            }
+            if !range.prefix.is_empty() {
+                label.insert(Name::from("P"), Primitives::Str(encode_text(&range.prefix)));
+            }
+            if range.first != 1 {
+                label.insert(Name::from("St"), Primitives::Int(range.first));
+            }
+            nums.push(Primitives::Int(range.start as i64));
+            nums.push(Primitives::Dict(label));
+        }
+        let mut tree = Dictionary::new();
+        tree.insert(Name::from("Nums"), Primitives::Array(nums));
+        let tree_ref = self.add_object(Primitives::Dict(tree));
+        catalog.insert(Name::from("PageLabels"), tree_ref.into());
+        self.replace_object(catalog_ref, Primitives::Dict(catalog))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::PdfDocument;
+    use crate::writer::SaveOptions;
+    use super::{LabelStyle, PageLabelRange};
+
+    #[test]
+    fn format_labels() {
+        assert_eq!(LabelStyle::UpperRoman.format(1994), "MCMXCIV");
+        assert_eq!(LabelStyle::LowerRoman.format(4), "iv");
+        assert_eq!(LabelStyle::UpperLetters.format(3), "C");
+        assert_eq!(LabelStyle::LowerLetters.format(28), "bb");
+        assert_eq!(LabelStyle::Decimal.format(12), "12");
+        assert_eq!(LabelStyle::UpperRoman.format(10001), "10001");
+        assert_eq!(LabelStyle::LowerLetters.format(i64::MAX), i64::MAX.to_string());
+    }
+
+    #[test]
+    fn write_and_read_page_labels() {
+        let mut doc = PdfDocument::open("tests/examples/sample.pdf").unwrap();
+        assert_eq!(doc.page_labels().unwrap(), None);
+
+        let front = PageLabelRange::new(0, Some(LabelStyle::LowerRoman));
+        let mut appendix = PageLabelRange::new(1, Some(LabelStyle::Decimal));
+        appendix.prefix = "A-".to_string();
+        appendix.first = 3;
+        doc.set_page_labels(&[front.clone(), appendix.clone()]).unwrap();
+        assert_eq!(doc.page_labels().unwrap().unwrap(), ["i", "A-3"]);
+
+        let mut data = Vec::new();
+        doc.write_to(&mut data, &SaveOptions::default()).unwrap();
+        let saved = PdfDocument::load_data(data).unwrap();
+        assert_eq!(saved.page_label_ranges().unwrap(), [front.clone(), appendix]);
+
+        // A huge `/St` neither overflows nor spells out the value.
+        let mut huge = PageLabelRange::new(0, Some(LabelStyle::UpperRoman));
+        huge.first = 9223372036854775807;
+        doc.set_page_labels(&[huge]).unwrap();
+        assert_eq!(doc.page_labels().unwrap().unwrap(), ["9223372036854775807"; 2]);
+
+        assert!(doc.set_page_labels(&[front.clone(), front]).is_err());
+        doc.set_page_labels(&[]).unwrap();
+        assert_eq!(doc.page_labels().unwrap(), None);
+    }
+}
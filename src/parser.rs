@@ -2,9 +2,10 @@ use std::collections::HashMap;
 
 use crate::stream::{Stream, ReadSeek};
 use crate::lexer::{Lexer};
-use crate::primitives::Primitives::{self, Null, Int, Str, HexStr, Real, Array, Dict, Ref, Cmd, EOF};
-use crate::primitives::Name;
+use crate::primitives::Primitives::{self, Int, Array, Dict, Ref, Cmd, EOF};
+use crate::primitives::{Name, PdfStream};
 use crate::error::Error;
+use crate::utils::is_whitespace;
 
 macro_rules! primitive {
     ($token:expr) => { $token.ok_or_else(|| Error::ParserError) };
@@ -29,6 +30,10 @@ impl<T: ReadSeek> Parser<T> {
         p
     }
 
+    pub fn lexer(&self) -> &Lexer<T> {
+        &self.lexer
+    }
+
     pub fn buf1(&self) -> Option<Primitives> {
         self.buf1.clone()
     }
@@ -142,8 +147,77 @@ impl<T: ReadSeek> Parser<T> {
     }
 
 
-    fn make_stream(&self, dict: HashMap<Name, Primitives>) -> Result<Primitives, Error> {
-        Err(Error::ParserError)
+    fn make_stream(&mut self, dict: HashMap<Name, Primitives>) -> Result<Primitives, Error> {
+        // The lexer stopped right after the `stream` keyword, which is
+        // followed by an end-of-line marker before the data starts.
+        self.lexer.skip_to_next_line();
+        let start = self.lexer.stream().pos() - 1;
+
+        // `/Length` may be an indirect reference that can't be resolved from
+        // here, and it is wrong often enough, so it is only trusted if
+        // `endstream` follows where it says the data ends.
+        let declared = dict.get(&b"Length"[..]).and_then(|l| l.get_integer()).filter(|&l| l >= 0);
+        let length = match declared {
+            Some(length) if self.ends_stream_at(start + length as u64) => length as u64,
+            _ => self.find_stream_length(start)?,
+        };
+
+        let stream = self.lexer.stream_mut();
+        let data = if length > 0 {
+            stream.get_byte_range(start, start + length).ok_or(Error::ParserError)?
+        } else {
+            Vec::new()
+        };
+
+        // Continue after the data and drop the `endstream` keyword, so that
+        // `buf1` holds whatever follows (usually `endobj`).
+        self.lexer.set_pos(start + length);
+        let next = self.lexer.get_obj().ok();
+        self.buf1 = if next == Primitives::cmd("endstream") { self.lexer.get_obj().ok() } else { next };
+        self.buf2 = self.lexer.get_obj().ok();
+
+        Ok(Primitives::Stream(PdfStream::new(dict, data)))
+    }
+
+    fn ends_stream_at(&mut self, pos: u64) -> bool {
+        let stream = self.lexer.stream_mut();
+        stream.set_pos(pos);
+        let tail = stream.peek_bytes(32).unwrap_or_default();
+        let keyword = tail.iter().position(|&ch| !is_whitespace(ch)).map(|i| &tail[i..]);
+        keyword.is_some_and(|k| k.starts_with(b"endstream"))
+    }
+
+    // Scans for `endstream` and returns the length of the data before it,
+    // not counting the end-of-line marker that precedes the keyword.
+    fn find_stream_length(&mut self, start: u64) -> Result<u64, Error> {
+        const SCAN_BLOCK: usize = 2048;
+        const KEYWORD: &[u8] = b"endstream";
+
+        let stream = self.lexer.stream_mut();
+        let mut pos = start;
+        loop {
+            stream.set_pos(pos);
+            let block = stream.peek_bytes(SCAN_BLOCK).ok_or(Error::ParserError)?;
+            if let Some(i) = block.windows(KEYWORD.len()).position(|w| w == KEYWORD) {
+                let mut end = pos + i as u64;
+                stream.set_pos(start);
+                let data = stream.peek_bytes((end - start) as usize).unwrap_or_default();
+                let mut len = data.len();
+                if len > 0 && data[len - 1] == 0x0a {
+                    len -= 1;
+                }
+                if len > 0 && data[len - 1] == 0x0d {
+                    len -= 1;
+                }
+                end = start + len as u64;
+                eprintln!("Parser.make_stream - using {} as the stream length.", end - start);
+                return Ok(end - start);
+            }
+            if block.len() < SCAN_BLOCK {
+                return Err(Error::ParserError);
+            }
+            pos += (SCAN_BLOCK - KEYWORD.len()) as u64;
+        }
     }
 
 }
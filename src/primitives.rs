@@ -1,20 +1,91 @@
 //use super::xref::XRef;
-use crate::stream::Stream;
-
+use std::borrow::Borrow;
 use std::collections::HashMap;
 use std::fmt::{self, Debug};
+use std::sync::Arc;
+
+use crate::error::Error;
+use crate::filters;
+use crate::utils::decode_text;
 
 #[derive(PartialEq, Eq, Hash, Clone)]
 pub struct Name(pub Vec<u8>);
 impl Debug for Name {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_tuple("Name").field(&String::from_utf8(self.0.clone()).unwrap()).finish()
+        f.debug_tuple("Name").field(&String::from_utf8_lossy(&self.0)).finish()
+    }
+}
+
+// Lets dictionaries be queried with plain byte strings, e.g. `dict.get(&b"Type"[..])`.
+impl Borrow<[u8]> for Name {
+    fn borrow(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Name {
+    pub fn from(name: &str) -> Name {
+        Name(name.as_bytes().to_vec())
+    }
+}
+
+pub type Dictionary = HashMap<Name, Primitives>;
+
+// An indirect reference, `num gen R`.
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy, Debug)]
+pub struct Ref {
+    pub num: u32,
+    pub gen: u32,
+}
+
+impl Ref {
+    pub fn new(num: u32, gen: u32) -> Ref {
+        Ref { num: num, gen: gen }
+    }
+}
+
+impl From<Ref> for Primitives {
+    fn from(r: Ref) -> Primitives {
+        Primitives::Ref(r.num, r.gen)
+    }
+}
+
+// A stream object. `data` holds the bytes between `stream` and `endstream`
+// as they appear in the file, i.e. still encoded with the stream's filters.
+#[derive(PartialEq, Clone)]
+pub struct PdfStream {
+    pub dict: Dictionary,
+    pub data: Arc<[u8]>,
+}
+
+impl Debug for PdfStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PdfStream").field("dict", &self.dict).field("length", &self.data.len()).finish()
+    }
+}
+
+impl PdfStream {
+    pub fn new(dict: Dictionary, data: Vec<u8>) -> Self {
+        PdfStream {
+            dict: dict,
+            data: data.into(),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Primitives> {
+        self.dict.get(key.as_bytes())
+    }
+
+    // Applies the filters listed in the stream dictionary.
+    pub fn decode(&self) -> Result<Vec<u8>, Error> {
+        filters::decode(&self.dict, &self.data)
     }
 }
 
 #[derive(PartialEq, Clone)]
 pub enum Primitives {
     Null,
+    Bool(bool),
     Int(i64),
     Str(Vec<u8>),
     HexStr(Vec<u16>),
@@ -22,7 +93,7 @@ pub enum Primitives {
     Name(Name),
     Array(Vec<Primitives>),
     Dict(HashMap<Name, Primitives>),
-    //Stream(Stream),
+    Stream(PdfStream),
     Ref(u32, u32),
     Cmd(Vec<u8>),
     EOF,
@@ -32,15 +103,17 @@ impl Debug for Primitives {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Primitives::Null => f.debug_tuple("Null").finish(),
+            Primitives::Bool(value) => f.debug_tuple("Bool").field(value).finish(),
             Primitives::Int(num) => f.debug_tuple("Int").field(num).finish(),
-            Primitives::Str(str) => f.debug_tuple("Str").field(&String::from_utf8(str.clone()).unwrap()).finish(),
+            Primitives::Str(str) => f.debug_tuple("Str").field(&String::from_utf8_lossy(str)).finish(),
             Primitives::HexStr(str) => f.debug_tuple("HexStr").field(&String::from_utf16(str.as_slice())).finish(),
             Primitives::Real(num) => f.debug_tuple("Real").field(num).finish(),
             Primitives::Name(name) => f.debug_tuple("Name").field(name).finish(),
             Primitives::Array(objects) => f.debug_tuple("Array").field(objects).finish(),
             Primitives::Dict(dict) => f.debug_tuple("Dict").field(dict).finish(),
+            Primitives::Stream(stream) => f.debug_tuple("Stream").field(stream).finish(),
             Primitives::Ref(n, g) => f.debug_tuple("Ref").field(n).field(g).finish(),
-            Primitives::Cmd(cmd) => f.debug_tuple("Cmd").field(&String::from_utf8(cmd.clone()).unwrap()).finish(),
+            Primitives::Cmd(cmd) => f.debug_tuple("Cmd").field(&String::from_utf8_lossy(cmd)).finish(),
             Primitives::EOF => f.debug_tuple("EOF").finish(),
         }
     }
@@ -125,6 +198,104 @@ impl Primitives {
         }
         None
     }
-}
 
+    // The bytes of a literal or hexadecimal string.
+    pub fn get_string_bytes(&self) -> Option<Vec<u8>> {
+        match self {
+            Primitives::Str(bytes) => Some(bytes.clone()),
+            Primitives::HexStr(units) => Some(units.iter().map(|&u| u as u8).collect()),
+            _ => None,
+        }
+    }
+
+    // A text string (title, contents, ...) decoded to Unicode.
+    pub fn get_text(&self) -> Option<String> {
+        self.get_string_bytes().map(|bytes| decode_text(&bytes))
+    }
+
+    pub fn get_bool(&self) -> Option<bool> {
+        if let Primitives::Bool(value) = self {
+            return Some(*value);
+        }
+        None
+    }
+
+    pub fn get_real(&self) -> Option<f64> {
+        match self {
+            Primitives::Int(num) => Some(*num as f64),
+            Primitives::Real(num) => Some(*num),
+            _ => None,
+        }
+    }
+
+    pub fn get_name(&self) -> Option<&[u8]> {
+        if let Primitives::Name(name) = self {
+            return Some(&name.0);
+        }
+        None
+    }
+
+    pub fn get_array(&self) -> Option<&Vec<Primitives>> {
+        if let Primitives::Array(array) = self {
+            return Some(array);
+        }
+        None
+    }
 
+    pub fn get_dict(&self) -> Option<&Dictionary> {
+        match self {
+            Primitives::Dict(dict) => Some(dict),
+            Primitives::Stream(stream) => Some(&stream.dict),
+            _ => None,
+        }
+    }
+
+    pub fn get_stream(&self) -> Option<&PdfStream> {
+        if let Primitives::Stream(stream) = self {
+            return Some(stream);
+        }
+        None
+    }
+
+    pub fn get_ref(&self) -> Option<Ref> {
+        if let Primitives::Ref(num, gen) = self {
+            return Some(Ref::new(*num, *gen));
+        }
+        None
+    }
+
+    // Looks up `key` in a dictionary or in the dictionary of a stream.
+    pub fn get(&self, key: &str) -> Option<&Primitives> {
+        self.get_dict()?.get(key.as_bytes())
+    }
+
+    // Calls `f` for every indirect reference inside this object, including
+    // those nested in arrays, dictionaries and stream dictionaries.
+    pub fn for_each_ref<F: FnMut(Ref)>(&self, f: &mut F) {
+        match self {
+            Primitives::Ref(num, gen) => f(Ref::new(*num, *gen)),
+            Primitives::Array(items) => items.iter().for_each(|item| item.for_each_ref(f)),
+            Primitives::Dict(dict) => dict.values().for_each(|value| value.for_each_ref(f)),
+            Primitives::Stream(stream) => stream.dict.values().for_each(|value| value.for_each_ref(f)),
+            _ => (),
+        }
+    }
+
+    // Returns a copy with every indirect reference replaced by `f(ref)`.
+    pub fn map_refs<F: Fn(Ref) -> Primitives>(&self, f: &F) -> Primitives {
+        match self {
+            Primitives::Ref(num, gen) => f(Ref::new(*num, *gen)),
+            Primitives::Array(items) => Primitives::Array(items.iter().map(|item| item.map_refs(f)).collect()),
+            Primitives::Dict(dict) => Primitives::Dict(map_dict_refs(dict, f)),
+            Primitives::Stream(stream) => Primitives::Stream(PdfStream {
+                dict: map_dict_refs(&stream.dict, f),
+                data: stream.data.clone(),
+            }),
+            _ => self.clone(),
+        }
+    }
+}
+
+fn map_dict_refs<F: Fn(Ref) -> Primitives>(dict: &Dictionary, f: &F) -> Dictionary {
+    dict.iter().map(|(key, value)| (key.clone(), value.map_refs(f))).collect()
+}
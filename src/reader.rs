@@ -1,58 +1,140 @@
 use std::fs::File;
-
-use anyhow::Result;
+use std::path::Path;
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use crate::document::PdfDocument;
-use crate::stream::{Stream, ReadSeek};
+use crate::stream::{Stream, ReadSeek, Source, FileSource};
+use crate::xref::XRef;
+use crate::linearization::Linearization;
 use crate::error::Error;
 use crate::utils::is_whitespace;
 
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
 impl PdfDocument {
-    pub fn load_data(data: Vec<u8>) {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<PdfDocument, Error> {
+        PdfDocument::load_file(File::open(path)?)
     }
 
-    pub fn load_file(file: File) {
-        let stream = Stream::from_file(&file);
-        let start_xref = {
-            let mut reader = Reader { stream: stream };
-            reader.parse_startxref()
-        };
+    pub fn load_data(data: Vec<u8>) -> Result<PdfDocument, Error> {
+        PdfDocument::load(Stream::from_bytes(data))
+    }
+
+    pub fn load_file(file: File) -> Result<PdfDocument, Error> {
+        PdfDocument::load(Stream::new(FileSource::new(file)?)?)
+    }
+
+    #[cfg(feature = "mmap")]
+    pub fn load_mmap(file: &File) -> Result<PdfDocument, Error> {
+        PdfDocument::load(Stream::from_mmap(file)?)
+    }
 
-        // let xref = Xref::parse(stream, start_xref);
+    // Loads a document from any shareable source, e.g. a `ChunkedSource`.
+    pub fn load<T: Source+'static>(stream: Stream<T>) -> Result<PdfDocument, Error> {
+        let mut reader = Reader::new(stream.into_boxed());
+        let version = reader.parse_header();
+        let xref = reader.parse()?;
+
+        Ok(PdfDocument {
+            xref: xref,
+            linearization: reader.linearization,
+            version: version,
+            page_refs: RwLock::new(None),
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        })
     }
 }
 
 struct Reader<T> {
     stream: Stream<T>,
+    linearization: Option<Linearization>,
 }
 
-impl<T: ReadSeek+std::fmt::Debug> Reader<T> {
-    fn parse(&mut self) -> String {
-        // 1. header
-        self.stream.reset();
-        // 2. startxref
-        let startxref = self.parse_startxref();
+impl<T: ReadSeek+Clone> Reader<T> {
+    fn new(stream: Stream<T>) -> Self {
+        let mut reader = Reader {
+            stream: stream,
+            linearization: None,
+        };
+        reader.check_linearization();
+        reader
+    }
 
-        // let mut xref = XRef::parse(self.stream.clone(), startxref);
-        // return format!("{:?}", xref);
-        // dbg!(&xref);
+    fn check_linearization(&mut self) {
+        self.linearization = match Linearization::create(self.stream.clone()) {
+            Ok(linearization) => linearization,
+            Err(e) => {
+                eprintln!("The linearization data is not available or unreadable: {}", e);
+                None
+            }
+        };
+    }
 
-        /*
-        let catalog = Catalog::new(&xref);
-        if let Some(version) = catalog.version() {
-            dbg!(version);
+    // Reads the version from the `%PDF-x.y` header, which may be preceded by
+    // some garbage.
+    fn parse_header(&mut self) -> Option<String> {
+        self.stream.reset();
+        if !self.find(b"%PDF-", 1024, false).unwrap_or(false) {
+            return None;
+        }
+        self.stream.skip(5);
+        let mut version = String::new();
+        while let Some(ch) = self.stream.get_byte() {
+            if !(ch.is_ascii_digit() || ch == b'.') || version.len() >= 12 {
+                break;
+            }
+            version.push(ch as char);
         }
-        */
+        if version.is_empty() {
+            return None;
+        }
+        Some(version)
+    }
 
-        String::from("")
+    fn parse(&mut self) -> Result<XRef<T>, Error> {
+        let startxref = self.parse_startxref();
+        let mut xref = XRef::new(self.stream.clone(), startxref, None);
+        if let Err(e) = xref.parse() {
+            // The first page section of a linearized file isn't always right
+            // after the linearization dictionary, so the section the
+            // trailing `startxref` points to is tried next. Incremental
+            // updates link to whichever one was read.
+            let linearization = self.linearization.take().ok_or(e)?;
+            let startxref = self.parse_startxref();
+            self.linearization = Some(linearization);
+            xref = XRef::new(self.stream.clone(), startxref, None);
+            xref.parse()?;
+        }
+        Ok(xref)
     }
 
     fn parse_startxref(&mut self) -> u64 {
         let mut start_xref = 0;
-        /*
-        if self.linearization {
-        } else
-        */
+        if self.linearization.is_some() {
+            // The first page cross-reference section follows the
+            // linearization dictionary, its trailer points to the main one.
+            // Whitespace and comments may come in between.
+            self.stream.reset();
+            if self.find(b"endobj", 1024, false).unwrap_or(false) {
+                self.stream.skip(6);
+                while let Some(ch) = self.stream.peek_byte() {
+                    if ch == b'%' {
+                        while let Some(ch) = self.stream.get_byte() {
+                            if ch == b'\r' || ch == b'\n' {
+                                break;
+                            }
+                        }
+                    } else if is_whitespace(ch) {
+                        self.stream.skip(1);
+                    } else {
+                        break;
+                    }
+                }
+                start_xref = self.stream.pos() - self.stream.start();
+            }
+            return start_xref;
+        }
 
         // Find `startxref`.
         let start_xref_length = "startxref".len() as i64;
@@ -90,7 +172,7 @@ impl<T: ReadSeek+std::fmt::Debug> Reader<T> {
         start_xref
     }
 
-    fn find(&mut self, signature: &[u8], limit: usize, backwords: bool) -> Result<bool> {
+    fn find(&mut self, signature: &[u8], limit: usize, backwords: bool) -> Result<bool, Error> {
         let scan_bytes = self.stream.peek_bytes(limit).ok_or(Error::InvalidFile("startxref not found"))?;
 
         let signature_length = signature.len();
@@ -159,11 +241,24 @@ mod tests {
         for entry in EXAMPLES {
             let file = File::open(entry.0)?;
             let stream = Stream::from_file(&file);
-            let mut reader = Reader { stream: stream };
+            let mut reader = Reader::new(stream);
             let start_xref = reader.parse_startxref();
             assert_eq!(start_xref, entry.1);
         }
 
         Ok(())
     }
+
+    #[test]
+    fn read_start_xref_of_linearized_file() -> io::Result<()> {
+        // Only the first 8355 bytes are linearized, see `linearization.rs`.
+        let data = fs::read("tests/examples/140514041111253731pdf1.pdf")?;
+        let mut reader = Reader::new(Stream::from_bytes(&data[..8355]));
+        assert_eq!(reader.linearization.as_ref().map(|l| l.object_number_first), Some(28));
+        // The first page xref stream, after the padding that follows `endobj`
+        // of the linearization dictionary.
+        assert_eq!(reader.parse_startxref(), 116);
+        assert_eq!(&data[116..124], b"33 0 obj");
+        Ok(())
+    }
 }
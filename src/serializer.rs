@@ -0,0 +1,203 @@
+use std::io::{self, Write};
+
+use crate::primitives::{Dictionary, Name, Primitives, Ref};
+
+// Writes objects in PDF syntax. Output is deterministic: dictionary keys are
+// sorted, so the same object always serializes to the same bytes.
+
+pub fn serialize(obj: &Primitives) -> Vec<u8> {
+    let mut out = Vec::new();
+    // Writing into a `Vec` can't fail.
+    write_object(&mut out, obj).unwrap();
+    out
+}
+
+// Writes `num gen obj ... endobj` followed by a newline.
+pub fn write_indirect_object<W: Write>(out: &mut W, r: Ref, obj: &Primitives) -> io::Result<()> {
+    writeln!(out, "{} {} obj", r.num, r.gen)?;
+    write_object(out, obj)?;
+    out.write_all(b"\nendobj\n")
+}
+
+pub fn write_object<W: Write>(out: &mut W, obj: &Primitives) -> io::Result<()> {
+    match obj {
+        Primitives::Null => out.write_all(b"null"),
+        Primitives::Bool(value) => out.write_all(if *value { b"true" } else { b"false" }),
+        Primitives::Int(num) => write!(out, "{}", num),
+        Primitives::Real(num) => out.write_all(format_real(*num).as_bytes()),
+        Primitives::Str(bytes) => write_literal_string(out, bytes),
+        Primitives::HexStr(units) => write_hex_string(out, units),
+        Primitives::Name(name) => write_name(out, name),
+        Primitives::Array(items) => {
+            out.write_all(b"[")?;
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.write_all(b" ")?;
+                }
+                write_object(out, item)?;
+            }
+            out.write_all(b"]")
+        }
+        Primitives::Dict(dict) => write_dict(out, dict),
+        Primitives::Stream(stream) => {
+            // `/Length` may be an indirect reference or stale, the actual
+            // size of the data always wins.
+            let mut dict = stream.dict.clone();
+            dict.insert(Name::from("Length"), Primitives::Int(stream.data.len() as i64));
+            write_dict(out, &dict)?;
+            out.write_all(b"\nstream\n")?;
+            out.write_all(&stream.data)?;
+            out.write_all(b"\nendstream")
+        }
+        Primitives::Ref(num, gen) => write!(out, "{} {} R", num, gen),
+        Primitives::Cmd(cmd) => out.write_all(cmd),
+        Primitives::EOF => Err(io::Error::new(io::ErrorKind::InvalidInput, "EOF can't be serialized")),
+    }
+}
+
+pub fn write_dict<W: Write>(out: &mut W, dict: &Dictionary) -> io::Result<()> {
+    let mut keys: Vec<&Name> = dict.keys().collect();
+    keys.sort_by(|a, b| a.0.cmp(&b.0));
+
+    out.write_all(b"<<")?;
+    for key in keys {
+        write_name(out, key)?;
+        out.write_all(b" ")?;
+        write_object(out, &dict[key])?;
+    }
+    out.write_all(b">>")
+}
+
+// Real numbers must not use exponent notation, which PDF doesn't have.
+// Six decimal places is more than any reader uses for coordinates.
+pub fn format_real(num: f64) -> String {
+    if !num.is_finite() {
+        return String::from("0");
+    }
+    let mut s = format!("{:.6}", num);
+    while s.ends_with('0') {
+        s.pop();
+    }
+    if s.ends_with('.') {
+        s.pop();
+    }
+    if s == "-0" {
+        s = String::from("0");
+    }
+    s
+}
+
+pub fn write_literal_string<W: Write>(out: &mut W, bytes: &[u8]) -> io::Result<()> {
+    let mut buf = Vec::with_capacity(bytes.len() + 2);
+    buf.push(b'(');
+    for &ch in bytes {
+        match ch {
+            b'(' | b')' | b'\\' => buf.extend_from_slice(&[b'\\', ch]),
+            b'\n' => buf.extend_from_slice(b"\\n"),
+            // A bare CR would be read back as LF.
+            b'\r' => buf.extend_from_slice(b"\\r"),
+            b'\t' => buf.extend_from_slice(b"\\t"),
+            0x08 => buf.extend_from_slice(b"\\b"),
+            0x0c => buf.extend_from_slice(b"\\f"),
+            0x00..=0x1f | 0x7f => buf.extend_from_slice(format!("\\{:03o}", ch).as_bytes()),
+            _ => buf.push(ch),
+        }
+    }
+    buf.push(b')');
+    out.write_all(&buf)
+}
+
+pub fn write_hex_string<W: Write>(out: &mut W, units: &[u16]) -> io::Result<()> {
+    let mut s = String::with_capacity(units.len() * 2 + 2);
+    s.push('<');
+    for &unit in units {
+        if unit > 0xff {
+            s.push_str(&format!("{:04X}", unit));
+        } else {
+            s.push_str(&format!("{:02X}", unit));
+        }
+    }
+    s.push('>');
+    out.write_all(s.as_bytes())
+}
+
+pub fn write_name<W: Write>(out: &mut W, name: &Name) -> io::Result<()> {
+    let mut buf = Vec::with_capacity(name.0.len() + 1);
+    buf.push(b'/');
+    for &ch in &name.0 {
+        // Anything outside the regular printable range, the delimiters and
+        // `#` itself must be written as a two-digit hex code.
+        let regular = (0x21..=0x7e).contains(&ch) && !b"#()<>[]{}/%".contains(&ch);
+        if regular {
+            buf.push(ch);
+        } else {
+            buf.extend_from_slice(format!("#{:02X}", ch).as_bytes());
+        }
+    }
+    out.write_all(&buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::stream::Stream;
+    use crate::primitives::{Name, PdfStream, Primitives};
+    use super::{format_real, serialize};
+
+    fn parse(bytes: Vec<u8>) -> Primitives {
+        let mut parser = Parser::new(Lexer::new(Stream::from_bytes(bytes)), true);
+        parser.get_obj().unwrap()
+    }
+
+    #[test]
+    fn write_simple_objects() {
+        assert_eq!(serialize(&Primitives::Null), b"null");
+        assert_eq!(serialize(&Primitives::Bool(true)), b"true");
+        assert_eq!(serialize(&Primitives::Int(-42)), b"-42");
+        assert_eq!(serialize(&Primitives::Real(0.5)), b"0.5");
+        assert_eq!(serialize(&Primitives::Ref(12, 0)), b"12 0 R");
+        assert_eq!(serialize(&Primitives::name(b"A B#/".to_vec())), b"/A#20B#23#2F");
+        assert_eq!(serialize(&Primitives::Str(b"a(b)\\\r".to_vec())), b"(a\\(b\\)\\\\\\r)");
+        assert_eq!(serialize(&Primitives::HexStr(vec![0x0a, 0xff])), b"<0AFF>");
+    }
+
+    #[test]
+    fn write_reals_without_exponent() {
+        assert_eq!(format_real(1e-7), "0");
+        assert_eq!(format_real(1.5e20), "150000000000000000000");
+        assert_eq!(format_real(-0.000001), "-0.000001");
+        assert_eq!(format_real(-0.0000001), "0");
+        assert_eq!(format_real(3.14159265), "3.141593");
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut dict = HashMap::new();
+        dict.insert(Name::from("Type"), Primitives::name(b"Test".to_vec()));
+        dict.insert(Name::from("Weird Key"), Primitives::Array(vec![
+            Primitives::Int(1),
+            Primitives::Real(-2.25),
+            Primitives::Str(b"(nested) \\ \x01 strings\n".to_vec()),
+            Primitives::HexStr(vec![0x00, 0x7f]),
+            Primitives::Ref(3, 1),
+            Primitives::Null,
+            Primitives::Bool(false),
+        ]));
+        let dict = Primitives::Dict(dict);
+        assert_eq!(parse(serialize(&dict)), dict);
+
+        let mut stream_dict = HashMap::new();
+        stream_dict.insert(Name::from("Length"), Primitives::Ref(9, 0));
+        let stream = Primitives::Stream(PdfStream::new(stream_dict, b"BT /F1 12 Tf (endstream?) Tj ET".to_vec()));
+        match parse(serialize(&stream)) {
+            Primitives::Stream(parsed) => {
+                assert_eq!(&parsed.data[..], b"BT /F1 12 Tf (endstream?) Tj ET");
+                assert_eq!(parsed.get("Length"), Some(&Primitives::Int(31)));
+            }
+            other => panic!("expected a stream, got {:?}", other),
+        }
+    }
+}
@@ -1,19 +1,137 @@
 use std::io::Cursor;
-use std::io::{Read, Seek};
+use std::io::{self, Read, Seek};
 use std::io::SeekFrom;
 use std::fs::File;
+use std::sync::Arc;
+
+use crate::cache::{BlockCache, PAGE_SIZE};
 
 pub trait ReadSeek: Read+Seek {}
-impl<'a> ReadSeek for &'a File {}
-impl<'a> ReadSeek for Cursor<&'a Vec<u8>> {}
+impl<T: Read+Seek> ReadSeek for T {}
+
+// A source that can be shared between threads. Every clone has its own read
+// position, so streams created from clones never disturb each other.
+pub trait Source: ReadSeek+Send+Sync {
+    fn box_clone(&self) -> Box<dyn Source>;
+}
+
+impl<T: ReadSeek+Clone+Send+Sync+'static> Source for T {
+    fn box_clone(&self) -> Box<dyn Source> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn Source> {
+    fn clone(&self) -> Self {
+        (**self).box_clone()
+    }
+}
+
+// A file read with positioned reads (`pread` on Unix), so clones can be used
+// from several threads at once without sharing a file cursor.
+#[derive(Clone, Debug)]
+pub struct FileSource {
+    file: Arc<File>,
+    pos: u64,
+    len: u64,
+}
+
+impl FileSource {
+    pub fn new(file: File) -> io::Result<Self> {
+        let len = file.metadata()?.len();
+        Ok(FileSource {
+            file: Arc::new(file),
+            pos: 0,
+            len: len,
+        })
+    }
+}
 
-#[derive(Clone, Copy)]
+impl Read for FileSource {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        #[cfg(unix)]
+        let size = std::os::unix::fs::FileExt::read_at(&*self.file, buf, self.pos)?;
+        #[cfg(windows)]
+        let size = std::os::windows::fs::FileExt::seek_read(&*self.file, buf, self.pos)?;
+        self.pos += size as u64;
+        Ok(size)
+    }
+}
+
+impl Seek for FileSource {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(n) => n as i64,
+            SeekFrom::End(n) => self.len as i64 + n,
+            SeekFrom::Current(n) => self.pos as i64 + n,
+        };
+        if pos < 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "seek before the start of the file"));
+        }
+        self.pos = pos as u64;
+        Ok(self.pos)
+    }
+}
+
+#[derive(Clone)]
 #[derive(Debug)]
 pub struct Stream<T> {
     source: T,
     start: u64,
     pos: u64,
     end: u64,
+    cache: BlockCache,
+}
+
+impl<T: ReadSeek> Stream<T> {
+    // Wraps any seekable reader. The length is taken from the end of the
+    // source, so the reader should not grow while the stream is in use.
+    pub fn new(mut source: T) -> io::Result<Self> {
+        let end = source.seek(SeekFrom::End(0))?;
+        source.seek(SeekFrom::Start(0))?;
+        Ok(Stream {
+            source: source,
+            start: 0,
+            pos: 0,
+            end: end,
+            cache: BlockCache::default(),
+        })
+    }
+}
+
+impl<T: Source+'static> Stream<T> {
+    // Erases the source type, e.g. to keep streams over different kinds of
+    // sources in one place.
+    pub fn into_boxed(self) -> Stream<Box<dyn Source>> {
+        Stream {
+            source: Box::new(self.source),
+            start: self.start,
+            pos: self.pos,
+            end: self.end,
+            cache: self.cache,
+        }
+    }
+}
+
+impl<T: ReadSeek+Clone> Stream<T> {
+    pub fn new_sub(&self, start: u64) -> Self {
+        self.make_sub_stream(start, self.end - start.min(self.end))
+    }
+
+    // Creates a stream over `[start, start + length)` sharing the same source.
+    // For in-memory and memory-mapped sources this is just a reference count
+    // bump; no bytes are copied.
+    pub fn make_sub_stream(&self, start: u64, length: u64) -> Self {
+        let start = start.min(self.end);
+        let end = start.saturating_add(length).min(self.end);
+        Stream {
+            source: self.source.clone(),
+            start: start,
+            pos: start,
+            end: end,
+            cache: self.cache.clone(),
+        }
+    }
 }
 
 impl<'a> Stream<Cursor<&'a Vec<u8>>> {
@@ -28,18 +146,34 @@ impl<'a> Stream<Cursor<&'a Vec<u8>>> {
             start: 0,
             pos: 0,
             end: source.len() as u64,
+            cache: BlockCache::default(),
         }
     }
-    pub fn new_sub(&self, start: u64) -> Self {
+}
+
+impl Stream<Cursor<Arc<[u8]>>> {
+    // Takes ownership of the data. Cloning the stream or creating sub-streams
+    // shares the same buffer.
+    pub fn from_bytes<B: Into<Arc<[u8]>>>(bytes: B) -> Self {
+        let bytes = bytes.into();
+        let end = bytes.len() as u64;
         Stream {
-            source: self.source.clone(),
-            start: start,
-            pos: start,
-            end: self.len(),
+            source: Cursor::new(bytes),
+            start: 0,
+            pos: 0,
+            end: end,
+            cache: BlockCache::default(),
         }
     }
 }
 
+impl<B: AsRef<[u8]>> Stream<Cursor<B>> {
+    // The bytes between `start` and `end`, borrowed from the underlying buffer.
+    pub fn as_slice(&self) -> &[u8] {
+        &self.source.get_ref().as_ref()[self.start as usize..self.end as usize]
+    }
+}
+
 impl<'a> Stream<&'a File> {
     pub fn from_file(source: &'a File) -> Self {
         Stream {
@@ -47,21 +181,40 @@ impl<'a> Stream<&'a File> {
             start: 0,
             pos: 0,
             end: source.metadata().unwrap().len(),
+            cache: BlockCache::default(),
         }
     }
+}
 
-    pub fn new_sub(&self, start: u64) -> Self {
-        Stream {
-            source: self.source,
-            start: start,
-            pos: start,
-            end: self.len(),
-        }
+#[cfg(feature = "mmap")]
+#[derive(Clone, Debug)]
+pub struct MmapSource(Arc<memmap2::Mmap>);
+
+#[cfg(feature = "mmap")]
+impl AsRef<[u8]> for MmapSource {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
     }
+}
 
+#[cfg(feature = "mmap")]
+impl Stream<Cursor<MmapSource>> {
+    // Maps the whole file into memory. The file must not be truncated or
+    // modified by another process while the mapping is alive.
+    pub fn from_mmap(file: &File) -> io::Result<Self> {
+        let map = unsafe { memmap2::Mmap::map(file)? };
+        let end = map.len() as u64;
+        Ok(Stream {
+            source: Cursor::new(MmapSource(Arc::new(map))),
+            start: 0,
+            pos: 0,
+            end: end,
+            cache: BlockCache::default(),
+        })
+    }
 }
 
-impl<'a, T: ReadSeek> Stream<T> {
+impl<T: ReadSeek> Stream<T> {
 
     pub fn len(&self) -> u64 {
         self.end - self.start
@@ -97,61 +250,44 @@ impl<'a, T: ReadSeek> Stream<T> {
         if self.pos >= self.end {
             return None;
         }
-        let mut byte = [0_u8; 1];
-        if let Ok(size) = self.source.read(&mut byte) {
-            self.rollback_pos();
-            if size > 0 {
-                return Some(byte[0]);
-            }
-        }
-        None
+        let page = self.cache.page(&mut self.source, self.pos / PAGE_SIZE).ok()?;
+        page.get((self.pos % PAGE_SIZE) as usize).copied()
     }
 
     pub fn peek_bytes(&mut self, len: usize) -> Option<Vec<u8>> {
-        let pos = self.pos;
-        let str_end = self.end;
-        let mut end = pos + len as u64;
-        if end > str_end {
-            end = str_end;
+        let end = self.end.min(self.pos + len as u64);
+        if self.pos >= end {
+            return None;
         }
-        let length = end - pos;
-        let mut bytes = vec![0_u8; length as usize];
-        if let Ok(size) = self.source.read(&mut bytes) {
-            self.rollback_pos();
-            if size > 0 {
-                return Some(bytes);
-            }
+        let mut bytes = vec![0_u8; (end - self.pos) as usize];
+        let size = self.cache.read_at(&mut self.source, self.pos, &mut bytes).ok()?;
+        if size == 0 {
+            return None;
         }
-        None
+        bytes.truncate(size);
+        Some(bytes)
+    }
+
+    // Number of pages kept by the read cache, see `BlockCache`.
+    pub fn set_cache_capacity(&mut self, pages: usize) {
+        self.cache.set_capacity(pages);
     }
 
     pub fn get_byte_range(&mut self, begin: u64, end: u64) -> Option<Vec<u8>> {
-        let (begin, end) = {
-            let mut b = begin;
-            if begin < 0 {
-                b = 0;
-            }
-            let mut e = end;
-            if end > self.end {
-                e = self.end;
-            }
-            (b, e)
-        };
-    
+        let end = end.min(self.end);
+        if begin >= end {
+            return None;
+        }
+
         let length = end - begin;
         let mut bytes = vec![0_u8; length as usize];
-        if let Ok(size) = self.source.read(&mut bytes) {
-            if size > 0 {
-                return Some(bytes);
-            }
+        let _ = self.source.seek(SeekFrom::Start(begin));
+        if self.source.read_exact(&mut bytes).is_ok() {
+            return Some(bytes);
         }
         None
     }
 
-    pub fn rollback_pos(&mut self) {
-        self.set_pos(self.pos);
-    }
-
     pub fn start(&self) -> u64 {
         self.start
     }
@@ -166,33 +302,85 @@ impl<'a, T: ReadSeek> Stream<T> {
 
     pub fn set_pos(&mut self, pos: u64) {
         self.pos = pos;
-        let _ = self.source.seek(SeekFrom::Start(pos));
     }
 
     pub fn seek_pos(&mut self, n: i64) {
         let pos = self.pos as i64 + n;
         self.pos = pos as u64;
-        let _ = self.source.seek(SeekFrom::Current(n));
     }
 
     pub fn skip(&mut self, n: i64) {
-        let pos = self.pos as i64 + n;
-        self.pos = pos as u64;
-        let _ = self.source.seek(SeekFrom::Current(n));
+        self.seek_pos(n);
     }
 
     pub fn reset(&mut self) {
         self.pos = self.start;
-        let _ = self.source.seek(SeekFrom::Start(self.start as u64));
     }
 
     pub fn move_start(&mut self) {
         self.start = self.pos;
     }
-
-    //pub fn make_substream(&self, start: u64 /*, length: u64, dict: Primitives::Dict*/) -> Stream {
-    //    self.new_sub(start)
-    //}
 }
 
 
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Cursor};
+    use std::fs::{self, File};
+    use std::sync::Arc;
+
+    use super::Stream;
+
+    #[test]
+    fn read_from_any_source() -> io::Result<()> {
+        let data = fs::read("tests/examples/sample.pdf")?;
+        let file = File::open("tests/examples/sample.pdf")?;
+
+        let mut from_file = Stream::from_file(&file);
+        let mut from_bytes = Stream::from_bytes(data.clone());
+        let mut from_reader = Stream::new(Cursor::new(data.clone()))?;
+
+        assert_eq!(from_file.get_bytes(8).unwrap(), b"%PDF-1.3");
+        assert_eq!(from_bytes.get_bytes(8).unwrap(), b"%PDF-1.3");
+        assert_eq!(from_reader.get_bytes(8).unwrap(), b"%PDF-1.3");
+        assert_eq!(from_reader.len(), data.len() as u64);
+        Ok(())
+    }
+
+    #[test]
+    fn sub_streams_share_the_source() {
+        let data: Arc<[u8]> = Arc::from(&b"0 obj << /A 1 >> endobj"[..]);
+        let stream = Stream::from_bytes(data.clone());
+
+        let mut sub = stream.make_sub_stream(6, 10);
+        assert_eq!(sub.as_slice(), b"<< /A 1 >>");
+        assert_eq!(sub.as_slice().as_ptr(), data[6..].as_ptr());
+        assert_eq!(sub.get_bytes(100).unwrap(), b"<< /A 1 >>");
+        assert_eq!(sub.get_byte(), None);
+
+        let mut rest = stream.new_sub(17);
+        assert_eq!(rest.get_bytes(6).unwrap(), b"endobj");
+    }
+
+    #[test]
+    fn sub_streams_of_a_file_do_not_disturb_each_other() -> io::Result<()> {
+        let file = File::open("tests/examples/sample.pdf")?;
+        let mut stream = Stream::from_file(&file);
+        let mut sub = stream.new_sub(5);
+
+        assert_eq!(stream.get_byte(), Some(b'%'));
+        assert_eq!(sub.get_byte(), Some(b'1'));
+        assert_eq!(stream.get_byte(), Some(b'P'));
+        Ok(())
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn read_from_mmap() -> io::Result<()> {
+        let file = File::open("tests/examples/sample.pdf")?;
+        let stream = Stream::from_mmap(&file)?;
+        assert_eq!(&stream.make_sub_stream(0, 8).as_slice(), b"%PDF-1.3");
+        Ok(())
+    }
+}
@@ -3,3 +3,50 @@ pub fn is_whitespace(ch: u8) -> bool {
     ch == 0x20 || ch == 0x09 || ch == 0x0d || ch == 0x0a
 }
 
+
+// PDFDocEncoding differs from Latin-1 in these two ranges.
+const PDF_DOC_18: [char; 8] = ['\u{02d8}', '\u{02c7}', '\u{02c6}', '\u{02d9}', '\u{02dd}', '\u{02db}', '\u{02da}', '\u{02dc}'];
+const PDF_DOC_80: [char; 33] = [
+    '\u{2022}', '\u{2020}', '\u{2021}', '\u{2026}', '\u{2014}', '\u{2013}', '\u{0192}', '\u{2044}',
+    '\u{2039}', '\u{203a}', '\u{2212}', '\u{2030}', '\u{201e}', '\u{201c}', '\u{201d}', '\u{2018}',
+    '\u{2019}', '\u{201a}', '\u{2122}', '\u{fb01}', '\u{fb02}', '\u{0141}', '\u{0152}', '\u{0160}',
+    '\u{0178}', '\u{017d}', '\u{0131}', '\u{0142}', '\u{0153}', '\u{0161}', '\u{017e}', '\u{fffd}',
+    '\u{20ac}',
+];
+
+// Decodes a text string: UTF-16BE or UTF-8 with a byte order mark,
+// PDFDocEncoding otherwise.
+pub fn decode_text(bytes: &[u8]) -> String {
+    if let Some(rest) = bytes.strip_prefix(&[0xfe, 0xff]) {
+        let units: Vec<u16> = rest.chunks_exact(2).map(|u| u16::from_be_bytes([u[0], u[1]])).collect();
+        return String::from_utf16_lossy(&units);
+    }
+    if let Some(rest) = bytes.strip_prefix(&[0xef, 0xbb, 0xbf]) {
+        return String::from_utf8_lossy(rest).into_owned();
+    }
+    bytes
+        .iter()
+        .map(|&b| match b {
+            0x18..=0x1f => PDF_DOC_18[(b - 0x18) as usize],
+            0x80..=0xa0 => PDF_DOC_80[(b - 0x80) as usize],
+            _ => b as char,
+        })
+        .collect()
+}
+
+// Encodes a text string, as PDFDocEncoding when that is lossless and as
+// UTF-16BE otherwise.
+pub fn encode_text(text: &str) -> Vec<u8> {
+    let latin = text.chars().all(|c| {
+        let c = c as u32;
+        (0x20..0x7f).contains(&c) || c == 0x09 || c == 0x0a || c == 0x0d || (0xa1..=0xff).contains(&c)
+    });
+    if latin {
+        return text.chars().map(|c| c as u8).collect();
+    }
+    let mut bytes = vec![0xfe, 0xff];
+    for unit in text.encode_utf16() {
+        bytes.extend_from_slice(&unit.to_be_bytes());
+    }
+    bytes
+}
@@ -0,0 +1,681 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::document::PdfDocument;
+use crate::xref::Change;
+use crate::primitives::{Dictionary, Name, PdfStream, Primitives, Ref};
+use crate::serializer::{write_dict, write_indirect_object, write_object};
+use crate::filters::{filter_names, flate_encode};
+use crate::error::Error;
+
+#[derive(Debug, Clone)]
+pub struct SaveOptions {
+    // Write a cross-reference stream (PDF 1.5) instead of a classic table.
+    pub xref_stream: bool,
+    // Pack non-stream objects into object streams. Implies `xref_stream`,
+    // since compressed objects can only be addressed from an xref stream.
+    pub object_streams: bool,
+    // Flate-compress streams that don't have a filter yet.
+    pub compress_streams: bool,
+    pub objects_per_stream: usize,
+}
+
+impl Default for SaveOptions {
+    fn default() -> Self {
+        SaveOptions {
+            xref_stream: false,
+            object_streams: false,
+            compress_streams: false,
+            objects_per_stream: 100,
+        }
+    }
+}
+
+impl SaveOptions {
+    fn use_xref_stream(&self) -> bool {
+        self.xref_stream || self.object_streams
+    }
+}
+
+// Trailer keys that are specific to one cross-reference section.
+const UPDATE_DROPPED_KEYS: [&[u8]; 11] = [
+    b"Prev", b"XRefStm", b"Size", b"ID", b"Type", b"W", b"Index", b"Filter", b"DecodeParms", b"Length", b"DL",
+];
+
+// Where an object ended up in the output.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Location {
+    // The next free object number, free entries form a linked list.
+    Free(u32),
+    Offset(u64),
+    // Object stream number and index within it.
+    Compressed(u32, usize),
+}
+
+impl PdfDocument {
+    // Writes a complete new file. Only objects reachable from the trailer's
+    // /Root and /Info are kept, renumbered from 1 with generation 0.
+    pub fn save<P: AsRef<Path>>(&self, path: P, options: SaveOptions) -> Result<(), Error> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write_to(&mut out, &options)?;
+        out.flush()?;
+        Ok(())
+    }
+
+    pub fn write_to<W: Write>(&self, out: W, options: &SaveOptions) -> Result<(), Error> {
+        let (objects, renumber) = self.reachable_objects()?;
+        let map = |r: Ref| match renumber.get(&r) {
+            Some(&num) => Primitives::Ref(num, 0),
+            // Dangling references are read as null anyway.
+            None => Primitives::Null,
+        };
+
+        let mut out = CountingWriter::new(out);
+        let version = self.version().unwrap_or("1.4");
+        let version = if options.use_xref_stream() && version_lower(version, "1.5") { "1.5" } else { version };
+        write_header(&mut out, version)?;
+
+        let objects: Vec<Primitives> = objects
+            .iter()
+            .map(|obj| {
+                let obj = obj.map_refs(&map);
+                if options.compress_streams { compress(obj) } else { obj }
+            })
+            .collect();
+
+        let mut locations = vec![Location::Free(0); objects.len() + 1];
+        let mut next_num = objects.len() as u32 + 1;
+        let mut packed = Vec::new();
+        for (i, obj) in objects.iter().enumerate() {
+            let num = i as u32 + 1;
+            if options.object_streams && !matches!(obj, Primitives::Stream(_)) {
+                packed.push(num);
+                continue;
+            }
+            locations[num as usize] = Location::Offset(out.offset);
+            write_indirect_object(&mut out, Ref::new(num, 0), obj)?;
+        }
+
+        for group in packed.chunks(options.objects_per_stream.max(1)) {
+            let stream_num = next_num;
+            next_num += 1;
+            let members: Vec<(u32, &Primitives)> = group.iter().map(|&num| (num, &objects[num as usize - 1])).collect();
+            let stream = object_stream(&members)?;
+            for (index, &num) in group.iter().enumerate() {
+                locations[num as usize] = Location::Compressed(stream_num, index);
+            }
+            locations.push(Location::Offset(out.offset));
+            write_indirect_object(&mut out, Ref::new(stream_num, 0), &stream)?;
+        }
+
+        let mut trailer = Dictionary::new();
+        for key in ["Root", "Info"].iter() {
+            if let Some(value) = self.trailer().get(key.as_bytes()) {
+                trailer.insert(Name::from(key), value.map_refs(&map));
+            }
+        }
+        // The digest covers everything written so far, so it changes with
+        // the content like the specification recommends.
+        let id = Primitives::HexStr(out.digest().iter().map(|&b| b as u16).collect());
+        trailer.insert(Name::from("ID"), Primitives::Array(vec![id.clone(), id]));
+
+        let entries: Vec<(Ref, Location)> = locations
+            .into_iter()
+            .enumerate()
+            .map(|(num, location)| (Ref::new(num as u32, if num == 0 { 65535 } else { 0 }), location))
+            .collect();
+        if options.use_xref_stream() {
+            write_xref_stream(&mut out, &entries, next_num, trailer)?;
+        } else {
+            write_xref_table(&mut out, &entries, next_num, trailer)?;
+        }
+        Ok(())
+    }
+
+    // Writes the original file unchanged followed by an update section with
+    // the added and replaced objects, so existing signatures stay valid.
+    pub fn save_incremental<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        // The update is built in memory first: `path` may well be the file
+        // the document is still reading from.
+        let mut data = Vec::new();
+        self.write_incremental(&mut data)?;
+        fs::write(path, data)?;
+        Ok(())
+    }
+
+    pub fn write_incremental<W: Write>(&self, mut out: W) -> Result<(), Error> {
+        let mut stream = self.xref.stream().clone();
+        let original = stream.get_byte_range(0, stream.end()).unwrap_or_default();
+        out.write_all(&original)?;
+        if !self.is_modified() {
+            return Ok(());
+        }
+        let mut offset = original.len() as u64;
+        if !original.ends_with(b"\n") && !original.ends_with(b"\r") {
+            out.write_all(b"\n")?;
+            offset += 1;
+        }
+        self.write_update(&mut CountingWriter::with_offset(out, offset))
+    }
+
+    // Writes only the update section, for appending to the original file.
+    fn write_update<W: Write>(&self, out: &mut CountingWriter<W>) -> Result<(), Error> {
+        let mut entries = Vec::new();
+        let mut deleted = Vec::new();
+        for (r, change) in self.xref.changes() {
+            match change {
+                Change::Modified(obj) => {
+                    entries.push((r, Location::Offset(out.offset)));
+                    write_indirect_object(out, r, obj)?;
+                }
+                Change::Deleted => deleted.push(r),
+            }
+        }
+        if !deleted.is_empty() {
+            // Link the freed numbers into a list starting at object 0.
+            entries.push((Ref::new(0, 65535), Location::Free(deleted[0].num)));
+            for (i, r) in deleted.iter().enumerate() {
+                let next = deleted.get(i + 1).map_or(0, |next| next.num);
+                entries.push((*r, Location::Free(next)));
+            }
+            entries.sort_by_key(|(r, _)| r.num);
+        }
+
+        // Keep what the previous trailer says about the document, but none
+        // of the keys that describe the previous section itself.
+        let mut trailer: Dictionary = self
+            .trailer()
+            .iter()
+            .filter(|(key, _)| !UPDATE_DROPPED_KEYS.contains(&&key.0[..]))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        trailer.insert(Name::from("Prev"), Primitives::Int(self.xref.startxref() as i64));
+        // The first identifier is permanent, the second one changes with
+        // every update.
+        let new_id = Primitives::HexStr(out.digest().iter().map(|&b| b as u16).collect());
+        let first_id = self
+            .trailer()
+            .get(&b"ID"[..])
+            .and_then(|id| id.get_array())
+            .and_then(|id| id.first().cloned())
+            .unwrap_or_else(|| new_id.clone());
+        trailer.insert(Name::from("ID"), Primitives::Array(vec![first_id, new_id]));
+
+        let size = self.xref.size();
+        if self.xref.is_stream() {
+            write_xref_stream(out, &entries, size, trailer)
+        } else {
+            write_xref_table(out, &entries, size, trailer)
+        }
+    }
+
+    // Objects reachable from the trailer, breadth first so the catalog comes
+    // first, and the new number of every reference that was kept.
+    fn reachable_objects(&self) -> Result<(Vec<Primitives>, HashMap<Ref, u32>), Error> {
+        let mut queue = VecDeque::new();
+        for key in ["Root", "Info"].iter() {
+            if let Some(r) = self.trailer().get(key.as_bytes()).and_then(|r| r.get_ref()) {
+                queue.push_back(r);
+            }
+        }
+
+        let mut visited = HashSet::new();
+        let mut objects = Vec::new();
+        let mut renumber = HashMap::new();
+        while let Some(r) = queue.pop_front() {
+            if !visited.insert(r) {
+                continue;
+            }
+            let obj = self.fetch(r)?;
+            if obj == Primitives::Null {
+                continue;
+            }
+            obj.for_each_ref(&mut |r| {
+                if !visited.contains(&r) {
+                    queue.push_back(r);
+                }
+            });
+            objects.push(obj);
+            renumber.insert(r, objects.len() as u32);
+        }
+        Ok((objects, renumber))
+    }
+}
+
+// A file with an empty catalog and page tree, the starting point for new
+// documents.
+pub(crate) fn empty_file() -> Vec<u8> {
+    let mut pages = Dictionary::new();
+    pages.insert(Name::from("Type"), Primitives::name(b"Pages".to_vec()));
+    pages.insert(Name::from("Kids"), Primitives::Array(Vec::new()));
+    pages.insert(Name::from("Count"), Primitives::Int(0));
+    let mut catalog = Dictionary::new();
+    catalog.insert(Name::from("Type"), Primitives::name(b"Catalog".to_vec()));
+    catalog.insert(Name::from("Pages"), Primitives::Ref(2, 0));
+
+    // Writing into a `Vec` can't fail.
+    let mut out = CountingWriter::new(Vec::new());
+    write_header(&mut out, "1.7").unwrap();
+    let mut entries = vec![(Ref::new(0, 65535), Location::Free(0))];
+    for (num, obj) in [(1, catalog), (2, pages)].iter() {
+        entries.push((Ref::new(*num, 0), Location::Offset(out.offset)));
+        write_indirect_object(&mut out, Ref::new(*num, 0), &Primitives::Dict(obj.clone())).unwrap();
+    }
+    let mut trailer = Dictionary::new();
+    trailer.insert(Name::from("Root"), Primitives::Ref(1, 0));
+    write_xref_table(&mut out, &entries, 3, trailer).unwrap();
+    out.inner
+}
+
+fn version_lower(version: &str, other: &str) -> bool {
+    let parse = |v: &str| -> (u32, u32) {
+        let mut parts = v.split('.').map(|p| p.parse().unwrap_or(0));
+        (parts.next().unwrap_or(0), parts.next().unwrap_or(0))
+    };
+    parse(version) < parse(other)
+}
+
+pub(crate) fn write_header<W: Write>(out: &mut W, version: &str) -> io::Result<()> {
+    // The comment with high-bit bytes marks the file as binary for tools
+    // that sniff the first lines.
+    writeln!(out, "%PDF-{}", version)?;
+    out.write_all(b"%\xe2\xe3\xcf\xd3\n")
+}
+
+fn compress(obj: Primitives) -> Primitives {
+    match obj {
+        Primitives::Stream(stream) if filter_names(&stream.dict).is_empty() => {
+            let mut dict = stream.dict.clone();
+            dict.insert(Name::from("Filter"), Primitives::name(b"FlateDecode".to_vec()));
+            dict.remove(&b"DecodeParms"[..]);
+            Primitives::Stream(PdfStream::new(dict, flate_encode(&stream.data)))
+        }
+        obj => obj,
+    }
+}
+
+fn object_stream(members: &[(u32, &Primitives)]) -> Result<Primitives, Error> {
+    let mut header = Vec::new();
+    let mut body = Vec::new();
+    for &(num, obj) in members {
+        write!(header, "{} {} ", num, body.len())?;
+        write_object(&mut body, obj)?;
+        body.push(b'\n');
+    }
+    let first = header.len();
+    header.extend(body);
+
+    let mut dict = Dictionary::new();
+    dict.insert(Name::from("Type"), Primitives::name(b"ObjStm".to_vec()));
+    dict.insert(Name::from("N"), Primitives::Int(members.len() as i64));
+    dict.insert(Name::from("First"), Primitives::Int(first as i64));
+    dict.insert(Name::from("Filter"), Primitives::name(b"FlateDecode".to_vec()));
+    Ok(Primitives::Stream(PdfStream::new(dict, flate_encode(&header))))
+}
+
+// Splits entries sorted by object number into runs of consecutive numbers,
+// the subsections of a table or the `/Index` pairs of a stream.
+fn subsections(entries: &[(Ref, Location)]) -> Vec<&[(Ref, Location)]> {
+    let mut sections = Vec::new();
+    let mut start = 0;
+    for i in 1..=entries.len() {
+        if i == entries.len() || entries[i].0.num != entries[i - 1].0.num + 1 {
+            sections.push(&entries[start..i]);
+            start = i;
+        }
+    }
+    sections
+}
+
+// Writes a classic table followed by the trailer. `entries` must be sorted by
+// object number and can't contain compressed objects.
+pub(crate) fn write_xref_table<W: Write>(
+    out: &mut CountingWriter<W>,
+    entries: &[(Ref, Location)],
+    size: u32,
+    mut trailer: Dictionary,
+) -> Result<(), Error> {
+    let startxref = out.offset;
+    out.write_all(b"xref\n")?;
+    for section in subsections(entries) {
+        writeln!(out, "{} {}", section[0].0.num, section.len())?;
+        for (r, location) in section {
+            match location {
+                Location::Offset(offset) => write!(out, "{:010} {:05} n\r\n", offset, r.gen)?,
+                Location::Free(next) => write!(out, "{:010} {:05} f\r\n", next, r.gen)?,
+                Location::Compressed(..) => {
+                    return Err(Error::InvalidFile("compressed objects need an xref stream"));
+                }
+            }
+        }
+    }
+    trailer.insert(Name::from("Size"), Primitives::Int(size as i64));
+    out.write_all(b"trailer\n")?;
+    write_dict(out, &trailer)?;
+    write!(out, "\nstartxref\n{}\n%%EOF\n", startxref)?;
+    Ok(())
+}
+
+// Writes the cross-reference stream as object `num`, which also carries the
+// trailer entries. `entries` must be sorted by object number and `num` be
+// larger than all of them.
+pub(crate) fn write_xref_stream<W: Write>(
+    out: &mut CountingWriter<W>,
+    entries: &[(Ref, Location)],
+    num: u32,
+    mut trailer: Dictionary,
+) -> Result<(), Error> {
+    let startxref = out.offset;
+    let mut entries = entries.to_vec();
+    entries.push((Ref::new(num, 0), Location::Offset(startxref)));
+
+    let largest = entries.iter().fold(0, |max, (_, location)| match location {
+        Location::Offset(offset) => max.max(*offset),
+        Location::Compressed(stream, _) => max.max(*stream as u64),
+        Location::Free(next) => max.max(*next as u64),
+    });
+    let width = (1..8).find(|&w| largest < 1 << (8 * w)).unwrap_or(8);
+    // Generations fit in two bytes, indices in object streams may not.
+    let largest_third = entries.iter().fold(0, |max, (r, location)| match location {
+        Location::Compressed(_, i) => max.max(*i as u64),
+        _ => max.max(r.gen as u64),
+    });
+    let third_width = (2..8).find(|&w| largest_third < 1 << (8 * w)).unwrap_or(8);
+
+    let mut data = Vec::with_capacity(entries.len() * (width + third_width + 1));
+    let mut push = |ty: u8, field2: u64, field3: u64| {
+        data.push(ty);
+        data.extend_from_slice(&field2.to_be_bytes()[8 - width..]);
+        data.extend_from_slice(&field3.to_be_bytes()[8 - third_width..]);
+    };
+    let mut index = Vec::new();
+    for section in subsections(&entries) {
+        index.push(Primitives::Int(section[0].0.num as i64));
+        index.push(Primitives::Int(section.len() as i64));
+        for (r, location) in section {
+            match location {
+                Location::Free(next) => push(0, *next as u64, r.gen as u64),
+                Location::Offset(offset) => push(1, *offset, r.gen as u64),
+                Location::Compressed(stream, i) => push(2, *stream as u64, *i as u64),
+            }
+        }
+    }
+
+    trailer.insert(Name::from("Type"), Primitives::name(b"XRef".to_vec()));
+    trailer.insert(Name::from("Size"), Primitives::Int(num as i64 + 1));
+    trailer.insert(Name::from("Index"), Primitives::Array(index));
+    trailer.insert(Name::from("W"), Primitives::Array(vec![
+        Primitives::Int(1),
+        Primitives::Int(width as i64),
+        Primitives::Int(third_width as i64),
+    ]));
+    trailer.insert(Name::from("Filter"), Primitives::name(b"FlateDecode".to_vec()));
+    let stream = Primitives::Stream(PdfStream::new(trailer, flate_encode(&data)));
+    write_indirect_object(out, Ref::new(num, 0), &stream)?;
+    write!(out, "startxref\n{}\n%%EOF\n", startxref)?;
+    Ok(())
+}
+
+// Keeps track of the output position for the cross-reference data and
+// hashes the output for the file identifier.
+pub(crate) struct CountingWriter<W> {
+    inner: W,
+    pub(crate) offset: u64,
+    md5: md5::Context,
+}
+
+impl<W: Write> CountingWriter<W> {
+    pub(crate) fn new(inner: W) -> Self {
+        CountingWriter::with_offset(inner, 0)
+    }
+
+    pub(crate) fn with_offset(inner: W, offset: u64) -> Self {
+        CountingWriter {
+            inner: inner,
+            offset: offset,
+            md5: md5::Context::new(),
+        }
+    }
+
+    pub(crate) fn digest(&self) -> [u8; 16] {
+        self.md5.clone().compute().0
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.md5.consume(&buf[..n]);
+        self.offset += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::document::PdfDocument;
+    use crate::primitives::{Dictionary, Name, Primitives, Ref};
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::stream::Stream;
+    use super::{write_xref_stream, CountingWriter, Location, SaveOptions};
+
+    const EXAMPLES: [&str; 5] = [
+        "tests/examples/dummy.pdf",
+        "tests/examples/sample.pdf",
+        "tests/examples/PDF_sample.pdf",
+        "tests/examples/140514041111253731pdf1.pdf",
+        "tests/examples/7a79c35f7ce0704dec63be82440c8182.pdf",
+    ];
+
+    fn save(doc: &PdfDocument, options: &SaveOptions) -> Vec<u8> {
+        let mut out = Vec::new();
+        doc.write_to(&mut out, options).unwrap();
+        out
+    }
+
+    fn assert_same_pages(a: &PdfDocument, b: &PdfDocument) {
+        assert_eq!(a.page_count().unwrap(), b.page_count().unwrap());
+        for (pa, pb) in a.pages().unwrap().iter().zip(b.pages().unwrap().iter()) {
+            assert_eq!(pa.contents(a).unwrap(), pb.contents(b).unwrap());
+            assert_eq!(pa.media_box(a).unwrap(), pb.media_box(b).unwrap());
+        }
+    }
+
+    #[test]
+    fn save_with_xref_table() {
+        for path in EXAMPLES.iter() {
+            let doc = PdfDocument::open(path).unwrap();
+            let data = save(&doc, &SaveOptions::default());
+            assert!(data.starts_with(b"%PDF-"));
+            let saved = PdfDocument::load_data(data).unwrap();
+            assert_same_pages(&doc, &saved);
+            assert_eq!(saved.catalog_ref().unwrap(), Ref::new(1, 0));
+        }
+    }
+
+    #[test]
+    fn save_with_object_streams() {
+        let options = SaveOptions {
+            object_streams: true,
+            compress_streams: true,
+            objects_per_stream: 3,
+            ..SaveOptions::default()
+        };
+        for path in EXAMPLES.iter() {
+            let doc = PdfDocument::open(path).unwrap();
+            let data = save(&doc, &options);
+            let saved = PdfDocument::load_data(data).unwrap();
+            assert_same_pages(&doc, &saved);
+            assert!(!saved.xref.get_entry(1).unwrap().uncompressed);
+            assert!(saved.version().unwrap() >= "1.5");
+        }
+    }
+
+    #[test]
+    fn write_large_object_stream_indices() {
+        // More objects in one stream than a two byte index can address.
+        let entries = [
+            (Ref::new(0, 65535), Location::Free(0)),
+            (Ref::new(1, 0), Location::Offset(9)),
+            (Ref::new(2, 0), Location::Compressed(1, 70000)),
+        ];
+        let mut data = Vec::new();
+        write_xref_stream(&mut CountingWriter::new(&mut data), &entries, 3, Dictionary::new()).unwrap();
+
+        let mut parser = Parser::new(Lexer::new(Stream::from_bytes(data)), true);
+        for _ in 0..3 {
+            parser.get_obj().unwrap();
+        }
+        let stream = parser.get_obj().unwrap();
+        let stream = stream.get_stream().unwrap();
+        let widths: Vec<i64> = stream.get("W").unwrap().get_array().unwrap().iter().filter_map(|w| w.get_integer()).collect();
+        assert_eq!(widths, [1, 1, 3]);
+        assert_eq!(&stream.decode().unwrap()[10..15], [2, 1, 0x01, 0x11, 0x70]);
+    }
+
+    #[test]
+    fn drop_unreachable_objects() {
+        let mut data = b"%PDF-1.4\n".to_vec();
+        let objects: [&[u8]; 4] = [
+            b"<< /Type /Catalog /Pages 2 0 R >>",
+            b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+            b"<< /Type /Page /Parent 2 0 R /Missing 9 0 R >>",
+            b"(unreachable)",
+        ];
+        let mut offsets = Vec::new();
+        for (i, obj) in objects.iter().enumerate() {
+            offsets.push(data.len());
+            data.extend(format!("{} 0 obj\n", i + 1).into_bytes());
+            data.extend_from_slice(obj);
+            data.extend_from_slice(b"\nendobj\n");
+        }
+        let startxref = data.len();
+        data.extend(b"xref\n0 5\n0000000000 65535 f\r\n".iter());
+        for offset in offsets {
+            data.extend(format!("{:010} 00000 n\r\n", offset).into_bytes());
+        }
+        data.extend(format!("trailer\n<< /Size 5 /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", startxref).into_bytes());
+
+        let doc = PdfDocument::load_data(data).unwrap();
+        let saved = PdfDocument::load_data(save(&doc, &SaveOptions::default())).unwrap();
+        assert_eq!(saved.xref.size(), 4);
+        assert_eq!(saved.page_count().unwrap(), 1);
+        assert!(saved.page(0).unwrap().dict.get(&b"Missing"[..]).unwrap().get_ref().is_none());
+        assert!(saved.trailer().get(&b"ID"[..]).is_some());
+    }
+
+    fn update_catalog(doc: &mut PdfDocument) -> Ref {
+        let title = doc.add_object(Primitives::Str(b"Updated".to_vec()));
+        let catalog_ref = doc.catalog_ref().unwrap();
+        let mut catalog = doc.catalog().unwrap();
+        catalog.insert(Name::from("Title"), title.into());
+        doc.replace_object(catalog_ref, Primitives::Dict(catalog)).unwrap();
+        title
+    }
+
+    #[test]
+    fn save_incremental_update() {
+        for path in EXAMPLES.iter() {
+            let original = fs::read(path).unwrap();
+            let mut doc = PdfDocument::load_data(original.clone()).unwrap();
+            let title = update_catalog(&mut doc);
+
+            let mut data = Vec::new();
+            doc.write_incremental(&mut data).unwrap();
+            assert_eq!(&data[..original.len()], &original[..], "{}", path);
+
+            let saved = PdfDocument::load_data(data).unwrap();
+            assert_same_pages(&doc, &saved);
+            let catalog = saved.catalog().unwrap();
+            assert_eq!(catalog.get(&b"Title"[..]).and_then(|t| t.get_ref()), Some(title));
+            assert_eq!(saved.fetch(title).unwrap(), Primitives::Str(b"Updated".to_vec()));
+            let prev = saved.trailer().get(&b"Prev"[..]).and_then(|p| p.get_integer());
+            assert_eq!(prev, Some(doc.xref.startxref() as i64));
+        }
+    }
+
+    #[test]
+    fn save_incremental_update_with_xref_stream() {
+        let doc = PdfDocument::open("tests/examples/sample.pdf").unwrap();
+        let options = SaveOptions { object_streams: true, ..SaveOptions::default() };
+        let original = save(&doc, &options);
+        let mut doc = PdfDocument::load_data(original.clone()).unwrap();
+        let title = update_catalog(&mut doc);
+
+        let mut data = Vec::new();
+        doc.write_incremental(&mut data).unwrap();
+        assert!(data.starts_with(&original));
+        let saved = PdfDocument::load_data(data).unwrap();
+        assert!(saved.xref.is_stream());
+        assert_same_pages(&doc, &saved);
+        assert!(saved.xref.get_entry(title.num).unwrap().uncompressed);
+        assert_eq!(saved.fetch(title).unwrap(), Primitives::Str(b"Updated".to_vec()));
+    }
+
+    #[test]
+    fn save_incremental_update_of_linearized_file() {
+        // The first 8355 bytes are a linearized file whose first page xref
+        // stream starts at 116.
+        let original = fs::read("tests/examples/140514041111253731pdf1.pdf").unwrap()[..8355].to_vec();
+        let mut doc = PdfDocument::load_data(original.clone()).unwrap();
+        let title = update_catalog(&mut doc);
+
+        let mut data = Vec::new();
+        doc.write_incremental(&mut data).unwrap();
+        let saved = PdfDocument::load_data(data).unwrap();
+        assert_eq!(saved.trailer().get(&b"Prev"[..]).and_then(|p| p.get_integer()), Some(116));
+        assert_same_pages(&doc, &saved);
+        assert_eq!(saved.fetch(title).unwrap(), Primitives::Str(b"Updated".to_vec()));
+
+        // With junk in the padding, the section is found through the
+        // trailing `startxref` instead.
+        let mut original = original;
+        original[100..104].copy_from_slice(b"junk");
+        let mut doc = PdfDocument::load_data(original).unwrap();
+        update_catalog(&mut doc);
+        let mut data = Vec::new();
+        doc.write_incremental(&mut data).unwrap();
+        let saved = PdfDocument::load_data(data).unwrap();
+        assert_eq!(saved.trailer().get(&b"Prev"[..]).and_then(|p| p.get_integer()), Some(116));
+    }
+
+    #[test]
+    fn save_incremental_deletion() {
+        let original = fs::read("tests/examples/sample.pdf").unwrap();
+        let mut doc = PdfDocument::load_data(original).unwrap();
+        let title = update_catalog(&mut doc);
+        doc.delete_object(title).unwrap();
+
+        let mut data = Vec::new();
+        doc.write_incremental(&mut data).unwrap();
+        let saved = PdfDocument::load_data(data).unwrap();
+        let entry = saved.xref.get_entry(title.num).unwrap();
+        assert!(entry.free);
+        assert_eq!(entry.gen, 1);
+        assert_eq!(saved.fetch(title).unwrap(), Primitives::Null);
+        assert_same_pages(&doc, &saved);
+
+        // The full writer drops the deleted object along with the reference.
+        let saved = PdfDocument::load_data(save(&doc, &SaveOptions::default())).unwrap();
+        assert_eq!(saved.catalog().unwrap().get(&b"Title"[..]), Some(&Primitives::Null));
+    }
+
+    #[test]
+    fn save_to_file() {
+        let doc = PdfDocument::open("tests/examples/sample.pdf").unwrap();
+        let path = std::env::temp_dir().join("pdf-rs-writer-test.pdf");
+        doc.save(&path, SaveOptions::default()).unwrap();
+        let saved = PdfDocument::open(&path).unwrap();
+        assert_same_pages(&doc, &saved);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,280 @@
+use crate::error::Error;
+
+// A minimal XML reader for the packets found in PDF files (XMP metadata,
+// XFDF). Namespaces aren't resolved, names keep their prefix. Processing
+// instructions, comments and the DOCTYPE are skipped.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct XmlNode {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<XmlNode>,
+    // Character data directly inside this element, entities decoded.
+    pub text: String,
+}
+
+impl XmlNode {
+    pub fn new(name: &str) -> XmlNode {
+        XmlNode {
+            name: name.to_string(),
+            attributes: Vec::new(),
+            children: Vec::new(),
+            text: String::new(),
+        }
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    pub fn child(&self, name: &str) -> Option<&XmlNode> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a XmlNode> + 'a {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+
+    // Depth-first search for the first element called `name`, this one
+    // included.
+    pub fn find(&self, name: &str) -> Option<&XmlNode> {
+        if self.name == name {
+            return Some(self);
+        }
+        self.children.iter().find_map(|c| c.find(name))
+    }
+
+    pub fn write(&self, out: &mut String) {
+        out.push('<');
+        out.push_str(&self.name);
+        for (name, value) in &self.attributes {
+            out.push_str(&format!(" {}=\"{}\"", name, escape(value)));
+        }
+        if self.children.is_empty() && self.text.is_empty() {
+            out.push_str("/>");
+            return;
+        }
+        out.push('>');
+        out.push_str(&escape(&self.text));
+        for child in &self.children {
+            child.write(out);
+        }
+        out.push_str(&format!("</{}>", self.name));
+    }
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = match rest.find(';') {
+            Some(end) => end,
+            None => break,
+        };
+        let entity = &rest[1..end];
+        let decoded = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ if entity.starts_with("#x") || entity.starts_with("#X") => {
+                u32::from_str_radix(&entity[2..], 16).ok().and_then(std::char::from_u32)
+            }
+            _ if entity.starts_with('#') => entity[1..].parse().ok().and_then(std::char::from_u32),
+            _ => None,
+        };
+        match decoded {
+            Some(c) => {
+                result.push(c);
+                rest = &rest[end + 1..];
+            }
+            // Unknown entities are kept as they are.
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+// Parses a document and returns its root element.
+pub(crate) fn parse(data: &[u8]) -> Result<XmlNode, Error> {
+    let text = String::from_utf8_lossy(data);
+    let mut parser = XmlParser { text: &text, pos: 0 };
+    parser.skip_misc();
+    if !parser.text[parser.pos..].starts_with('<') {
+        return Err(Error::InvalidXml("no root element"));
+    }
+    parser.element(0)
+}
+
+const MAX_DEPTH: usize = 256;
+
+struct XmlParser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> XmlParser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    // Skips whitespace, the BOM, comments, processing instructions and the
+    // DOCTYPE in front of an element.
+    fn skip_misc(&mut self) {
+        loop {
+            self.skip_whitespace();
+            let rest = self.rest();
+            let end = if rest.starts_with('\u{feff}') {
+                Some(('\u{feff}'.len_utf8(), ""))
+            } else if rest.starts_with("<?") {
+                rest.find("?>").map(|e| (e, "?>"))
+            } else if rest.starts_with("<!--") {
+                rest.find("-->").map(|e| (e, "-->"))
+            } else if rest.starts_with("<!") {
+                rest.find('>').map(|e| (e, ">"))
+            } else {
+                return;
+            };
+            match end {
+                Some((e, terminator)) => self.pos += e + terminator.len(),
+                None => {
+                    self.pos = self.text.len();
+                    return;
+                }
+            }
+        }
+    }
+
+    fn name(&mut self) -> Result<&'a str, Error> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| c.is_whitespace() || c == '>' || c == '/' || c == '=')
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return Err(Error::InvalidXml("expected a name"));
+        }
+        self.pos += len;
+        Ok(&rest[..len])
+    }
+
+    fn element(&mut self, depth: usize) -> Result<XmlNode, Error> {
+        if depth > MAX_DEPTH {
+            return Err(Error::InvalidXml("elements nested too deeply"));
+        }
+        // Skip '<'.
+        self.pos += 1;
+        let mut node = XmlNode::new(self.name()?);
+        loop {
+            self.skip_whitespace();
+            let rest = self.rest();
+            if rest.starts_with("/>") {
+                self.pos += 2;
+                return Ok(node);
+            }
+            if rest.starts_with('>') {
+                self.pos += 1;
+                break;
+            }
+            let name = self.name()?;
+            self.skip_whitespace();
+            if !self.rest().starts_with('=') {
+                return Err(Error::InvalidXml("expected '=' after attribute name"));
+            }
+            self.pos += 1;
+            self.skip_whitespace();
+            let quote = match self.rest().chars().next() {
+                Some(q) if q == '"' || q == '\'' => q,
+                _ => return Err(Error::InvalidXml("attribute value is not quoted")),
+            };
+            self.pos += 1;
+            let len = self.rest().find(quote).ok_or(Error::InvalidXml("unterminated attribute value"))?;
+            let value = unescape(&self.rest()[..len]);
+            self.pos += len + 1;
+            node.attributes.push((name.to_string(), value));
+        }
+
+        loop {
+            let rest = self.rest();
+            let len = rest.find('<').ok_or(Error::InvalidXml("unterminated element"))?;
+            node.text.push_str(&unescape(&rest[..len]));
+            self.pos += len;
+            let rest = self.rest();
+            if rest.starts_with("</") {
+                self.pos += 2;
+                let name = self.name()?;
+                if name != node.name {
+                    return Err(Error::InvalidXml("mismatched end tag"));
+                }
+                self.skip_whitespace();
+                if !self.rest().starts_with('>') {
+                    return Err(Error::InvalidXml("unterminated end tag"));
+                }
+                self.pos += 1;
+                return Ok(node);
+            } else if rest.starts_with("<![CDATA[") {
+                let end = rest.find("]]>").ok_or(Error::InvalidXml("unterminated CDATA section"))?;
+                node.text.push_str(&rest[9..end]);
+                self.pos += end + 3;
+            } else if rest.starts_with("<?") || rest.starts_with("<!") {
+                self.skip_misc();
+            } else {
+                let child = self.element(depth + 1)?;
+                node.children.push(child);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, XmlNode};
+
+    #[test]
+    fn parse_xml() {
+        let data = br#"<?xml version="1.0"?>
+            <!-- comment -->
+            <a:root x="1 &amp; 2" y='q'>
+                text &lt;here&gt; &#x41;&#66;
+                <child/><child n="2">two<![CDATA[<raw>]]></child>
+            </a:root>"#;
+        let root = parse(data).unwrap();
+        assert_eq!(root.name, "a:root");
+        assert_eq!(root.attribute("x"), Some("1 & 2"));
+        assert_eq!(root.text.trim(), "text <here> AB");
+        assert_eq!(root.children_named("child").count(), 2);
+        assert_eq!(root.children[1].text, "two<raw>");
+
+        let mut out = String::new();
+        root.children[1].write(&mut out);
+        assert_eq!(out, r#"<child n="2">two&lt;raw&gt;</child>"#);
+        assert_eq!(parse(out.as_bytes()).unwrap(), XmlNode { text: "two<raw>".to_string(), ..root.children[1].clone() });
+
+        assert!(parse(b"<a><b></a>").is_err());
+    }
+}
@@ -1,3 +1,6 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::RwLock;
+
 use crate::lexer::Lexer;
 use crate::parser::Parser;
 use crate::stream::{Stream, ReadSeek};
@@ -7,86 +10,155 @@ use crate::error::Error;
 macro_rules! get_integer {
     ($obj:expr) => { $obj.get_integer().ok_or_else(|| Error::ParserError) };
 }
-macro_rules! get_str {
-    ($obj:expr) => { $obj.get_str().ok_or_else(|| Error::ParserError) };
-}
-
-macro_rules! get_cmd {
-    ($obj:expr) => { $obj.get_cmd().ok_or_else(|| Error::ParserError) };
-}
 
 pub struct XRef<T> {
     stream: Stream<T>,
+    // Offset of the newest cross-reference section, where reading starts.
+    startxref: u64,
     startxref_queue: Vec<u64>,
     password: Option<String>,
     table_state: Option<TableState>,
-    entries: Vec<Entry>,
+    entries: Vec<Option<Entry>>,
+    top_dict: Option<Primitives>,
+    // Objects are immutable once parsed, so they are kept around and handed
+    // out as clones. Guarded by a lock so fetching works through `&self`
+    // from any thread.
+    cache: RwLock<HashMap<Ref, Primitives>>,
+    // Objects added, replaced or deleted since loading, by object number.
+    // They take precedence over the file and are what the writers emit.
+    changes: BTreeMap<u32, Change>,
+    // Whether trailer entries such as /Info were changed since loading.
+    trailer_modified: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Modified(Primitives),
+    Deleted,
 }
 
+// Generation numbers stop at 65535, a free entry with that generation is
+// never reused.
+const MAX_GEN: i64 = 65535;
 
-impl<T: ReadSeek> XRef<T> {
+
+impl<T: ReadSeek+Clone> XRef<T> {
     pub fn new(stream: Stream<T>, startxref: u64, password: Option<String>) -> XRef<T> {
         XRef {
             stream: stream,
+            startxref: startxref,
             startxref_queue: vec![startxref],
             password: password,
             table_state: None,
             entries: Vec::new(),
+            top_dict: None,
+            cache: RwLock::new(HashMap::new()),
+            changes: BTreeMap::new(),
+            trailer_modified: false,
         }
     }
     pub fn set_startxref(&mut self, startxref: u64) {
         self.startxref_queue.push(startxref);
     }
 
-    pub fn parse(&mut self) {
-        let trait_dict = self.read_xref();
+    pub fn parse(&mut self) -> Result<(), Error> {
+        self.read_xref()?;
 
-        // TODO: Encrypt
+        let trailer = self.top_dict.as_ref().ok_or(Error::InvalidFile("trailer not found"))?;
+        if trailer.get("Encrypt").is_some() {
+            // TODO: Encrypt
+            let _ = &self.password;
+            return Err(Error::InvalidFile("encrypted documents are not supported"));
+        }
 
-        let root = trait_dict.get("Root");
-        /*
-        if (is_dict(root) && root.has("Page")) {
-            self.root = root;
+        match trailer.get("Root") {
+            Some(Primitives::Ref(..)) => Ok(()),
+            _ => Err(Error::InvalidFile("trailer has no /Root")),
         }
-        */
     }
 
-    fn read_xref(&mut self) {
-        let mut startxref_parsed_cache = vec![0_u64; self.startxref_queue.len()];
+    pub fn trailer(&self) -> Option<&Dictionary> {
+        self.top_dict.as_ref().and_then(|dict| dict.get_dict())
+    }
+
+    pub fn startxref(&self) -> u64 {
+        self.startxref
+    }
 
-        while self.startxref_queue.len() > 0 {
-            let startxref = self.startxref_queue[0];
+    // Whether the newest section is a cross-reference stream rather than a
+    // table.
+    pub fn is_stream(&self) -> bool {
+        self.trailer().and_then(|t| t.get(&b"Type"[..])).and_then(|t| t.get_name()) == Some(b"XRef")
+    }
+
+    pub fn stream(&self) -> &Stream<T> {
+        &self.stream
+    }
+
+    // One past the highest object number known to the cross-reference data.
+    pub fn size(&self) -> u32 {
+        self.entries.len() as u32
+    }
+
+    pub fn get_entry(&self, num: u32) -> Option<&Entry> {
+        self.entries.get(num as usize)?.as_ref()
+    }
+
+    fn read_xref(&mut self) -> Result<(), Error> {
+        let mut startxref_parsed_cache = Vec::new();
+
+        while !self.startxref_queue.is_empty() {
+            let startxref = self.startxref_queue.remove(0);
             if startxref_parsed_cache.contains(&startxref) {
-                println!("read_xref - skipping XRef table since it was already parsed.");
-                self.startxref_queue.remove(0);
+                eprintln!("read_xref - skipping XRef table since it was already parsed.");
                 continue;
             }
             startxref_parsed_cache.push(startxref);
 
-            self.stream.set_pos(startxref + self.stream.start());
-
-            let lexer = Lexer::new(self.stream.clone());
+            let lexer = Lexer::new(self.stream.new_sub(startxref + self.stream.start()));
             let mut parser = Parser::new(lexer, true);
-            let obj = parser.get_obj().unwrap();
-            if obj.is_cmd("xref") {
+            let obj = parser.get_obj()?;
+            let dict = if obj.is_cmd("xref") {
                 // Parse end-of-file XRef
-                let dict = self.process_xreftable(parser);
-                if self.top_dict.is_none() {
-                    self.top_dict = dict;
+                let dict = self.process_xreftable(parser)?;
+
+                // Recursively get other XRefs 'XRefStm', if any
+                if let Some(Primitives::Int(pos)) = dict.get("XRefStm") {
+                    // Hybrid files keep the entries of compressed objects in
+                    // an xref stream next to the table.
+                    self.startxref_queue.push(*pos as u64);
+                }
+                dict
+            } else if obj.is_integer() {
+                // Parse in-stream XRef
+                if !parser.get_obj()?.is_integer() || !parser.get_obj()?.is_cmd("obj") {
+                    return Err(Error::InvalidFile("invalid XRef stream header"));
                 }
+                let obj = parser.get_obj()?;
+                let stream = obj.get_stream().ok_or(Error::InvalidFile("invalid XRef stream"))?;
+                self.process_xrefstream(stream)?;
+                Primitives::Dict(stream.dict.clone())
+            } else {
+                return Err(Error::InvalidFile("invalid XRef stream header"));
+            };
+
+            if self.top_dict.is_none() {
+                self.top_dict = Some(dict.clone());
+            }
+
+            if let Some(Primitives::Int(prev)) = dict.get("Prev") {
+                self.startxref_queue.push(*prev as u64);
+            } else if let Some(Primitives::Ref(num, _)) = dict.get("Prev") {
+                // The spec says Prev must not be a reference, i.e. "/Prev NNN"
+                // This is a fallback for non-compliant PDFs, i.e. "/Prev NNN 0 R"
+                self.startxref_queue.push(*num as u64);
             }
         }
+        Ok(())
     }
 
     fn process_xreftable(&mut self, mut parser: Parser<T>) -> Result<Primitives, Error> {
-        self.table_state = Some(TableState {
-            entry_num: 0,
-            stream_pos: parser.lexer().stream().pos(),
-            parser_buf1: None,
-            parser_buf2: None,
-            first_entry_num: None,
-            entry_count: None,
-        });
+        self.table_state = Some(TableState::from_parser(&parser));
 
         let obj = self.read_xreftable(&mut parser)?;
 
@@ -104,9 +176,12 @@ impl<T: ReadSeek> XRef<T> {
         // The parser goes through the entire stream << ... >> and provides
         // a getter interface for the key-value table
         let dict = parser.get_obj()?;
+        if dict.get_dict().is_none() {
+            return Err(Error::InvalidFile("invalid trailer dictionary"));
+        }
 
         self.table_state = None;
-        return Ok(dict);
+        Ok(dict)
     }
 
     fn read_xreftable(&mut self, parser: &mut Parser<T>) -> Result<Primitives, Error> {
@@ -120,9 +195,7 @@ impl<T: ReadSeek> XRef<T> {
         // trailer
         // ...
 
-         let mut table_state = TableState::from_parser(&parser);
-
-         let mut parser = parser;
+        let mut table_state = TableState::from_parser(parser);
 
         // Outer loop is over subsection headers.
         let mut obj;
@@ -133,14 +206,16 @@ impl<T: ReadSeek> XRef<T> {
                 if obj.is_cmd("trailer") {
                     break;
                 }
-                table_state.set_first_entry_num(get_integer!(obj));
+                table_state.set_first_entry_num(get_integer!(obj)?);
                 let next = parser.get_obj()?;
-                table_state.set_entry_count(get_integer!(next));
+                table_state.set_entry_count(get_integer!(next)?);
             }
 
             let mut first = table_state.first_entry_num.unwrap();
             let count = table_state.entry_count.unwrap();
-
+            if first < 0 || count < 0 {
+                return Err(Error::InvalidFile("invalid XRef table subsection"));
+            }
 
             // Inner loop is over objects themselves
             for i in table_state.entry_num .. count {
@@ -149,8 +224,8 @@ impl<T: ReadSeek> XRef<T> {
                 table_state.parser_buf1 = parser.buf1();
                 table_state.parser_buf2 = parser.buf2();
 
-                let offset = get_integer!(parser.get_obj()?);
-                let gen = get_integer!(parser.get_obj()?);
+                let offset = get_integer!(parser.get_obj()?)?;
+                let gen = get_integer!(parser.get_obj()?)?;
                 let ty = parser.get_obj()?;
 
                 let (free, uncompressed) = {
@@ -159,14 +234,13 @@ impl<T: ReadSeek> XRef<T> {
                     } else if ty.is_cmd("n") {
                         (false, true)
                     } else {
-                        (false, false)
+                        return Err(Error::InvalidFile("invalid XRef table entry"));
                     }
                 };
 
                 let entry = Entry {
                     offset: offset,
                     gen: gen,
-                    ty: ty,
                     free: free,
                     uncompressed: uncompressed,
                 };
@@ -177,12 +251,7 @@ impl<T: ReadSeek> XRef<T> {
                     first = 0;
                 }
 
-                dbg!(&entry);
-                if self.entries.len() > i as usize {
-                    self.entries[(i + first) as usize] = entry;
-                } else {
-                    self.entries.push(entry);
-                }
+                self.set_entry((i + first) as usize, entry);
             }
 
             table_state.entry_num = 0;
@@ -194,14 +263,240 @@ impl<T: ReadSeek> XRef<T> {
         }
 
         // Sanity check: as per spec, first ojbect must be free
-        if self.entries.len() > 1 && !self.entries[0].free {
-            return Err(Error::ParserError);
+        if let Some(Some(first)) = self.entries.first() {
+            if !first.free {
+                return Err(Error::InvalidFile("first XRef entry is not free"));
+            }
         }
 
-        return Ok(obj);
+        Ok(obj)
+    }
+
+    fn process_xrefstream(&mut self, stream: &PdfStream) -> Result<(), Error> {
+        let size = stream.get("Size").and_then(|s| s.get_integer()).unwrap_or(0);
+        let widths: Vec<usize> = match stream.get("W").and_then(|w| w.get_array()) {
+            Some(w) => w.iter().map(|n| n.get_integer().unwrap_or(0).max(0) as usize).collect(),
+            None => return Err(Error::InvalidFile("XRef stream has no /W array")),
+        };
+        if widths.len() != 3 || widths.iter().any(|&w| w > 8) {
+            return Err(Error::InvalidFile("invalid /W array in XRef stream"));
+        }
+        let index: Vec<i64> = match stream.get("Index").and_then(|i| i.get_array()) {
+            Some(index) => index.iter().filter_map(|n| n.get_integer()).collect(),
+            None => vec![0, size],
+        };
+
+        let data = stream.decode()?;
+        let entry_size: usize = widths.iter().sum();
+        let mut entries = data.chunks_exact(entry_size.max(1));
+
+        for range in index.chunks_exact(2) {
+            let (first, count) = (range[0], range[1]);
+            if first < 0 || count < 0 {
+                return Err(Error::InvalidFile("invalid /Index in XRef stream"));
+            }
+            for i in 0..count {
+                let bytes = match entries.next() {
+                    Some(bytes) => bytes,
+                    None => return Err(Error::InvalidFile("XRef stream is truncated")),
+                };
+                let (f1, rest) = bytes.split_at(widths[0]);
+                let (f2, f3) = rest.split_at(widths[1]);
+                // The type defaults to 1 when its field is omitted.
+                let ty = if widths[0] == 0 { 1 } else { read_uint(f1) };
+                let f2 = read_uint(f2) as i64;
+                let f3 = read_uint(f3) as i64;
+
+                let entry = match ty {
+                    0 => Entry { offset: f2, gen: f3, free: true, uncompressed: false },
+                    1 => Entry { offset: f2, gen: f3, free: false, uncompressed: true },
+                    // Compressed: object stream number and index within it.
+                    2 => Entry { offset: f2, gen: f3, free: false, uncompressed: false },
+                    _ => continue,
+                };
+                self.set_entry((first + i) as usize, entry);
+            }
+        }
+        Ok(())
+    }
+
+    // Sections are read newest first, so entries that are already known win.
+    fn set_entry(&mut self, num: usize, entry: Entry) {
+        if self.entries.len() <= num {
+            self.entries.resize(num + 1, None);
+        }
+        if self.entries[num].is_none() {
+            self.entries[num] = Some(entry);
+        }
+    }
+
+    // Stores `obj` under a free object number, reusing deleted numbers with
+    // their next generation before growing the table.
+    pub fn add(&mut self, obj: Primitives) -> Ref {
+        let reusable = self.entries.iter().enumerate().skip(1).find_map(|(num, entry)| match entry {
+            Some(entry) if entry.free && entry.gen < MAX_GEN => Some(num),
+            _ => None,
+        });
+        let num = reusable.unwrap_or_else(|| self.entries.len().max(1));
+        let gen = reusable.map_or(0, |num| self.entries[num].as_ref().unwrap().gen);
+        if self.entries.len() <= num {
+            self.entries.resize(num + 1, None);
+        }
+        self.entries[num] = Some(Entry { offset: 0, gen: gen, free: false, uncompressed: true });
+        self.changes.insert(num as u32, Change::Modified(obj));
+        Ref::new(num as u32, gen as u32)
+    }
+
+    pub fn replace(&mut self, r: Ref, obj: Primitives) -> Result<(), Error> {
+        self.check_in_use(r)?;
+        self.changes.insert(r.num, Change::Modified(obj));
+        Ok(())
+    }
+
+    // Frees the object number. The entry's generation is bumped so stale
+    // references to the old object resolve to null.
+    pub fn delete(&mut self, r: Ref) -> Result<(), Error> {
+        self.check_in_use(r)?;
+        let entry = self.entries[r.num as usize].as_mut().unwrap();
+        *entry = Entry {
+            offset: 0,
+            gen: (entry.generation() + 1).min(MAX_GEN),
+            free: true,
+            uncompressed: false,
+        };
+        self.changes.insert(r.num, Change::Deleted);
+        Ok(())
+    }
+
+    fn check_in_use(&self, r: Ref) -> Result<(), Error> {
+        match self.get_entry(r.num) {
+            Some(entry) if !entry.free && entry.generation() == r.gen as i64 && r.num != 0 => Ok(()),
+            _ => Err(Error::MissingObject(r)),
+        }
+    }
+
+    // The pending changes in object number order, with the reference under
+    // which each one is written: the object's own for modified objects and
+    // the generation for reuse for deleted ones.
+    pub fn changes(&self) -> impl Iterator<Item = (Ref, &Change)> {
+        self.changes.iter().map(move |(&num, change)| {
+            let gen = self.get_entry(num).map_or(0, |e| e.generation());
+            (Ref::new(num, gen as u32), change)
+        })
+    }
+
+    pub fn is_modified(&self) -> bool {
+        !self.changes.is_empty() || self.trailer_modified
+    }
+
+    // Sets or, with `None`, removes a document-level trailer entry like
+    // /Info. The writers carry it into the next trailer.
+    pub fn set_trailer_entry(&mut self, key: &str, value: Option<Primitives>) {
+        let dict = match self.top_dict.as_mut() {
+            Some(Primitives::Dict(dict)) => dict,
+            Some(Primitives::Stream(stream)) => &mut stream.dict,
+            _ => return,
+        };
+        match value {
+            Some(value) => dict.insert(Name::from(key), value),
+            None => dict.remove(key.as_bytes()),
+        };
+        self.trailer_modified = true;
+    }
+
+    pub fn fetch(&self, r: Ref) -> Result<Primitives, Error> {
+        match self.changes.get(&r.num) {
+            Some(Change::Modified(obj)) if self.check_in_use(r).is_ok() => return Ok(obj.clone()),
+            Some(_) => return Ok(Primitives::Null),
+            None => (),
+        }
+        if let Some(obj) = self.cache.read().unwrap().get(&r) {
+            return Ok(obj.clone());
+        }
+
+        let entry = match self.get_entry(r.num) {
+            // Missing and free objects are treated as null, and so are
+            // references to another generation than the one in use.
+            Some(entry) if !entry.free && entry.generation() == r.gen as i64 => entry.clone(),
+            _ => return Ok(Primitives::Null),
+        };
+
+        let obj = if entry.uncompressed {
+            self.fetch_uncompressed(r, &entry)?
+        } else {
+            self.fetch_compressed(r, &entry)?
+        };
+
+        self.cache.write().unwrap().insert(r, obj.clone());
+        Ok(obj)
+    }
+
+    fn fetch_uncompressed(&self, r: Ref, entry: &Entry) -> Result<Primitives, Error> {
+        let lexer = Lexer::new(self.stream.new_sub(entry.offset as u64 + self.stream.start()));
+        let mut parser = Parser::new(lexer, true);
+        let obj1 = parser.get_obj()?;
+        let obj2 = parser.get_obj()?;
+        let obj3 = parser.get_obj()?;
+
+        if obj1.get_integer() != Some(r.num as i64) || !obj2.is_integer() || !obj3.is_cmd("obj") {
+            eprintln!("fetch - bad (uncompressed) XRef entry for {:?}", r);
+            return Err(Error::InvalidFile("bad (uncompressed) XRef entry"));
+        }
+        parser.get_obj()
+    }
+
+    fn fetch_compressed(&self, r: Ref, entry: &Entry) -> Result<Primitives, Error> {
+        let objstm_num = entry.offset as u32;
+        // Object streams can't be stored in object streams. Checking keeps
+        // entries that lead back to their own stream from recursing forever.
+        let nested = self.get_entry(objstm_num).is_some_and(|e| !e.free && !e.uncompressed);
+        if nested && !self.changes.contains_key(&objstm_num) {
+            return Err(Error::InvalidFile("ObjStm stored in an ObjStm"));
+        }
+        let objstm = self.fetch(Ref::new(objstm_num, 0))?;
+        let objstm = objstm.get_stream().ok_or(Error::InvalidFile("bad ObjStm stream"))?;
+
+        let first = objstm.get("First").and_then(|f| f.get_integer()).unwrap_or(-1);
+        let n = objstm.get("N").and_then(|n| n.get_integer()).unwrap_or(-1);
+        if first < 0 || n < 0 {
+            return Err(Error::InvalidFile("invalid first and n parameters for ObjStm stream"));
+        }
+
+        let decoded = objstm.decode()?;
+        // Each pair takes at least four bytes, which bounds a bogus /N.
+        let mut nums = Vec::with_capacity((n as usize).min(decoded.len() / 4));
+        let data = Stream::from_bytes(decoded);
+        let mut parser = Parser::new(Lexer::new(data.clone()), false);
+        // read the object numbers to populate cache
+        for _ in 0..n {
+            let num = get_integer!(parser.get_obj()?)?;
+            let offset = get_integer!(parser.get_obj()?)?;
+            nums.push((num as u32, offset as u64));
+        }
+
+        let mut found = None;
+        let mut cache = self.cache.write().unwrap();
+        for (i, &(num, offset)) in nums.iter().enumerate() {
+            let mut parser = Parser::new(Lexer::new(data.new_sub(first as u64 + offset)), false);
+            let obj = parser.get_obj()?;
+            if num == r.num {
+                found = Some(obj.clone());
+            }
+            // Only cache objects that the cross-reference data says live in
+            // this object stream; later updates may have replaced others.
+            let owned = self.get_entry(num).is_some_and(|e| !e.free && !e.uncompressed && e.offset as u32 == objstm_num && e.gen as usize == i);
+            if owned {
+                cache.entry(Ref::new(num, 0)).or_insert(obj);
+            }
+        }
+        found.ok_or(Error::InvalidFile("object not found in ObjStm"))
     }
 }
 
+fn read_uint(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0, |acc, &b| (acc << 8) | b as u64)
+}
+
 struct TableState {
     entry_num: i64,
     stream_pos: u64,
@@ -230,13 +525,20 @@ impl TableState {
     }
 }
 
-#[derive(Debug)]
-struct Entry { 
-    offset: i64, 
-    gen: i64, 
-    ty: Primitives, 
-    free: bool, 
-    uncompressed: bool 
+// For uncompressed objects `offset` is the byte offset and `gen` the
+// generation; for objects inside an object stream they are the number of the
+// object stream and the index within it.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub offset: i64,
+    pub gen: i64,
+    pub free: bool,
+    pub uncompressed: bool,
 }
 
-
+impl Entry {
+    // Objects in object streams always have generation 0.
+    pub fn generation(&self) -> i64 {
+        if self.free || self.uncompressed { self.gen } else { 0 }
+    }
+}